@@ -1,5 +1,5 @@
 use ass_lib::{
-    anyhow::{Context, Result},
+    anyhow::{bail, Context, Result},
     ShaderIR,
 };
 use std::path::PathBuf;
@@ -26,6 +26,16 @@ fn output_vulkan(ir: ShaderIR, options: CommandlineOptions) -> Result<()> {
 
     Ok(())
 }
+/// output compute shader as vulkan
+fn output_vulkan_compute(ir: ShaderIR, options: CommandlineOptions) -> Result<()> {
+    let vulkan = ass_vk::ComputeShader::from_ir(
+        ir,
+        ass_vk::Options {
+            verbose: options.verbose,
+        },
+    )?;
+    vulkan.write_to_disk(options.out_file)
+}
 fn output_wgl(ir: ShaderIR, options: CommandlineOptions) -> Result<()> {
     let glsl = ass_wgl::Shader::from_ir(
         ir,
@@ -42,9 +52,11 @@ fn run(options: CommandlineOptions) -> Result<()> {
             verbose: options.verbose,
         },
     )?;
-    match options.output_type {
-        OutputType::Vk => output_vulkan(ir, options),
-        OutputType::Wgl => output_wgl(ir, options),
+    match (options.output_type, options.compute) {
+        (OutputType::Vk, false) => output_vulkan(ir, options),
+        (OutputType::Vk, true) => output_vulkan_compute(ir, options),
+        (OutputType::Wgl, false) => output_wgl(ir, options),
+        (OutputType::Wgl, true) => bail!("compute shaders can not be output as wgl"),
     }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -84,6 +96,9 @@ struct CommandlineOptions {
     vertex_spv: Option<PathBuf>,
     #[structopt(long = "fragment-spv", parse(from_os_str))]
     fragment_spv: Option<PathBuf>,
+    /// Shader is a compute shader with a `cs_main` entry point
+    #[structopt(short = "c", long = "compute")]
+    compute: bool,
     ///Format of output
     #[structopt(short = "o", long = "output-type")]
     output_type: OutputType,
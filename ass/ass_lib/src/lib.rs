@@ -1,18 +1,28 @@
 mod shader_type;
 pub use anyhow;
 use anyhow::{bail, Context, Result};
-use ass_types::{VertexField, VertexInput};
+use ass_types::{BufferBinding, BufferKind, StorageBinding, VertexField, VertexInput};
 use naga::front::wgsl;
 use serde::Deserialize;
-pub use shader_type::{scalar_from_naga, type_from_naga};
+pub use shader_type::{scalar_from_naga, type_from_naga, type_size};
 use std::{fs::File, io::Read, path::Path};
 pub const VERTEX_SHADER_MAIN: &str = "vs_main";
 pub const FRAGMENT_SHADER_MAIN: &str = "fs_main";
+/// Entry point of compute shaders, declared with `[[stage(compute), workgroup_size(x, y, z)]]`
+pub const COMPUTE_SHADER_MAIN: &str = "cs_main";
+/// Group of the uniform holding per draw data, backends turn it into a push constant
+pub const PUSH_CONSTANT_GROUP: u32 = 0;
+/// Group of the first buffer, groups before it hold the push constant, textures and samplers
+pub const FIRST_BUFFER_GROUP: u32 = 2;
 
 #[derive(Deserialize)]
 pub struct Project {
     /// Path relative to project root specififying location of vertex shader
     pub shader_path: String,
+    /// Names of the vertex inputs read from each vertex buffer, the index is the
+    /// binding. Inputs not listed are read from binding 0.
+    #[serde(default)]
+    pub vertex_streams: Vec<Vec<String>>,
 }
 #[derive(Debug)]
 pub struct Options {
@@ -24,6 +34,8 @@ pub struct Options {
 pub struct ShaderIR {
     pub module: naga::Module,
     pub info: naga::valid::ModuleInfo,
+    /// Vertex streams from the project file
+    pub vertex_streams: Vec<Vec<String>>,
 }
 impl ShaderIR {
     pub fn compile_from_disk<P: AsRef<Path>>(path: P, options: Options) -> Result<Self> {
@@ -70,14 +82,140 @@ impl ShaderIR {
             println!("parsing shader string:\n\"\"\"\n{}\n\"\"\"", shader_string);
         }
 
-        let module = wgsl::parse_str(&shader_string).with_context(|| "failed to parse shader")?;
+        Self::from_wgsl(&shader_string, project_data.vertex_streams)
+    }
+    /// Parses and validates wgsl source
+    pub fn from_wgsl(source: &str, vertex_streams: Vec<Vec<String>>) -> Result<Self> {
+        let module = wgsl::parse_str(source).with_context(|| "failed to parse shader")?;
         let mut validator = naga::valid::Validator::new(
             naga::valid::ValidationFlags::all(),
             naga::valid::Capabilities::PUSH_CONSTANT,
         );
 
         let info = validator.validate(&module)?;
-        Ok(Self { module, info })
+        Ok(Self {
+            module,
+            info,
+            vertex_streams,
+        })
+    }
+    /// Uniform and read only storage buffers other than the push constant uniform in
+    /// `PUSH_CONSTANT_GROUP`. Buffers are ordered by their group and binding in the source and
+    /// each is given its own group starting at `FIRST_BUFFER_GROUP` with binding 0.
+    pub fn get_buffers(&self) -> Result<Vec<(naga::Handle<naga::GlobalVariable>, BufferBinding)>> {
+        let mut buffers = vec![];
+        for (handle, var) in self.module.global_variables.iter() {
+            let kind = match var.class {
+                naga::StorageClass::Uniform => BufferKind::Uniform,
+                naga::StorageClass::Storage { access } => {
+                    if access.contains(naga::StorageAccess::STORE) {
+                        bail!(
+                            "storage buffer \"{}\" must be read only",
+                            var.name.as_deref().unwrap_or("")
+                        );
+                    }
+                    BufferKind::Storage
+                }
+                _ => continue,
+            };
+            let binding = match var.binding.as_ref() {
+                Some(binding) => binding,
+                None => bail!(
+                    "buffer \"{}\" does not have a binding",
+                    var.name.as_deref().unwrap_or("")
+                ),
+            };
+            if kind == BufferKind::Uniform && binding.group == PUSH_CONSTANT_GROUP {
+                continue;
+            }
+            buffers.push((
+                handle,
+                (binding.group, binding.binding),
+                BufferBinding {
+                    name: var.name.clone().with_context(|| "buffers must be named")?,
+                    kind,
+                    group: 0,
+                    binding: 0,
+                    min_size: type_size(&self.module, var.ty),
+                },
+            ));
+        }
+        buffers.sort_by_key(|(_handle, source_binding, _buffer)| *source_binding);
+        Ok(buffers
+            .into_iter()
+            .enumerate()
+            .map(|(i, (handle, _source_binding, mut buffer))| {
+                buffer.group = FIRST_BUFFER_GROUP + i as u32;
+                (handle, buffer)
+            })
+            .collect())
+    }
+    /// Storage buffers of a compute shader. Buffers are ordered by their group and binding in
+    /// the source and each is given its own group starting at 0 with binding 0.
+    pub fn get_storage_bindings(
+        &self,
+    ) -> Result<Vec<(naga::Handle<naga::GlobalVariable>, StorageBinding)>> {
+        let mut buffers = vec![];
+        for (handle, var) in self.module.global_variables.iter() {
+            let name = var.name.as_deref().unwrap_or("");
+            let writable = match var.class {
+                naga::StorageClass::Storage { access } => {
+                    access.contains(naga::StorageAccess::STORE)
+                }
+                naga::StorageClass::Uniform => bail!(
+                    "compute shaders only read storage buffers, \"{}\" is a uniform",
+                    name
+                ),
+                _ => continue,
+            };
+            let binding = match var.binding.as_ref() {
+                Some(binding) => binding,
+                None => bail!("buffer \"{}\" does not have a binding", name),
+            };
+            buffers.push((
+                handle,
+                (binding.group, binding.binding),
+                StorageBinding {
+                    name: var.name.clone().with_context(|| "buffers must be named")?,
+                    group: 0,
+                    binding: 0,
+                    writable,
+                    min_size: type_size(&self.module, var.ty),
+                },
+            ));
+        }
+        buffers.sort_by_key(|(_handle, source_binding, _buffer)| *source_binding);
+        Ok(buffers
+            .into_iter()
+            .enumerate()
+            .map(|(i, (handle, _source_binding, mut buffer))| {
+                buffer.group = i as u32;
+                (handle, buffer)
+            })
+            .collect())
+    }
+    /// Gets the compute entry point, it must be the only one and be named `COMPUTE_SHADER_MAIN`
+    pub fn get_compute_entry_point(&self) -> Result<&naga::EntryPoint> {
+        let entry_points = self
+            .module
+            .entry_points
+            .iter()
+            .filter(|entry| entry.stage == naga::ShaderStage::Compute)
+            .collect::<Vec<_>>();
+        if entry_points.len() != 1 {
+            bail!(
+                "there must be one compute entry point in shader, got {} entry points",
+                entry_points.len()
+            );
+        }
+        if entry_points[0].name != COMPUTE_SHADER_MAIN {
+            bail!(
+                "compute entry point must be named \"{}\", got \"{}\"",
+                COMPUTE_SHADER_MAIN,
+                entry_points[0].name
+            );
+        }
+        Ok(entry_points[0])
     }
 
     /// Gets vertex input from shader
@@ -97,9 +235,16 @@ impl ShaderIR {
                 vertex_shader_entry_point.len()
             );
         }
-        let fields = vertex_shader_entry_point[0]
-            .function
-            .arguments
+        let arguments = &vertex_shader_entry_point[0].function.arguments;
+        for name in self.vertex_streams.iter().flatten() {
+            if !arguments.iter().any(|arg| arg.name.as_ref() == Some(name)) {
+                bail!(
+                    "vertex stream input \"{}\" is not a vertex shader input",
+                    name
+                );
+            }
+        }
+        let fields = arguments
             .iter()
             .map(|arg| VertexField {
                 ty: type_from_naga(
@@ -116,6 +261,11 @@ impl ShaderIR {
                     } => *location,
                 },
                 name: arg.name.as_ref().unwrap().clone(),
+                binding: self
+                    .vertex_streams
+                    .iter()
+                    .position(|stream| stream.contains(arg.name.as_ref().unwrap()))
+                    .unwrap_or(0) as u32,
             })
             .collect();
         Ok(VertexInput { binding: 0, fields })
@@ -124,9 +274,118 @@ impl ShaderIR {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    const BUFFER_SHADER: &str = r#"
+[[block]]
+struct Locals{
+    transform: mat4x4<f32>;
+};
+[[block]]
+struct Camera{
+    view: mat4x4<f32>;
+    time: f32;
+};
+[[block]]
+struct Joints{
+    matrices: array<mat4x4<f32>>;
+};
+[[group(0),binding(0)]]
+var<uniform> locals: Locals;
+[[group(3),binding(0)]]
+var<uniform> camera: Camera;
+[[group(2),binding(4)]]
+var<storage, read> joints: Joints;
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec4<f32>)->[[builtin(position)]] vec4<f32>{
+    return camera.view*joints.matrices[0]*locals.transform*position;
+}
+"#;
+    const COMPUTE_SHADER: &str = r#"
+[[block]]
+struct Heights{
+    values: array<f32>;
+};
+[[block]]
+struct Rain{
+    amount: f32;
+};
+[[group(1),binding(0)]]
+var<storage, read_write> heights: Heights;
+[[group(0),binding(2)]]
+var<storage, read> rain: Rain;
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>){
+    heights.values[id.x] = heights.values[id.x] + rain.amount;
+}
+"#;
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+    #[test]
+    fn reflects_buffers() {
+        let ir = ShaderIR::from_wgsl(BUFFER_SHADER, vec![]).unwrap();
+        let buffers = ir
+            .get_buffers()
+            .unwrap()
+            .into_iter()
+            .map(|(_handle, buffer)| buffer)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buffers,
+            vec![
+                BufferBinding {
+                    name: "joints".to_string(),
+                    kind: BufferKind::Storage,
+                    group: FIRST_BUFFER_GROUP,
+                    binding: 0,
+                    min_size: 0,
+                },
+                BufferBinding {
+                    name: "camera".to_string(),
+                    kind: BufferKind::Uniform,
+                    group: FIRST_BUFFER_GROUP + 1,
+                    binding: 0,
+                    min_size: 80,
+                },
+            ]
+        );
+    }
+    #[test]
+    fn reflects_compute_shader() {
+        let ir = ShaderIR::from_wgsl(COMPUTE_SHADER, vec![]).unwrap();
+        assert_eq!(
+            ir.get_compute_entry_point().unwrap().workgroup_size,
+            [64, 1, 1]
+        );
+        let buffers = ir
+            .get_storage_bindings()
+            .unwrap()
+            .into_iter()
+            .map(|(_handle, buffer)| buffer)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buffers,
+            vec![
+                StorageBinding {
+                    name: "rain".to_string(),
+                    group: 0,
+                    binding: 0,
+                    writable: false,
+                    min_size: 4,
+                },
+                StorageBinding {
+                    name: "heights".to_string(),
+                    group: 1,
+                    binding: 0,
+                    writable: true,
+                    min_size: 0,
+                },
+            ]
+        );
+        let vertex_ir = ShaderIR::from_wgsl(BUFFER_SHADER, vec![]).unwrap();
+        assert!(vertex_ir.get_compute_entry_point().is_err());
+        assert!(vertex_ir.get_storage_bindings().is_err());
+    }
 }
@@ -9,44 +9,14 @@ pub fn type_from_naga(
         &naga::TypeInner::Scalar { kind, width } => {
             Ok(ShaderType::Scalar(scalar_from_naga(&kind, width)?))
         }
-        &naga::TypeInner::Vector { size, kind, width } => match size {
-            naga::VectorSize::Bi => match kind {
-                naga::ScalarKind::Sint => bail!("signed int not supported"),
-                naga::ScalarKind::Uint => match width {
-                    4 => Ok(ShaderType::Vec2(Scalar::U32)),
-                    _ => bail!("invalid scalar width"),
-                },
-                naga::ScalarKind::Float => match width {
-                    4 => Ok(ShaderType::Vec2(Scalar::F32)),
-                    _ => bail!("invalid scalar width"),
-                },
-                naga::ScalarKind::Bool => bail!("bool not (yet) supported as kind for vec"),
-            },
-            naga::VectorSize::Tri => match kind {
-                naga::ScalarKind::Sint => bail!("signed int not supported"),
-                naga::ScalarKind::Uint => match width {
-                    4 => Ok(ShaderType::Vec3(Scalar::U32)),
-                    _ => bail!("invalid scalar width"),
-                },
-                naga::ScalarKind::Float => match width {
-                    4 => Ok(ShaderType::Vec3(Scalar::F32)),
-                    _ => bail!("invalid scalar width"),
-                },
-                naga::ScalarKind::Bool => bail!("bool not (yet) supported as kind for vec"),
-            },
-            naga::VectorSize::Quad => match kind {
-                naga::ScalarKind::Sint => bail!("signed int not supported"),
-                naga::ScalarKind::Uint => match width {
-                    4 => Ok(ShaderType::Vec4(Scalar::U32)),
-                    _ => bail!("invalid scalar width"),
-                },
-                naga::ScalarKind::Float => match width {
-                    4 => Ok(ShaderType::Vec4(Scalar::F32)),
-                    _ => bail!("invalid scalar width"),
-                },
-                naga::ScalarKind::Bool => bail!("bool not (yet) supported as kind for vec"),
-            },
-        },
+        &naga::TypeInner::Vector { size, kind, width } => {
+            let scalar = scalar_from_naga(&kind, width)?;
+            Ok(match size {
+                naga::VectorSize::Bi => ShaderType::Vec2(scalar),
+                naga::VectorSize::Tri => ShaderType::Vec3(scalar),
+                naga::VectorSize::Quad => ShaderType::Vec4(scalar),
+            })
+        }
         &naga::TypeInner::Matrix {
             columns,
             rows,
@@ -99,11 +69,66 @@ pub fn scalar_from_naga(kind: &naga::ScalarKind, width: u8) -> Result<Scalar> {
             _ => bail!("unsupported int width"),
         },
         &naga::ScalarKind::Float => match width {
+            2 => Ok(Scalar::F16),
             4 => Ok(Scalar::F32),
             8 => bail!("64 bit unsigned floats not yet supported"),
             _ => bail!("unsupported int width"),
         },
-        &naga::ScalarKind::Sint => bail!("signed ints not supported yet"),
+        &naga::ScalarKind::Sint => match width {
+            4 => Ok(Scalar::I32),
+            _ => bail!("unsupported int width"),
+        },
         &naga::ScalarKind::Bool => bail!("bools are not supported yet"),
     }
 }
+/// Size in bytes of a type in a buffer. Runtime sized arrays are counted as empty so a struct
+/// ending in one has the size of the members before it.
+pub fn type_size(module: &naga::Module, ty: naga::Handle<naga::Type>) -> u32 {
+    match &module.types[ty].inner {
+        naga::TypeInner::Scalar { width, .. } | naga::TypeInner::Atomic { width, .. } => {
+            *width as u32
+        }
+        naga::TypeInner::Vector { size, width, .. } => *size as u32 * *width as u32,
+        naga::TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => {
+            // columns are aligned as vec4 when there are three rows
+            let rows = match rows {
+                naga::VectorSize::Tri => 4,
+                rows => *rows as u32,
+            };
+            *columns as u32 * rows * *width as u32
+        }
+        naga::TypeInner::Array { size, stride, .. } => match size {
+            naga::ArraySize::Constant(constant) => match module.constants[*constant].inner {
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Uint(count),
+                    ..
+                } => count as u32 * stride,
+                naga::ConstantInner::Scalar {
+                    value: naga::ScalarValue::Sint(count),
+                    ..
+                } => count as u32 * stride,
+                _ => 0,
+            },
+            naga::ArraySize::Dynamic => 0,
+        },
+        naga::TypeInner::Struct { members, span, .. } => match members.last() {
+            Some(last)
+                if matches!(
+                    module.types[last.ty].inner,
+                    naga::TypeInner::Array {
+                        size: naga::ArraySize::Dynamic,
+                        ..
+                    }
+                ) =>
+            {
+                last.offset
+            }
+            _ => *span,
+        },
+        _ => 0,
+    }
+}
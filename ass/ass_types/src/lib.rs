@@ -5,9 +5,52 @@ use serde::{Deserialize, Serialize};
 /// Describes vertex input
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct VertexInput {
+    /// Binding of fields not assigned to a vertex stream
     pub binding: u32,
     pub fields: Vec<VertexField>,
 }
+impl VertexInput {
+    /// Vertex buffer bindings read by the fields in ascending order
+    pub fn bindings(&self) -> Vec<u32> {
+        let mut bindings = self.fields.iter().map(|f| f.binding).collect::<Vec<_>>();
+        bindings.sort_unstable();
+        bindings.dedup();
+        bindings
+    }
+}
+/// Kind of buffer read by a shader
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferKind {
+    /// `var<uniform>`, small data shared by every invocation
+    Uniform,
+    /// `var<storage, read>`, large or runtime sized read only data
+    Storage,
+}
+/// Uniform or storage buffer bound to a shader by name
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BufferBinding {
+    /// name of the global variable in the shader
+    pub name: String,
+    pub kind: BufferKind,
+    /// descriptor set of the buffer on vulkan, each buffer has its own set
+    pub group: u32,
+    pub binding: u32,
+    /// Size in bytes of the buffer's type, runtime sized arrays count as empty
+    pub min_size: u32,
+}
+/// Storage buffer read or written by a compute shader
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StorageBinding {
+    /// name of the global variable in the shader
+    pub name: String,
+    /// descriptor set of the buffer on vulkan, each buffer has its own set
+    pub group: u32,
+    pub binding: u32,
+    /// `var<storage, read_write>`, false for `var<storage, read>`
+    pub writable: bool,
+    /// Size in bytes of the buffer's type, runtime sized arrays count as empty
+    pub min_size: u32,
+}
 /// Describes a field in a vertex
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct VertexField {
@@ -16,6 +59,9 @@ pub struct VertexField {
     pub location: u32,
     /// name of field
     pub name: String,
+    /// Vertex buffer binding the field is read from
+    #[serde(default)]
+    pub binding: u32,
 }
 impl VertexField {
     pub fn size(&self) -> u32 {
@@ -26,12 +26,17 @@ impl ShaderType {
 pub enum Scalar {
     F32,
     U32,
+    I32,
+    /// 16 bit float
+    F16,
 }
 impl Scalar {
     pub fn size(&self) -> u32 {
         match *self {
             Self::F32 => std::mem::size_of::<f32>() as u32,
             Self::U32 => std::mem::size_of::<u32>() as u32,
+            Self::I32 => std::mem::size_of::<i32>() as u32,
+            Self::F16 => 2,
         }
     }
 }
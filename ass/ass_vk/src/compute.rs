@@ -0,0 +1,139 @@
+use super::Options;
+use anyhow::Result;
+use ass_lib::COMPUTE_SHADER_MAIN;
+use ass_types::StorageBinding;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Write, path::Path};
+/// Compute shader with a single entry point reading and writing storage buffers
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ComputeShader {
+    /// raw spirv compute shader data
+    pub spirv_data: Vec<u32>,
+    /// name of compute shader entrypoint
+    pub entrypoint: String,
+    /// invocations in each workgroup
+    pub workgroup_size: [u32; 3],
+    /// storage buffers, each in its own descriptor set
+    pub buffers: Vec<StorageBinding>,
+}
+impl ComputeShader {
+    /// Extension to use when writing out shader
+    const EXTENSION: &'static str = "ass_spv_compute";
+    pub fn from_ir(mut shader_ir: ass_lib::ShaderIR, options: Options) -> Result<Self> {
+        if options.verbose {
+            println!(
+                "initial intermediate representation:\n{:#?}",
+                shader_ir.module
+            );
+        }
+        let workgroup_size = shader_ir.get_compute_entry_point()?.workgroup_size;
+        let buffers = shader_ir
+            .get_storage_bindings()?
+            .into_iter()
+            .map(|(handle, buffer)| {
+                shader_ir.module.global_variables.get_mut(handle).binding =
+                    Some(naga::ResourceBinding {
+                        group: buffer.group,
+                        binding: buffer.binding,
+                    });
+                buffer
+            })
+            .collect::<Vec<_>>();
+        let mut validator = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::PUSH_CONSTANT,
+        );
+        let info = validator.validate(&shader_ir.module)?;
+        let spirv_data = naga::back::spv::write_vec(
+            &shader_ir.module,
+            &info,
+            &naga::back::spv::Options::default(),
+            Some(&naga::back::spv::PipelineOptions {
+                shader_stage: naga::ShaderStage::Compute,
+                entry_point: COMPUTE_SHADER_MAIN.to_string(),
+            }),
+        )?;
+        if options.verbose {
+            println!("output intermediate representation:");
+            println!("{:#?}", shader_ir.module);
+        }
+        Ok(Self {
+            spirv_data,
+            entrypoint: COMPUTE_SHADER_MAIN.to_string(),
+            workgroup_size,
+            buffers,
+        })
+    }
+    pub fn to_json_string(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+    /// Reads from json str, errors if parse
+    /// is unsucessfull
+    pub fn from_json_str(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+    /// writes to disk with extension ".ass_spv_compute"
+    pub fn write_to_disk<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let json_string = self.to_json_string()?;
+        let new_path = path.as_ref().with_extension(Self::EXTENSION);
+        let mut file = File::create(new_path)?;
+        file.write_all(json_string.as_bytes())?;
+        Ok(())
+    }
+    /// reads from disk, setting extension to ".ass_spv_compute"
+    pub fn read_from_disk<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let new_path = path.as_ref().with_extension(Self::EXTENSION);
+        let file = File::open(new_path)?;
+        let out: Self = serde_json::from_reader(file)?;
+        Ok(out)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ass_lib::ShaderIR;
+    #[test]
+    fn builds_compute_shader() {
+        let ir = ShaderIR::from_wgsl(
+            r#"
+[[block]]
+struct Particles{
+    positions: array<vec4<f32>>;
+};
+[[block]]
+struct Velocities{
+    values: array<vec4<f32>>;
+};
+[[group(0),binding(0)]]
+var<storage, read_write> particles: Particles;
+[[group(0),binding(1)]]
+var<storage, read> velocities: Velocities;
+[[stage(compute), workgroup_size(8, 8)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>){
+    let i = id.y * 8u + id.x;
+    particles.positions[i] = particles.positions[i] + velocities.values[i];
+}
+"#,
+            vec![],
+        )
+        .unwrap();
+        let shader = ComputeShader::from_ir(ir, Options::default()).unwrap();
+        assert_eq!(shader.workgroup_size, [8, 8, 1]);
+        assert_eq!(shader.entrypoint, COMPUTE_SHADER_MAIN);
+        assert!(!shader.spirv_data.is_empty());
+        let buffers = shader
+            .buffers
+            .iter()
+            .map(|buffer| (buffer.name.as_str(), buffer.group, buffer.writable))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buffers,
+            vec![("particles", 0, true), ("velocities", 1, false)]
+        );
+        let json = shader.to_json_string().unwrap();
+        assert_eq!(
+            ComputeShader::from_json_str(&json).unwrap().spirv_data,
+            shader.spirv_data
+        );
+    }
+}
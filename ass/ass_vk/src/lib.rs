@@ -1,10 +1,12 @@
 use anyhow::{bail, Result};
 pub use ass_lib;
-use ass_lib::{type_from_naga, FRAGMENT_SHADER_MAIN, VERTEX_SHADER_MAIN};
-use ass_types::{ShaderType, VertexField, VertexInput};
+use ass_lib::{type_from_naga, FRAGMENT_SHADER_MAIN, PUSH_CONSTANT_GROUP, VERTEX_SHADER_MAIN};
+use ass_types::{BufferBinding, ShaderType, VertexField, VertexInput};
 use serde::{Deserialize, Serialize};
 use std::{fs::File, io::Write, path::Path};
 use thiserror::Error;
+mod compute;
+pub use compute::ComputeShader;
 #[derive(Debug, Error)]
 pub enum VulkanConvertError {
     #[error("shader has zero push constants")]
@@ -54,6 +56,9 @@ pub struct Shader {
     pub textures: Vec<Texture>,
     /// samplers used
     pub samplers: Vec<Sampler>,
+    /// uniform and storage buffers, each in its own descriptor set
+    #[serde(default)]
+    pub buffers: Vec<BufferBinding>,
     /// name of vetex shader entrypoing
     pub vertex_entrypoint: String,
     /// name of fragment shader entrypoing
@@ -130,12 +135,28 @@ impl Shader {
             );
         }
         Self::validate(&shader_ir)?;
+        let buffers = shader_ir
+            .get_buffers()?
+            .into_iter()
+            .map(|(handle, buffer)| {
+                shader_ir.module.global_variables.get_mut(handle).binding =
+                    Some(naga::ResourceBinding {
+                        group: buffer.group,
+                        binding: buffer.binding,
+                    });
+                buffer
+            })
+            .collect::<Vec<_>>();
         let push_constants = shader_ir
             .module
             .global_variables
             .iter_mut()
             .map(|(_h, variable)| variable)
-            .filter(|variable| variable.class == naga::StorageClass::Uniform)
+            .filter(|variable| {
+                variable.class == naga::StorageClass::Uniform
+                    && variable.binding.as_ref().map(|binding| binding.group)
+                        == Some(PUSH_CONSTANT_GROUP)
+            })
             .map(|variable| {
                 variable.class = naga::StorageClass::PushConstant;
                 variable.binding = None;
@@ -149,7 +170,7 @@ impl Shader {
             bail!("Zero push constants in shader");
         }
         if push_constants.len() > 1 {
-            bail!("more then one push constant in shader, there must only ge one push constant per shader")
+            bail!("more then one uniform in group {}, there must only be one push constant per shader", PUSH_CONSTANT_GROUP)
         }
         let push_type: ShaderType = type_from_naga(
             shader_ir
@@ -250,6 +271,7 @@ impl Shader {
             fragment_spirv_data,
             vertex_spirv_data,
             samplers,
+            buffers,
             vertex_entrypoint,
             fragment_entrypoint,
 
@@ -320,9 +342,75 @@ impl Shader {
 }
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use ass_lib::{ShaderIR, FIRST_BUFFER_GROUP};
+    use ass_types::BufferKind;
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+    #[test]
+    fn keeps_buffers_out_of_push_constant() {
+        let ir = ShaderIR::from_wgsl(
+            r#"
+struct VertexOutput{
+    [[location(0)]] tex_coord: vec2<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+[[block]]
+struct Locals{
+    transform: mat4x4<f32>;
+};
+[[block]]
+struct Camera{
+    view: mat4x4<f32>;
+};
+[[block]]
+struct Lights{
+    colors: array<vec4<f32>>;
+};
+[[group(0),binding(3)]]
+var<uniform> locals: Locals;
+[[group(5),binding(0)]]
+var<uniform> camera: Camera;
+[[group(5),binding(1)]]
+var<storage, read> lights: Lights;
+[[stage(vertex)]]
+fn vs_main(
+	[[location(0)]] position: vec3<f32>,
+	[[location(1)]] tex_coord: vec2<f32>,
+)->VertexOutput{
+    var out: VertexOutput;
+    out.tex_coord=tex_coord;
+    out.position = camera.view*locals.transform*vec4<f32>(position,1.0);
+    return out;
+}
+[[group(0),binding(0)]]
+var mesh_texture: texture_2d<f32>;
+[[group(0),binding(1)]]
+var sampler: sampler;
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput)->[[location(0)]]vec4<f32>{
+    return textureSample(mesh_texture,sampler,in.tex_coord)*lights.colors[0];
+}
+"#,
+            vec![],
+        )
+        .unwrap();
+        let shader = Shader::from_ir(ir, Options::default()).unwrap();
+        assert_eq!(shader.push_constant.size(), 64);
+        let buffers = shader
+            .buffers
+            .iter()
+            .map(|buffer| (buffer.name.as_str(), buffer.kind, buffer.group))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buffers,
+            vec![
+                ("camera", BufferKind::Uniform, FIRST_BUFFER_GROUP),
+                ("lights", BufferKind::Storage, FIRST_BUFFER_GROUP + 1),
+            ]
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use anyhow::{bail, Result};
 use ass_lib::ShaderIR;
-use ass_types::{VertexField, VertexInput};
+use ass_types::{BufferBinding, BufferKind, VertexField, VertexInput};
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
 
@@ -19,6 +19,13 @@ enum ShaderStage {
     Vertex,
     Fragment,
 }
+/// Uniform buffer read through a uniform block
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct UniformBlock {
+    pub buffer: BufferBinding,
+    /// name of the block in the generated glsl
+    pub block_name: String,
+}
 /// GLSL shader module
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub struct Shader {
@@ -27,6 +34,9 @@ pub struct Shader {
     pub texture_name: String,
     pub uniform_name: String,
     pub vertex_input: VertexInput,
+    /// uniform buffers other than the push constant uniform
+    #[serde(default)]
+    pub buffers: Vec<UniformBlock>,
 }
 impl Shader {
     const EXTENSION: &'static str = "ass_glsl";
@@ -170,9 +180,36 @@ impl Shader {
         }
         todo!()
     }
+    /// Finds the block name of each buffer, webgl2 has no storage buffers
+    fn get_uniform_blocks(
+        buffers: &[(naga::Handle<naga::GlobalVariable>, BufferBinding)],
+        reflections: &[&naga::back::glsl::ReflectionInfo],
+    ) -> Result<Vec<UniformBlock>> {
+        buffers
+            .iter()
+            .map(|(handle, buffer)| {
+                if buffer.kind == BufferKind::Storage {
+                    bail!(
+                        "storage buffer \"{}\" is not supported by webgl2",
+                        buffer.name
+                    );
+                }
+                let block_name = reflections
+                    .iter()
+                    .find_map(|reflection| reflection.uniforms.get(handle))
+                    .cloned()
+                    .unwrap_or_else(|| buffer.name.clone());
+                Ok(UniformBlock {
+                    buffer: buffer.clone(),
+                    block_name,
+                })
+            })
+            .collect()
+    }
     fn get_uniform_name(
         ir: &ShaderIR,
         reflection: &naga::back::glsl::ReflectionInfo,
+        buffers: &[(naga::Handle<naga::GlobalVariable>, BufferBinding)],
         options: &Options,
     ) -> Result<String> {
         if options.verbose {
@@ -184,13 +221,15 @@ impl Shader {
                 );
             }
         }
-        if reflection.uniforms.len() != 1 {
-            bail!(
-                "there must be 1 uniform got {} uniforms",
-                reflection.uniforms.len()
-            );
+        let uniforms = reflection
+            .uniforms
+            .iter()
+            .filter(|(handle, _name)| !buffers.iter().any(|(buffer, _)| buffer == *handle))
+            .collect::<Vec<_>>();
+        if uniforms.len() != 1 {
+            bail!("there must be 1 uniform got {} uniforms", uniforms.len());
         }
-        Ok(reflection.uniforms.iter().next().unwrap().1.to_string())
+        Ok(uniforms[0].1.to_string())
     }
     fn get_texture_name(
         reflection: &naga::back::glsl::ReflectionInfo,
@@ -246,6 +285,7 @@ impl Shader {
         Ok((buffer, info))
     }
     pub fn from_ir(ir: ShaderIR, options: Options) -> Result<Self> {
+        let buffers = ir.get_buffers()?;
         let ir = Self::flatten_uniform_struct(ir, &options)?;
         let (fragment_shader, frag_info) =
             Self::write_string(&ir, &options, ShaderStage::Fragment)?;
@@ -266,7 +306,8 @@ impl Shader {
             println!("vertex shader:\n{}", vertex_shader)
         }
         let vertex_input = ir.get_vertex_input()?;
-        let uniform_name = Self::get_uniform_name(&ir, &vert_info, &options)?;
+        let uniform_name = Self::get_uniform_name(&ir, &vert_info, &buffers, &options)?;
+        let buffers = Self::get_uniform_blocks(&buffers, &[&vert_info, &frag_info])?;
 
         Ok(Self {
             fragment_shader,
@@ -274,6 +315,7 @@ impl Shader {
             vertex_input,
             texture_name,
             uniform_name,
+            buffers,
         })
     }
     pub fn to_json_string(&self) -> Result<String> {
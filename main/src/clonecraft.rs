@@ -7,7 +7,7 @@ use std::{
 };
 use sukakpak::{
     anyhow::Result, image, nalgebra as na, Bindable, Context, ContextTrait, DrawableTexture, Event,
-    Framebuffer, Mesh, MeshAsset, MouseButton, Texture,
+    Framebuffer, FramebufferDesc, Mesh, MeshAsset, MouseButton, Texture,
 };
 
 pub struct CloneCraft {
@@ -101,7 +101,7 @@ impl sukakpak::Renderable for CloneCraft {
                 image::Rgba([0, 0, 255, 0]),
             ))
             .expect("failed to build texture");
-        let sphere_obj = MeshAsset::from_obj("sphere.obj").expect("");
+        let sphere_obj = MeshAsset::new_uv_sphere(1.0, 32, 16);
         let sphere = context
             .build_mesh(sphere_obj, DrawableTexture::Texture(&red_texture))
             .expect("failed to build circle");
@@ -140,10 +140,10 @@ impl sukakpak::Renderable for CloneCraft {
                 .expect("failed to create image");
         }
         let framebuffer = context
-            .build_framebuffer(na::Vector2::new(300, 300))
+            .build_framebuffer(FramebufferDesc::new(na::Vector2::new(300, 300)))
             .expect("failed to build frame buffer");
         let alt_fb = context
-            .build_framebuffer(na::Vector2::new(1000, 1000))
+            .build_framebuffer(FramebufferDesc::new(na::Vector2::new(1000, 1000)))
             .expect("failed to build frame buffer");
         let alt_fb_mesh = context
             .build_mesh(
@@ -1,4 +1,7 @@
-use sukakpak::{nalgebra::Vector2, run, CreateInfo, Sukakpak};
+use std::path::PathBuf;
+use sukakpak::{
+    nalgebra::Vector2, run, CreateInfo, DevicePreference, PresentMode, SampleCount, Sukakpak,
+};
 
 mod clonecraft;
 fn main() {
@@ -6,5 +9,12 @@ fn main() {
         default_size: Vector2::new(800, 800),
         name: "clonecraft".to_string(),
         window_id: "canvas".to_string(),
+        vulkan_sdk_path: None,
+        pipeline_cache_dir: Some(PathBuf::from("cache")),
+        present_mode: PresentMode::NoVsync,
+        swapchain_images: None,
+        max_frames_in_flight: 2,
+        sample_count: SampleCount::X4,
+        device_preference: DevicePreference::Discrete,
     });
 }
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use structopt::StructOpt;
+use sukakpak::{
+    anyhow::{bail, Context, Result},
+    GltfScene, MeshAsset, MeshCompression, ObjScene,
+};
+/// Loads every mesh in the file, glTF primitives are flattened in order
+fn load_meshes(input: &str) -> Result<Vec<MeshAsset>> {
+    let extension = PathBuf::from(input)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "obj" => Ok(ObjScene::from_obj(input)?
+            .models
+            .drain(..)
+            .map(|model| model.mesh)
+            .collect()),
+        "gltf" | "glb" => Ok(GltfScene::from_gltf(input)?
+            .meshes
+            .drain(..)
+            .flat_map(|mesh| mesh.primitives.into_iter().map(|p| p.mesh))
+            .collect()),
+        _ => bail!(
+            "unsupported input \"{}\", valid inputs are .obj, .gltf and .glb",
+            input
+        ),
+    }
+}
+fn run(options: CommandlineOptions) -> Result<()> {
+    let input = options.input_file.to_string_lossy().to_string();
+    let mut meshes = load_meshes(&input).with_context(|| format!("failed to load {}", input))?;
+    if options.index >= meshes.len() {
+        bail!(
+            "mesh index {} out of range, {} contains {} meshes",
+            options.index,
+            input,
+            meshes.len()
+        );
+    }
+    let mesh = meshes.swap_remove(options.index);
+    let compression = if options.compress {
+        MeshCompression::Deflate
+    } else {
+        MeshCompression::None
+    };
+    mesh.save(&options.out_file, compression)?;
+    if options.verbose {
+        println!(
+            "wrote {} vertices and {} indices to {}",
+            mesh.vertices.len() / mesh.vertex_layout.vertex_size(),
+            mesh.indices.len(),
+            options.out_file.display()
+        );
+    }
+    Ok(())
+}
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "mesh_bin",
+    about = "Converts OBJ and glTF files to Sukakpak mesh caches"
+)]
+struct CommandlineOptions {
+    /// Input OBJ or glTF file
+    #[structopt(parse(from_os_str))]
+    input_file: PathBuf,
+    /// Where to write mesh cache
+    #[structopt(parse(from_os_str))]
+    out_file: PathBuf,
+    /// Index of mesh to convert, OBJ objects and glTF primitives are counted in file order
+    #[structopt(short = "i", long = "index", default_value = "0")]
+    index: usize,
+    /// Deflate vertex and index data
+    #[structopt(short = "c", long = "compress")]
+    compress: bool,
+    /// Verbose output
+    #[structopt(short = "V", long = "verbose")]
+    verbose: bool,
+}
+fn main() {
+    let opt = CommandlineOptions::from_args();
+    run(opt).expect("failed to convert mesh");
+}
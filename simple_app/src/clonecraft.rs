@@ -1,5 +1,7 @@
 use std::time::Duration;
-use sukakpak::{image, nalgebra, Context, ContextTrait, DrawableTexture, Event, MeshAsset};
+use sukakpak::{
+    image, nalgebra, Context, ContextTrait, DrawableTexture, Event, FramebufferDesc, MeshAsset,
+};
 pub struct CloneCraft {
     triangle: sukakpak::Mesh,
     framebuffer: sukakpak::Framebuffer,
@@ -23,7 +25,7 @@ impl sukakpak::Renderable for CloneCraft {
             )
             .expect("failed to build mesh");
         let framebuffer = context
-            .build_framebuffer(nalgebra::Vector2::new(100, 100))
+            .build_framebuffer(FramebufferDesc::new(nalgebra::Vector2::new(100, 100)))
             .expect("failed to build");
         let plane = context
             .build_mesh(
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
-use sukakpak::{nalgebra::Vector2, CreateInfo, Sukakpak};
+use sukakpak::{
+    nalgebra::Vector2, CreateInfo, DevicePreference, PresentMode, SampleCount, Sukakpak,
+};
 
 mod clonecraft;
 fn main() {
@@ -8,6 +10,12 @@ fn main() {
         default_size: Vector2::new(800, 800),
         name: "clonecraft".to_string(),
         vulkan_sdk_path: None,
+        pipeline_cache_dir: Some(PathBuf::from("cache")),
+        present_mode: PresentMode::NoVsync,
+        swapchain_images: None,
+        max_frames_in_flight: 2,
+        sample_count: SampleCount::X4,
+        device_preference: DevicePreference::Discrete,
         //vulkan_sdk_path: Some(PathBuf::from("C:/VulkanSDK/1.3.268.0/Lib")),
     });
 }
@@ -0,0 +1,29 @@
+struct VertexOutput{
+    [[location(0)]] color: vec4<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+[[block]]
+struct Locals{
+    view_projection: mat4x4<f32>;
+};
+[[group(0),binding(3)]]
+var<uniform> locals: Locals;
+[[stage(vertex)]]
+fn vs_main(
+	[[location(0)]] position: vec3<f32>,
+	[[location(1)]] color: vec4<f32>,
+)->VertexOutput{
+    var out: VertexOutput;
+    out.color=color;
+    out.position = locals.view_projection*vec4<f32>(position,1.0);
+    return out;
+}
+[[group(0),binding(0)]]
+var mesh_texture: texture_2d<f32>;
+[[group(0),binding(1)]]
+var sampler: sampler;
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput)->[[location(0)]]vec4<f32>{
+    let texture= textureSample(mesh_texture,sampler,vec2<f32>(0.5,0.5));
+    return in.color*texture;
+}
@@ -0,0 +1,573 @@
+//! Skeletal animation. Clips are sampled on the CPU into a `Pose`, which
+//! `Skeleton::joint_matrices` turns into the matrices used for skinning. Skinned
+//! meshes store joint indices in a `Vec4U16` component followed by weights in a
+//! `Vec4F32` component, as loaded by `GltfScene`.
+//!
+//! Meshes are skinned on the GPU by uploading `joint_matrix_bytes` to a
+//! `BufferKind::Storage` buffer read by the vertex shader, updated each frame
+//! with `ContextTrait::update_buffer`. `skin_mesh` does the same on the CPU.
+use super::{MeshAsset, VertexComponent};
+use anyhow::{anyhow, bail, Result};
+use nalgebra::{Matrix4, Point3, Quaternion, UnitQuaternion, Vector3, Vector4};
+/// Local transform of a joint relative to its parent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+impl JointTransform {
+    pub fn identity() -> Self {
+        Self {
+            translation: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+    /// Interpolates between transforms, `t` of 0 gives `self`
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: slerp(&self.rotation, &other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+fn slerp(a: &UnitQuaternion<f32>, b: &UnitQuaternion<f32>, t: f32) -> UnitQuaternion<f32> {
+    // take the short way around
+    let b = if a.coords.dot(&b.coords) < 0.0 {
+        UnitQuaternion::new_unchecked(-b.into_inner())
+    } else {
+        *b
+    };
+    a.try_slerp(&b, t, 1.0e-6).unwrap_or_else(|| a.nlerp(&b, t))
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    pub name: Option<String>,
+    /// Index of parent joint
+    pub parent: Option<usize>,
+    /// Transform used when no animation affects the joint
+    pub rest: JointTransform,
+    /// Transforms from model space to the joint's space in the bind pose
+    pub inverse_bind_matrix: Matrix4<f32>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skeleton {
+    /// Order matches joint indices stored in vertices
+    joints: Vec<Joint>,
+    /// Transform applied above root joints
+    root_transform: Matrix4<f32>,
+    /// Joint indices with parents before their children
+    order: Vec<usize>,
+}
+/// Local transform of every joint in a skeleton
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+    pub joints: Vec<JointTransform>,
+}
+impl Pose {
+    /// Interpolates every joint, `t` of 0 gives `self`. Poses must be from the same
+    /// skeleton.
+    pub fn blend(&self, other: &Self, t: f32) -> Self {
+        Self {
+            joints: self
+                .joints
+                .iter()
+                .zip(other.joints.iter())
+                .map(|(a, b)| a.interpolate(b, t))
+                .collect(),
+        }
+    }
+}
+impl Skeleton {
+    /// Fails if a parent is out of range or the joints form a cycle
+    pub fn new(joints: Vec<Joint>, root_transform: Matrix4<f32>) -> Result<Self> {
+        let mut order = Vec::with_capacity(joints.len());
+        let mut visited = vec![false; joints.len()];
+        for start in 0..joints.len() {
+            // walk up to the first visited ancestor then add joints top down
+            let mut chain = vec![];
+            let mut current = Some(start);
+            while let Some(index) = current {
+                if visited[index] {
+                    break;
+                }
+                if chain.contains(&index) {
+                    bail!("joint {} is its own ancestor", index)
+                }
+                chain.push(index);
+                current = joints[index].parent;
+                if let Some(parent) = current {
+                    if parent >= joints.len() {
+                        bail!("parent {} of joint {} is out of range", parent, index)
+                    }
+                }
+            }
+            for index in chain.iter().rev() {
+                visited[*index] = true;
+                order.push(*index);
+            }
+        }
+        Ok(Self {
+            joints,
+            root_transform,
+            order,
+        })
+    }
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+    pub fn root_transform(&self) -> Matrix4<f32> {
+        self.root_transform
+    }
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            joints: self.joints.iter().map(|j| j.rest).collect(),
+        }
+    }
+    pub fn find_joint(&self, name: &str) -> Option<usize> {
+        self.joints
+            .iter()
+            .position(|j| j.name.as_deref() == Some(name))
+    }
+    /// Model space transform of every joint
+    pub fn world_transforms(&self, pose: &Pose) -> Vec<Matrix4<f32>> {
+        let mut world = vec![Matrix4::identity(); self.joints.len()];
+        for index in self.order.iter() {
+            let parent = match self.joints[*index].parent {
+                Some(parent) => world[parent],
+                None => self.root_transform,
+            };
+            world[*index] = parent * pose.joints[*index].to_matrix();
+        }
+        world
+    }
+    /// Matrices moving vertices from the bind pose to `pose`
+    pub fn joint_matrices(&self, pose: &Pose) -> Vec<Matrix4<f32>> {
+        self.world_transforms(pose)
+            .iter()
+            .zip(self.joints.iter())
+            .map(|(world, joint)| world * joint.inverse_bind_matrix)
+            .collect()
+    }
+}
+/// Packs matrices as column major `f32`s, matching an array of `mat4` in both
+/// std140 and std430 layouts
+pub fn joint_matrix_bytes(matrices: &[Matrix4<f32>]) -> Vec<u8> {
+    matrices
+        .iter()
+        .flat_map(|m| m.as_slice().iter())
+        .flat_map(|f| f.to_ne_bytes())
+        .collect()
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelProperty {
+    Translation,
+    Rotation,
+    Scale,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    /// Hermite spline, every keyframe stores in tangent, value and out tangent
+    CubicSpline,
+}
+/// Keyframes animating one property of one joint
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    /// Joint index for `AnimationClip`, node index for `GltfAnimation`
+    pub target: usize,
+    pub property: ChannelProperty,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds, sorted
+    pub times: Vec<f32>,
+    /// Translation and scale use xyz, rotations are quaternions in ijkw order
+    pub values: Vec<Vector4<f32>>,
+}
+impl Channel {
+    /// Value of keyframe `index`, skipping cubic spline tangents
+    fn value(&self, index: usize) -> Vector4<f32> {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[index * 3 + 1],
+            _ => self.values[index],
+        }
+    }
+    /// Value at `time`, clamped to the first and last keyframes. `None` if the
+    /// channel has no keyframes.
+    pub fn sample(&self, time: f32) -> Option<Vector4<f32>> {
+        let last = self.times.len().checked_sub(1)?;
+        if time <= self.times[0] {
+            return Some(self.value(0));
+        }
+        if time >= self.times[last] {
+            return Some(self.value(last));
+        }
+        let next = self.times.partition_point(|t| *t <= time);
+        let previous = next - 1;
+        let delta = self.times[next] - self.times[previous];
+        let t = (time - self.times[previous]) / delta;
+        let (a, b) = (self.value(previous), self.value(next));
+        Some(match self.interpolation {
+            Interpolation::Step => a,
+            Interpolation::Linear => match self.property {
+                ChannelProperty::Rotation => {
+                    let to_quat =
+                        |v: Vector4<f32>| UnitQuaternion::from_quaternion(Quaternion::from(v));
+                    slerp(&to_quat(a), &to_quat(b), t).coords
+                }
+                _ => a.lerp(&b, t),
+            },
+            Interpolation::CubicSpline => {
+                let out_tangent = self.values[previous * 3 + 2] * delta;
+                let in_tangent = self.values[next * 3] * delta;
+                let (t2, t3) = (t * t, t * t * t);
+                let value = a * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + out_tangent * (t3 - 2.0 * t2 + t)
+                    + b * (-2.0 * t3 + 3.0 * t2)
+                    + in_tangent * (t3 - t2);
+                match self.property {
+                    ChannelProperty::Rotation => value.normalize(),
+                    _ => value,
+                }
+            }
+        })
+    }
+    /// Writes the sampled value into the matching property of `transform`, a
+    /// channel without keyframes leaves it unchanged
+    pub fn apply(&self, time: f32, transform: &mut JointTransform) {
+        let value = match self.sample(time) {
+            Some(value) => value,
+            None => return,
+        };
+        match self.property {
+            ChannelProperty::Translation => transform.translation = value.xyz(),
+            ChannelProperty::Rotation => {
+                transform.rotation = UnitQuaternion::from_quaternion(Quaternion::from(value))
+            }
+            ChannelProperty::Scale => transform.scale = value.xyz(),
+        }
+    }
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// Length in seconds
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+impl AnimationClip {
+    /// Builds clip, duration is the time of the last keyframe. Fails if a channel
+    /// has no keyframes or the wrong number of values.
+    pub fn new(name: Option<String>, channels: Vec<Channel>) -> Result<Self> {
+        for channel in channels.iter() {
+            let values_per_key = match channel.interpolation {
+                Interpolation::CubicSpline => 3,
+                _ => 1,
+            };
+            if channel.times.is_empty()
+                || channel.values.len() != channel.times.len() * values_per_key
+            {
+                bail!(
+                    "channel targeting {} has {} keyframes and {} values",
+                    channel.target,
+                    channel.times.len(),
+                    channel.values.len()
+                )
+            }
+        }
+        let duration = channels
+            .iter()
+            .filter_map(|c| c.times.last().copied())
+            .fold(0.0, f32::max);
+        Ok(Self {
+            name,
+            duration,
+            channels,
+        })
+    }
+    /// Samples clip at `time` seconds, joints without channels keep their rest
+    /// transform. If `looping` time wraps around at the end of the clip.
+    pub fn sample(&self, skeleton: &Skeleton, time: f32, looping: bool) -> Pose {
+        let mut pose = skeleton.rest_pose();
+        self.sample_into(time, looping, &mut pose);
+        pose
+    }
+    /// Overwrites animated joints of `pose`
+    pub fn sample_into(&self, time: f32, looping: bool, pose: &mut Pose) {
+        let time = if looping && self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            time
+        };
+        for channel in self.channels.iter() {
+            if let Some(joint) = pose.joints.get_mut(channel.target) {
+                channel.apply(time, joint);
+            }
+        }
+    }
+}
+/// Skins mesh on the CPU. The first component is transformed as a position and
+/// other `Vec3F32` components as directions, which covers normals. Joint indices
+/// are read from the first `Vec4U16` component and weights from the `Vec4F32`
+/// after it.
+pub fn skin_mesh(mesh: &MeshAsset, joint_matrices: &[Matrix4<f32>]) -> Result<MeshAsset> {
+    let layout = &mesh.vertex_layout;
+    let joint_component = layout
+        .components
+        .iter()
+        .position(|c| *c == VertexComponent::Vec4U16)
+        .ok_or_else(|| anyhow!("mesh does not have joint indices"))?;
+    if layout.components.get(joint_component + 1) != Some(&VertexComponent::Vec4F32) {
+        bail!("joint indices are not followed by weights")
+    }
+    if layout.components.first() != Some(&VertexComponent::Vec3F32) {
+        bail!("first component of skinned mesh must be a Vec3F32 position")
+    }
+    let joint_offset = layout.component_offset(joint_component);
+    let weight_offset = layout.component_offset(joint_component + 1);
+    let read_f32 = |b: &[u8], offset: usize| {
+        f32::from_ne_bytes([b[offset], b[offset + 1], b[offset + 2], b[offset + 3]])
+    };
+    let mut out = mesh.clone();
+    let vertex_size = layout.vertex_size();
+    for vertex in out.vertices.chunks_exact_mut(vertex_size) {
+        let mut skin = Matrix4::zeros();
+        for i in 0..4 {
+            let joint = u16::from_ne_bytes([
+                vertex[joint_offset + i * 2],
+                vertex[joint_offset + i * 2 + 1],
+            ]) as usize;
+            let weight = read_f32(vertex, weight_offset + i * 4);
+            if weight != 0.0 {
+                let matrix = joint_matrices.get(joint).ok_or_else(|| {
+                    anyhow!(
+                        "joint {} out of range, {} matrices given",
+                        joint,
+                        joint_matrices.len()
+                    )
+                })?;
+                skin += matrix * weight;
+            }
+        }
+        let normal_matrix = skin
+            .fixed_view::<3, 3>(0, 0)
+            .try_inverse()
+            .map(|m| m.transpose())
+            .unwrap_or_else(|| skin.fixed_view::<3, 3>(0, 0).into_owned());
+        for (index, component) in layout.components.iter().enumerate() {
+            if *component != VertexComponent::Vec3F32 {
+                continue;
+            }
+            let offset = layout.component_offset(index);
+            let value = Vector3::new(
+                read_f32(vertex, offset),
+                read_f32(vertex, offset + 4),
+                read_f32(vertex, offset + 8),
+            );
+            let skinned = if index == 0 {
+                skin.transform_point(&Point3::from(value)).coords
+            } else {
+                (normal_matrix * value)
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or(value)
+            };
+            for (i, f) in skinned.iter().enumerate() {
+                vertex[offset + i * 4..offset + i * 4 + 4].copy_from_slice(&f.to_ne_bytes());
+            }
+        }
+    }
+    Ok(out)
+}
+/// Appends joint indices and weights to every vertex of `mesh`, giving the layout
+/// read by `skin_mesh`. `skin` is called with the position of each vertex, which
+/// is read from the first component.
+pub fn with_skin<F: Fn(Vector3<f32>) -> ([u16; 4], [f32; 4])>(
+    mesh: &MeshAsset,
+    skin: F,
+) -> Result<MeshAsset> {
+    let positions = mesh
+        .positions()
+        .ok_or_else(|| anyhow!("mesh does not have positions"))?;
+    let vertex_size = mesh.vertex_layout.vertex_size();
+    let mut vertices = Vec::with_capacity(mesh.vertices.len() + positions.len() * 24);
+    for (vertex, position) in mesh.vertices.chunks_exact(vertex_size).zip(positions) {
+        let (joints, weights) = skin(position);
+        vertices.extend_from_slice(vertex);
+        vertices.extend(joints.iter().flat_map(|j| j.to_ne_bytes()));
+        vertices.extend(weights.iter().flat_map(|w| w.to_ne_bytes()));
+    }
+    let mut vertex_layout = mesh.vertex_layout.clone();
+    vertex_layout.components.push(VertexComponent::Vec4U16);
+    vertex_layout.components.push(VertexComponent::Vec4F32);
+    Ok(MeshAsset {
+        vertices,
+        indices: mesh.indices.clone(),
+        vertex_layout,
+        topology: mesh.topology,
+    })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Topology, VertexLayout};
+    /// Two joint arm along +y, the second joint starts at y = 1
+    fn arm() -> Skeleton {
+        let elbow = Vector3::new(0.0, 1.0, 0.0);
+        Skeleton::new(
+            vec![
+                Joint {
+                    name: Some("shoulder".to_string()),
+                    parent: None,
+                    rest: JointTransform::identity(),
+                    inverse_bind_matrix: Matrix4::identity(),
+                },
+                Joint {
+                    name: Some("elbow".to_string()),
+                    parent: Some(0),
+                    rest: JointTransform {
+                        translation: elbow,
+                        ..JointTransform::identity()
+                    },
+                    inverse_bind_matrix: Matrix4::new_translation(&-elbow),
+                },
+            ],
+            Matrix4::identity(),
+        )
+        .unwrap()
+    }
+    fn bend() -> AnimationClip {
+        let quarter =
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2);
+        AnimationClip::new(
+            Some("bend".to_string()),
+            vec![Channel {
+                target: 1,
+                property: ChannelProperty::Rotation,
+                interpolation: Interpolation::Linear,
+                times: vec![0.0, 1.0],
+                values: vec![UnitQuaternion::identity().coords, quarter.coords],
+            }],
+        )
+        .unwrap()
+    }
+    #[test]
+    fn samples_and_skins() {
+        let skeleton = arm();
+        let mut cycle = skeleton.joints().to_vec();
+        cycle[0].parent = Some(1);
+        assert!(Skeleton::new(cycle, Matrix4::identity()).is_err());
+        let clip = bend();
+        assert_eq!(clip.duration, 1.0);
+        let rest = skeleton.joint_matrices(&clip.sample(&skeleton, 0.0, false));
+        for m in rest.iter() {
+            assert!((m - Matrix4::identity()).norm() < 1e-5);
+        }
+        let half = clip.sample(&skeleton, 0.5, false);
+        let angle = half.joints[1].rotation.angle();
+        assert!((angle - std::f32::consts::FRAC_PI_4).abs() < 1e-5);
+        // looping wraps 1.5 back to 0.5
+        assert_eq!(clip.sample(&skeleton, 1.5, true), half);
+
+        let mut vertices = vec![];
+        for f in [0.0f32, 2.0, 0.0] {
+            vertices.extend_from_slice(&f.to_ne_bytes());
+        }
+        for j in [1u16, 0, 0, 0] {
+            vertices.extend_from_slice(&j.to_ne_bytes());
+        }
+        for w in [1.0f32, 0.0, 0.0, 0.0] {
+            vertices.extend_from_slice(&w.to_ne_bytes());
+        }
+        let mesh = MeshAsset {
+            vertices,
+            indices: vec![0],
+            vertex_layout: VertexLayout {
+                components: vec![
+                    VertexComponent::Vec3F32,
+                    VertexComponent::Vec4U16,
+                    VertexComponent::Vec4F32,
+                ],
+            },
+            topology: Topology::PointList,
+        };
+        let bent = clip.sample(&skeleton, 1.0, false);
+        let skinned = skin_mesh(&mesh, &skeleton.joint_matrices(&bent)).unwrap();
+        let position = skinned.positions().unwrap()[0];
+        // tip of the arm rotates about the elbow to point along -x
+        assert!((position - Vector3::new(-1.0, 1.0, 0.0)).norm() < 1e-5);
+        assert_eq!(joint_matrix_bytes(&rest).len(), 2 * 64);
+    }
+    #[test]
+    fn skins_by_position() {
+        let skeleton = arm();
+        let mesh = with_skin(&MeshAsset::new_cube(), |position| {
+            ([(position.y > 0.5) as u16, 0, 0, 0], [1.0, 0.0, 0.0, 0.0])
+        })
+        .unwrap();
+        assert_eq!(mesh.vertex_layout.components.len(), 5);
+        assert_eq!(mesh.validate(), Ok(()));
+        let bent = skeleton.joint_matrices(&bend().sample(&skeleton, 1.0, false));
+        let skinned = skin_mesh(&mesh, &bent).unwrap();
+        for (rest, skinned) in mesh
+            .positions()
+            .unwrap()
+            .iter()
+            .zip(skinned.positions().unwrap())
+        {
+            if rest.y > 0.5 {
+                // rotated a quarter turn about the elbow
+                let expected = Vector3::new(-(rest.y - 1.0), 1.0 + rest.x, rest.z);
+                assert!((skinned - expected).norm() < 1e-5);
+            } else {
+                assert!((skinned - rest).norm() < 1e-5);
+            }
+        }
+    }
+    #[test]
+    fn cubic_spline_hits_keyframes() {
+        let channel = Channel {
+            target: 0,
+            property: ChannelProperty::Translation,
+            interpolation: Interpolation::CubicSpline,
+            times: vec![0.0, 2.0],
+            values: vec![
+                Vector4::zeros(),
+                Vector4::new(1.0, 0.0, 0.0, 0.0),
+                Vector4::zeros(),
+                Vector4::zeros(),
+                Vector4::new(3.0, 0.0, 0.0, 0.0),
+                Vector4::zeros(),
+            ],
+        };
+        assert_eq!(channel.sample(0.0).unwrap().x, 1.0);
+        assert_eq!(channel.sample(2.0).unwrap().x, 3.0);
+        assert!((channel.sample(1.0).unwrap().x - 2.0).abs() < 1e-5);
+    }
+    #[test]
+    fn empty_channel_keeps_rest() {
+        let channel = Channel {
+            target: 0,
+            property: ChannelProperty::Scale,
+            interpolation: Interpolation::Linear,
+            times: vec![],
+            values: vec![],
+        };
+        assert_eq!(channel.sample(1.0), None);
+        let mut transform = JointTransform::identity();
+        let rest = transform;
+        channel.apply(1.0, &mut transform);
+        assert_eq!(transform, rest);
+    }
+}
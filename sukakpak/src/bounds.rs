@@ -0,0 +1,234 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+/// Axis aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+impl Aabb {
+    /// Builds smallest box containing points, returns None if there are no points
+    pub fn from_points<'a, I: IntoIterator<Item = &'a Vector3<f32>>>(points: I) -> Option<Self> {
+        let mut iter = points.into_iter();
+        let first = *iter.next()?;
+        Some(iter.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, p| Self {
+                min: aabb.min.inf(p),
+                max: aabb.max.sup(p),
+            },
+        ))
+    }
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+    /// Half of the size along each axis
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+    /// Smallest box containing both boxes
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+    /// Box containing this box after it is transformed
+    pub fn transform(&self, transform: &Matrix4<f32>) -> Self {
+        let corners: Vec<Vector3<f32>> = (0..8)
+            .map(|i| {
+                let corner = Vector3::new(
+                    if i & 1 == 0 { self.min.x } else { self.max.x },
+                    if i & 2 == 0 { self.min.y } else { self.max.y },
+                    if i & 4 == 0 { self.min.z } else { self.max.z },
+                );
+                transform.transform_point(&Point3::from(corner)).coords
+            })
+            .collect();
+        Self::from_points(corners.iter()).unwrap()
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+impl BoundingSphere {
+    /// Approximate bounding sphere using Ritter's algorithm, returns None if there
+    /// are no points
+    pub fn from_points(points: &[Vector3<f32>]) -> Option<Self> {
+        let furthest = |from: &Vector3<f32>| {
+            *points
+                .iter()
+                .max_by(|a, b| {
+                    (*a - from)
+                        .norm_squared()
+                        .total_cmp(&(*b - from).norm_squared())
+                })
+                .unwrap()
+        };
+        let x = points.first()?;
+        let y = furthest(x);
+        let z = furthest(&y);
+        let mut sphere = Self {
+            center: (y + z) / 2.0,
+            radius: (z - y).norm() / 2.0,
+        };
+        for point in points.iter() {
+            let distance = (point - sphere.center).norm();
+            if distance > sphere.radius {
+                let radius = (sphere.radius + distance) / 2.0;
+                sphere.center += (point - sphere.center) * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+        Some(sphere)
+    }
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        (point - self.center).norm_squared() <= self.radius * self.radius
+    }
+}
+/// Bounds of a mesh in model space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+/// Plane with points `p` on the plane satisfying `normal.dot(p) + d = 0`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub d: f32,
+}
+impl Plane {
+    fn from_vector(v: Vector4<f32>) -> Self {
+        let length = v.xyz().norm();
+        Self {
+            normal: v.xyz() / length,
+            d: v.w / length,
+        }
+    }
+    /// Signed distance, positive on the side the normal points to
+    pub fn distance(&self, point: &Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.d
+    }
+}
+/// View frustum, planes face inwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+impl Frustum {
+    /// Extracts planes from a view projection matrix. Objects are tested in the
+    /// space the matrix transforms from, so passing a model view projection matrix
+    /// allows testing model space bounds. The near plane uses the OpenGL depth range
+    /// of -1 to 1 which is conservative for Vulkan's 0 to 1.
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let row = |i: usize| matrix.row(i).transpose();
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [
+                Plane::from_vector(r3 + r0),
+                Plane::from_vector(r3 - r0),
+                Plane::from_vector(r3 + r1),
+                Plane::from_vector(r3 - r1),
+                Plane::from_vector(r3 + r2),
+                Plane::from_vector(r3 - r2),
+            ],
+        }
+    }
+    pub fn contains_point(&self, point: &Vector3<f32>) -> bool {
+        self.planes.iter().all(|p| p.distance(point) >= 0.0)
+    }
+    /// Returns false only if the sphere is fully outside
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.distance(&sphere.center) >= -sphere.radius)
+    }
+    /// Returns false only if the box is fully outside of one plane, boxes near
+    /// corners of the frustum may be reported as intersecting
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // corner furthest along the plane normal
+            let positive = Vector3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.distance(&positive) >= 0.0
+        })
+    }
+    pub fn intersects_bounds(&self, bounds: &Bounds) -> bool {
+        self.intersects_sphere(&bounds.sphere) && self.intersects_aabb(&bounds.aabb)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn camera() -> Frustum {
+        let projection = nalgebra::Perspective3::new(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        let view = Matrix4::look_at_rh(
+            &Point3::new(0.0, 0.0, 0.0),
+            &Point3::new(0.0, 0.0, -1.0),
+            &Vector3::y(),
+        );
+        Frustum::from_matrix(&(projection.as_matrix() * view))
+    }
+    #[test]
+    fn culls_behind_camera() {
+        let frustum = camera();
+        let in_front = Aabb {
+            min: Vector3::new(-1.0, -1.0, -11.0),
+            max: Vector3::new(1.0, 1.0, -9.0),
+        };
+        let behind = Aabb {
+            min: Vector3::new(-1.0, -1.0, 9.0),
+            max: Vector3::new(1.0, 1.0, 11.0),
+        };
+        assert!(frustum.intersects_aabb(&in_front));
+        assert!(!frustum.intersects_aabb(&behind));
+        assert!(frustum.intersects_sphere(&BoundingSphere {
+            center: Vector3::new(0.0, 0.0, -10.0),
+            radius: 1.0
+        }));
+        assert!(!frustum.intersects_sphere(&BoundingSphere {
+            center: Vector3::new(0.0, 0.0, 10.0),
+            radius: 1.0
+        }));
+        assert!(!frustum.contains_point(&Vector3::new(0.0, 0.0, -200.0)));
+    }
+    #[test]
+    fn sphere_contains_points() {
+        let points: Vec<Vector3<f32>> = (0..50)
+            .map(|i| {
+                let f = i as f32;
+                Vector3::new(f.sin() * 3.0, f.cos() * 2.0, (f * 0.3).sin())
+            })
+            .collect();
+        let sphere = BoundingSphere::from_points(&points).unwrap();
+        let aabb = Aabb::from_points(points.iter()).unwrap();
+        for p in points.iter() {
+            assert!((p - sphere.center).norm() <= sphere.radius + 1e-4);
+            assert!(aabb.contains_point(p));
+        }
+    }
+}
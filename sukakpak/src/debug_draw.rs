@@ -0,0 +1,303 @@
+//! Immediate mode debug drawing. Shapes are pushed while updating a frame and
+//! turned into a single line list that `ContextTrait::draw_debug` draws with
+//! the built in `debug_lines` shader, usually after the scene so lines show up
+//! on top of it.
+use super::{Aabb, MeshAsset, Topology};
+use nalgebra::{Matrix4, Vector3, Vector4};
+use std::f32::consts::PI;
+/// Name of the built in shader used to draw debug lines
+pub const DEBUG_SHADER: &str = "debug_lines";
+/// Segments used for circles and spheres
+const CIRCLE_SEGMENTS: u32 = 24;
+#[derive(Clone, Copy, Debug, PartialEq, sukakpak_derive::Vertex)]
+pub struct DebugVertex {
+    pub position: Vector3<f32>,
+    pub color: Vector4<f32>,
+}
+#[derive(Clone, Debug, PartialEq)]
+struct DebugText {
+    position: Vector3<f32>,
+    text: String,
+    height: f32,
+    color: Vector4<f32>,
+}
+/// Collects debug shapes for a frame. Colors are linear RGBA.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+    text: Vec<DebugText>,
+}
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Removes every shape, call once the frame's shapes are drawn
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.text.clear();
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty() && self.text.is_empty()
+    }
+    pub fn line(&mut self, a: Vector3<f32>, b: Vector3<f32>, color: Vector4<f32>) {
+        self.vertices.push(DebugVertex { position: a, color });
+        self.vertices.push(DebugVertex { position: b, color });
+    }
+    /// Draws the twelve edges of `aabb`
+    pub fn aabb(&mut self, aabb: &Aabb, color: Vector4<f32>) {
+        let corner = |i: usize| {
+            Vector3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            )
+        };
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+    /// Circle around `normal`
+    pub fn circle(
+        &mut self,
+        center: Vector3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        color: Vector4<f32>,
+    ) {
+        let normal = normal.normalize();
+        let helper = if normal.y.abs() < 0.9 {
+            Vector3::y()
+        } else {
+            Vector3::x()
+        };
+        let u = normal.cross(&helper).normalize() * radius;
+        let v = normal.cross(&u);
+        let point = |i: u32| {
+            let angle = 2.0 * PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + u * angle.cos() + v * angle.sin()
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+    /// Draws a circle around each axis
+    pub fn sphere(&mut self, center: Vector3<f32>, radius: f32, color: Vector4<f32>) {
+        for axis in [Vector3::x(), Vector3::y(), Vector3::z()] {
+            self.circle(center, axis, radius, color);
+        }
+    }
+    /// Line from `from` to `to` with a head at `to`
+    pub fn arrow(&mut self, from: Vector3<f32>, to: Vector3<f32>, color: Vector4<f32>) {
+        self.line(from, to, color);
+        let direction = to - from;
+        let length = direction.norm();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        let helper = if direction.y.abs() < 0.9 {
+            Vector3::y()
+        } else {
+            Vector3::x()
+        };
+        let side = direction.cross(&helper).normalize();
+        let up = direction.cross(&side);
+        let head = length * 0.2;
+        for offset in [side, -side, up, -up] {
+            self.line(to, to - direction * head + offset * head * 0.5, color);
+        }
+    }
+    /// Draws `length` units of a ray
+    pub fn ray(
+        &mut self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        length: f32,
+        color: Vector4<f32>,
+    ) {
+        self.arrow(origin, origin + direction.normalize() * length, color)
+    }
+    /// Grid on the xz plane centered at `center` with `divisions` cells along
+    /// each side
+    pub fn grid(&mut self, center: Vector3<f32>, size: f32, divisions: u32, color: Vector4<f32>) {
+        let divisions = divisions.max(1);
+        let half = size / 2.0;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            self.line(
+                center + Vector3::new(offset, 0.0, -half),
+                center + Vector3::new(offset, 0.0, half),
+                color,
+            );
+            self.line(
+                center + Vector3::new(-half, 0.0, offset),
+                center + Vector3::new(half, 0.0, offset),
+                color,
+            );
+        }
+    }
+    /// Text drawn with a line font facing the camera, `position` is the bottom
+    /// left of the first character. Letters, digits and `-+.,/_=()|'` are
+    /// supported, lowercase is drawn as uppercase and other characters as `?`.
+    pub fn text3d(&mut self, position: Vector3<f32>, text: &str, height: f32, color: Vector4<f32>) {
+        self.text.push(DebugText {
+            position,
+            text: text.to_string(),
+            height,
+            color,
+        });
+    }
+    /// Builds the line list for the current shapes. Text is oriented to face the
+    /// camera of `view_projection`.
+    pub fn line_mesh(&self, view_projection: &Matrix4<f32>) -> MeshAsset {
+        let mut vertices = self.vertices.clone();
+        let right = view_projection.row(0).transpose().xyz();
+        let up = view_projection.row(1).transpose().xyz();
+        let (right, up) = if right.norm() > f32::EPSILON && up.norm() > f32::EPSILON {
+            (right.normalize(), up.normalize())
+        } else {
+            (Vector3::x(), Vector3::y())
+        };
+        for text in self.text.iter() {
+            // glyphs are two units tall
+            let scale = text.height / 2.0;
+            for (i, c) in text.text.chars().enumerate() {
+                let origin = text.position + right * (i as f32 * GLYPH_ADVANCE * scale);
+                for segment in glyph(c).chars() {
+                    let (a, b) = segment_points(segment);
+                    for (x, y) in [a, b] {
+                        vertices.push(DebugVertex {
+                            position: origin + (right * x + up * y) * scale,
+                            color: text.color,
+                        });
+                    }
+                }
+            }
+        }
+        let indices = (0..vertices.len() as u32).collect();
+        MeshAsset::from_vertices(&vertices, indices).with_topology(Topology::LineList)
+    }
+}
+/// Distance between the starts of glyphs, glyphs are one unit wide
+const GLYPH_ADVANCE: f32 = 1.5;
+/// Ends of a segment of a sixteen segment display, `a`/`A` are the left and
+/// right halves of the top, `g`/`G` the middle and `d`/`D` the bottom. `b` and
+/// `c` are the right side, `e` and `f` the left, `i` and `l` the center and
+/// `h`, `j`, `k`, `m` the diagonals.
+fn segment_points(segment: char) -> ((f32, f32), (f32, f32)) {
+    match segment {
+        'a' => ((0.0, 2.0), (0.5, 2.0)),
+        'A' => ((0.5, 2.0), (1.0, 2.0)),
+        'b' => ((1.0, 2.0), (1.0, 1.0)),
+        'c' => ((1.0, 1.0), (1.0, 0.0)),
+        'd' => ((0.0, 0.0), (0.5, 0.0)),
+        'D' => ((0.5, 0.0), (1.0, 0.0)),
+        'e' => ((0.0, 0.0), (0.0, 1.0)),
+        'f' => ((0.0, 1.0), (0.0, 2.0)),
+        'g' => ((0.0, 1.0), (0.5, 1.0)),
+        'G' => ((0.5, 1.0), (1.0, 1.0)),
+        'h' => ((0.0, 2.0), (0.5, 1.0)),
+        'i' => ((0.5, 2.0), (0.5, 1.0)),
+        'j' => ((1.0, 2.0), (0.5, 1.0)),
+        'k' => ((0.0, 0.0), (0.5, 1.0)),
+        'l' => ((0.5, 0.0), (0.5, 1.0)),
+        'm' => ((1.0, 0.0), (0.5, 1.0)),
+        _ => unreachable!("unknown segment {}", segment),
+    }
+}
+/// Segments lit for a character, see `segment_points`
+fn glyph(c: char) -> &'static str {
+    match c.to_ascii_uppercase() {
+        ' ' => "",
+        '0' => "aAbcdDefjk",
+        '1' => "bcj",
+        '2' => "aAbgGedD",
+        '3' => "aAbGcdD",
+        '4' => "fgGbc",
+        '5' => "aAfgGcdD",
+        '6' => "aAfgGecdD",
+        '7' => "aAbc",
+        '8' => "aAbcdDefgG",
+        '9' => "aAbcdDfgG",
+        'A' => "aAbcefgG",
+        'B' => "aAbcdDilG",
+        'C' => "aAfedD",
+        'D' => "aAbcdDil",
+        'E' => "aAfedDg",
+        'F' => "aAfeg",
+        'G' => "aAfedDcG",
+        'H' => "febcgG",
+        'I' => "aAildD",
+        'J' => "bcdDe",
+        'K' => "fegjm",
+        'L' => "fedD",
+        'M' => "febchj",
+        'N' => "febchm",
+        'O' => "aAbcdDef",
+        'P' => "aAbfegG",
+        'Q' => "aAbcdDefm",
+        'R' => "aAbfegGm",
+        'S' => "aAfgGcdD",
+        'T' => "aAil",
+        'U' => "fedDbc",
+        'V' => "fekj",
+        'W' => "febckm",
+        'X' => "hjkm",
+        'Y' => "hjl",
+        'Z' => "aAjkdD",
+        '-' => "gG",
+        '+' => "gGil",
+        '.' => "d",
+        ',' => "k",
+        '/' => "jk",
+        '_' => "dD",
+        '=' => "gGdD",
+        '(' => "jm",
+        ')' => "hk",
+        '|' => "il",
+        '\'' => "i",
+        _ => "aAbGl",
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn builds_line_list() {
+        let white = Vector4::new(1.0, 1.0, 1.0, 1.0);
+        let mut debug = DebugDraw::new();
+        assert!(debug.is_empty());
+        debug.line(Vector3::zeros(), Vector3::x(), white);
+        debug.aabb(
+            &Aabb {
+                min: Vector3::zeros(),
+                max: Vector3::new(1.0, 1.0, 1.0),
+            },
+            white,
+        );
+        debug.sphere(Vector3::zeros(), 1.0, white);
+        debug.arrow(Vector3::zeros(), Vector3::y(), white);
+        debug.grid(Vector3::zeros(), 2.0, 2, white);
+        let lines = 1 + 12 + 3 * CIRCLE_SEGMENTS as usize + 5 + 2 * 3;
+        let mesh = debug.line_mesh(&Matrix4::identity());
+        assert_eq!(mesh.topology, Topology::LineList);
+        assert_eq!(mesh.num_vertices(), lines * 2);
+        assert_eq!(mesh.validate(), Ok(()));
+
+        debug.clear();
+        debug.text3d(Vector3::new(0.0, 0.0, 5.0), "Hi 1", 2.0, white);
+        let mesh = debug.line_mesh(&Matrix4::identity());
+        let segments = glyph('H').len() + glyph('I').len() + glyph('1').len();
+        assert_eq!(mesh.num_indices(), segments * 2);
+        let positions = mesh.positions().unwrap();
+        // last glyph starts three advances to the right
+        assert!(positions.iter().all(|p| p.z == 5.0));
+        assert!(positions
+            .iter()
+            .any(|p| (p.x - 3.0 * GLYPH_ADVANCE - 1.0).abs() < 1e-5));
+    }
+}
@@ -1,31 +1,57 @@
+// lets `#[derive(Vertex)]` refer to `::sukakpak` inside of this crate
+extern crate self as sukakpak;
 pub use anyhow;
 use anyhow::Result;
+pub use ass_types::BufferKind;
+pub use half;
 pub use image;
 use image::RgbaImage;
 pub use nalgebra;
-use nalgebra::Vector2;
+use nalgebra::{Matrix4, Vector2, Vector3};
+mod animation;
+mod bounds;
+mod debug_draw;
+// only the vulkan backend sorts draws
+#[cfg_attr(not(feature = "backend_vulkan"), allow(dead_code))]
+mod draw_list;
 mod events;
+mod lod;
 mod mesh;
 mod vertex;
-pub use mesh::{EasyMesh, Mesh as MeshAsset, Vertex as EasyMeshVertex};
+pub use animation::{
+    joint_matrix_bytes, skin_mesh, with_skin, AnimationClip, Channel, ChannelProperty,
+    Interpolation, Joint, JointTransform, Pose, Skeleton,
+};
+pub use bounds::{Aabb, BoundingSphere, Bounds, Frustum, Plane};
+pub use debug_draw::{DebugDraw, DebugVertex, DEBUG_SHADER};
+pub use draw_list::{DrawQueue, DrawStats};
+pub use lod::{LodLevel, LodMesh, LodThreshold};
+pub use mesh::ops as mesh_ops;
+pub use mesh::{
+    EasyMesh, GltfAnimation, GltfError, GltfMaterial, GltfMesh, GltfNode, GltfPrimitive,
+    GltfScene, GltfSkin, IndexWidth, Mesh as MeshAsset, MeshCacheError, MeshCompression,
+    MeshError, ObjMaterial, ObjModel, ObjScene, Topology, Vertex as EasyMeshVertex,
+    VertexStream, MESH_CACHE_VERSION,
+};
 use std::path::Path;
-pub use vertex::{VertexComponent, VertexLayout};
+pub use sukakpak_derive::Vertex;
+pub use vertex::{VertexAttribute, VertexComponent, VertexLayout, VertexTrait};
 
 pub use events::{Event, MouseButton, ScrollDelta, SemanticKeyCode};
 use std::time::Duration;
 cfg_if::cfg_if! {
     if #[cfg(feature="backend_vulkan")]{
         mod vulkan;
-        pub use vulkan::Context;
+        pub use vulkan::{list_adapters, Context};
     } else if #[cfg(feature="backend_webgl")]{
         mod webgl;
-        pub use webgl::Context;
+        pub use webgl::{list_adapters, Context};
     }else if #[cfg(feature="backend_web_stub")]{
         mod web_stub;
-        pub use web_stub::Context;
+        pub use web_stub::{list_adapters, Context};
     }else{
         mod stub_backend;
-        pub use stub_backend::Context;
+        pub use stub_backend::{list_adapters, Context};
     }
 }
 
@@ -33,6 +59,7 @@ pub type Backend = <Context as ContextTrait>::Backend;
 pub type Mesh = <Context as ContextTrait>::Mesh;
 pub type Framebuffer = <Context as ContextTrait>::Framebuffer;
 pub type Texture = <Context as ContextTrait>::Texture;
+pub type Buffer = <Context as ContextTrait>::Buffer;
 pub type DrawableTexture<'a> = GenericDrawableTexture<'a, Texture, Framebuffer>;
 /// Represents framebuffers that can be drawn to
 pub type Bindable<'a> = GenericBindable<'a, Framebuffer>;
@@ -42,6 +69,126 @@ pub struct CreateInfo {
     pub window_id: String,
     pub name: String,
     pub vulkan_sdk_path: Option<std::path::PathBuf>,
+    /// Directory the Vulkan pipeline cache is saved in on shutdown and loaded
+    /// from on startup, None keeps the cache in memory only
+    pub pipeline_cache_dir: Option<std::path::PathBuf>,
+    pub present_mode: PresentMode,
+    /// Number of swapchain images to ask for, clamped to what the surface
+    /// supports. None asks for one more than the minimum.
+    pub swapchain_images: Option<u32>,
+    /// Most frames recorded before the oldest one finishes on the gpu, at least 1
+    pub max_frames_in_flight: usize,
+    /// Samples per pixel of the screen framebuffer
+    pub sample_count: SampleCount,
+    /// GPU to render with, overridden by the `SUKAKPAK_DEVICE` environment variable
+    pub device_preference: DevicePreference,
+}
+/// Environment variable overriding `CreateInfo::device_preference`. Takes `discrete`,
+/// `integrated` or `software`, an index from `list_adapters`, or else a name substring.
+pub const DEVICE_ENV_VAR: &str = "SUKAKPAK_DEVICE";
+/// Chooses the GPU out of the ones able to render to the window. Type preferences fall
+/// back to another type if no GPU of the type is found, names and indices do not.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DevicePreference {
+    #[default]
+    Discrete,
+    Integrated,
+    /// a CPU implementation such as lavapipe
+    Software,
+    /// first GPU whose name contains the string, ignoring case
+    Name(String),
+    /// `AdapterInfo::index` of the GPU
+    Index(usize),
+}
+impl std::str::FromStr for DevicePreference {
+    type Err = std::convert::Infallible;
+    fn from_str(str_in: &str) -> std::result::Result<Self, Self::Err> {
+        let lowercase = str_in.trim().to_lowercase();
+        Ok(match lowercase.as_str() {
+            "discrete" => Self::Discrete,
+            "integrated" => Self::Integrated,
+            "software" => Self::Software,
+            _ => match lowercase.parse() {
+                Ok(index) => Self::Index(index),
+                Err(_) => Self::Name(str_in.trim().to_string()),
+            },
+        })
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdapterType {
+    Discrete,
+    Integrated,
+    Virtual,
+    Software,
+    Other,
+}
+/// GPU found by `list_adapters`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdapterInfo {
+    /// used by `DevicePreference::Index`
+    pub index: usize,
+    pub name: String,
+    pub adapter_type: AdapterType,
+    /// largest width and height of a texture or framebuffer
+    pub max_texture_size: u32,
+    pub max_push_constant_size: u32,
+    pub max_uniform_buffer_size: u32,
+    pub max_storage_buffer_size: u32,
+    /// highest sample count usable by framebuffers
+    pub max_sample_count: SampleCount,
+}
+/// Samples per pixel used to antialias edges. Counts the device does not support
+/// fall back to the highest supported count below them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleCount {
+    X1,
+    X2,
+    X4,
+    X8,
+}
+impl SampleCount {
+    pub fn samples(&self) -> u32 {
+        match self {
+            Self::X1 => 1,
+            Self::X2 => 2,
+            Self::X4 => 4,
+            Self::X8 => 8,
+        }
+    }
+}
+/// Describes a framebuffer built with `ContextTrait::build_framebuffer`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramebufferDesc {
+    pub resolution: Vector2<u32>,
+    /// multisampled framebuffers are resolved before being sampled as a texture
+    pub sample_count: SampleCount,
+}
+impl FramebufferDesc {
+    /// Framebuffer without multisampling
+    pub fn new(resolution: Vector2<u32>) -> Self {
+        Self {
+            resolution,
+            sample_count: SampleCount::X1,
+        }
+    }
+}
+/// Rust version of a compute shader run in place of the shader by backends without a GPU.
+/// Called once per dispatch with the number of workgroups and the contents of the bound
+/// buffers in the order they were passed to `ContextTrait::dispatch`.
+pub type ComputeReference = fn(groups: Vector3<u32>, buffers: &mut [Vec<u8>]);
+/// How finished frames are shown. Modes the surface does not support fall back
+/// to `Vsync`, which is always available. WebGL always uses the browser's vsync.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Frames wait for the vertical blank and never tear (FIFO)
+    Vsync,
+    /// Vsync unless a frame misses the vertical blank, late frames are shown
+    /// at once and may tear (FIFO_RELAXED)
+    AdaptiveVsync,
+    /// No vsync. Uses MAILBOX, which replaces queued frames without tearing,
+    /// falling back to IMMEDIATE which can tear.
+    NoVsync,
 }
 pub struct Sukakpak {}
 unsafe impl Send for Sukakpak {}
@@ -173,6 +320,9 @@ pub trait ContextTrait: Send + Sync {
     /// Stores runtime texture data. Texture data will only be freed once  
     /// .drop is called on *both* texture and all meshes that bind the texture
     type Texture: std::fmt::Debug;
+    /// Uniform or storage buffer. Data is only freed once .drop is called on
+    /// the buffer and every shader and mesh it is bound to stops using it
+    type Buffer;
     type Timer: Timer;
     fn new(backend: Self::Backend) -> Self;
     /// does steps for starting rendering
@@ -192,9 +342,64 @@ pub trait ContextTrait: Send + Sync {
         mesh: &mut Self::Mesh,
         texture: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
     ) -> Result<()>;
+    /// Same as `build_mesh`, `name` labels the mesh's buffers in graphics debuggers
+    /// such as RenderDoc. Backends without debug names ignore it.
+    fn build_mesh_named(
+        &mut self,
+        mesh: MeshAsset,
+        texture: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
+        name: &str,
+    ) -> Result<Self::Mesh>;
     fn build_texture(&mut self, image: &RgbaImage) -> Result<Self::Texture>;
+    /// Same as `build_texture`, `name` labels the image in graphics debuggers
+    fn build_texture_named(&mut self, image: &RgbaImage, name: &str) -> Result<Self::Texture>;
     fn draw_mesh(&mut self, push: Vec<u8>, mesh: &Self::Mesh) -> Result<()>;
-    fn build_framebuffer(&mut self, resolution: Vector2<u32>) -> Result<Self::Framebuffer>;
+    /// Queues `mesh` to be drawn sorted with the other queued draws. Opaque draws are
+    /// grouped by pipeline and texture then drawn front to back, transparent draws are
+    /// drawn after them back to front. `depth` is the distance of the mesh from the camera.
+    /// Queued draws are recorded before anything that depends on draw order: binding a
+    /// framebuffer, shader, texture or buffer, `draw_mesh`, `draw_debug`, `update_buffer`,
+    /// debug groups, `dispatch` and `finish_render`. Framebuffer passes are never merged
+    /// as binding a framebuffer clears it. Backends without a draw list draw immediately.
+    fn draw_mesh_sorted(
+        &mut self,
+        push: Vec<u8>,
+        mesh: &Self::Mesh,
+        queue: DrawQueue,
+        depth: f32,
+    ) -> Result<()>;
+    /// Binds `draw_mesh_sorted` recorded and saved in the last finished frame, all zero on
+    /// backends without a draw list
+    fn draw_stats(&self) -> DrawStats;
+    /// Draws the lines in `debug` to the bound framebuffer with the built in
+    /// `DEBUG_SHADER`, call after drawing the scene. Does not change the bound
+    /// shader and does not clear `debug`.
+    fn draw_debug(&mut self, debug: &DebugDraw, view_projection: Matrix4<f32>) -> Result<()>;
+    fn build_framebuffer(&mut self, desc: FramebufferDesc) -> Result<Self::Framebuffer>;
+    /// Same as `build_framebuffer`, `name` labels the attachments in graphics debuggers
+    fn build_framebuffer_named(
+        &mut self,
+        desc: FramebufferDesc,
+        name: &str,
+    ) -> Result<Self::Framebuffer>;
+    /// Builds a uniform or read only storage buffer holding `data`, the size of
+    /// the buffer is fixed to the length of `data`
+    fn build_buffer(&mut self, kind: BufferKind, data: &[u8]) -> Result<Self::Buffer>;
+    /// Overwrites the start of `buffer`. Draws recorded before the update keep
+    /// reading the old data.
+    fn update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]) -> Result<()>;
+    /// Binds `buffer` to the buffer called `name` in `shader` for every mesh
+    /// drawn with the shader
+    fn bind_shader_buffer(&mut self, shader: &str, name: &str, buffer: &Self::Buffer)
+        -> Result<()>;
+    /// Binds `buffer` to the buffer called `name` when drawing `mesh`, takes
+    /// priority over `bind_shader_buffer`
+    fn bind_mesh_buffer(
+        &mut self,
+        mesh: &mut Self::Mesh,
+        name: &str,
+        buffer: &Self::Buffer,
+    ) -> Result<()>;
     /// Shader being stringly typed is not ideal but better shader system is waiting
     /// on a naga translation layer for shaders
     fn bind_shader(
@@ -203,10 +408,45 @@ pub trait ContextTrait: Send + Sync {
         shader: &str,
     ) -> Result<()>;
     fn bind_framebuffer(&mut self, framebuffer: GenericBindable<Self::Framebuffer>) -> Result<()>;
+    /// Starts a labeled group of the commands recorded this frame, shown in graphics
+    /// debuggers. Groups nest and are closed with `pop_debug_group`, groups left open
+    /// are closed when the frame finishes. Does nothing on backends without debug labels.
+    fn push_debug_group(&mut self, label: &str) -> Result<()>;
+    /// Closes the group opened by the last `push_debug_group`
+    fn pop_debug_group(&mut self) -> Result<()>;
     /// Gets screen resolution in pixels
     fn get_screen_size(&self) -> Vector2<u32>;
-    /// Loads v2 shader from string. Will work on all backends
+    /// Changes how frames are presented, rebuilds the swapchain
+    fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()>;
+    /// Loads v2 shader from string. Will work on all backends. Pipelines built from the
+    /// shader are labeled with `shader_name` in graphics debuggers.
     fn load_shader(&mut self, shader: &str, shader_name: &str) -> Result<()>;
+    /// Compiles the WGSL shader project in `project_dir` and loads it as `shader_name`.
+    /// The project is watched and recompiled when its files change, framebuffers bound
+    /// to the shader switch to the new pipeline on the next frame. If recompiling fails
+    /// the error is printed and the previous shader is kept. Needs a filesystem so it
+    /// is unavailable on WebGL.
+    fn watch_shader(&mut self, project_dir: &Path, shader_name: &str) -> Result<()>;
+    /// Loads a compute shader built by ass with `--compute`. Unavailable on WebGL.
+    fn load_compute_shader(&mut self, shader: &str, shader_name: &str) -> Result<()>;
+    /// Sets the CPU version of compute shader `shader_name`, which the stub backend runs
+    /// so results can be tested without a GPU. GPU backends ignore it.
+    fn set_compute_reference(
+        &mut self,
+        shader_name: &str,
+        reference: ComputeReference,
+    ) -> Result<()>;
+    /// Runs `groups` workgroups of compute shader `shader`. `bindings` pairs the name of
+    /// every storage buffer in the shader with a `BufferKind::Storage` buffer. Draws
+    /// recorded after the dispatch read the results, earlier draws read the old data.
+    fn dispatch(
+        &mut self,
+        shader: &str,
+        groups: Vector3<u32>,
+        bindings: &[(&str, &Self::Buffer)],
+    ) -> Result<()>;
+    /// Reads the latest contents of `buffer`, including results of dispatches
+    fn read_buffer(&mut self, buffer: &Self::Buffer) -> Result<Vec<u8>>;
     /// quits the program once `render_frame` finishes
     fn quit(&mut self);
     ///checks if quit was called
@@ -0,0 +1,135 @@
+use super::{mesh_ops, BoundingSphere, MeshAsset};
+use anyhow::{anyhow, bail, Result};
+use nalgebra::{Matrix4, Vector4};
+/// Describes a level to build with `LodMesh::build`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodThreshold {
+    /// Fraction of the original triangles to keep
+    pub ratio: f32,
+    /// Smallest projected size, as a fraction of screen height, the level is used at
+    pub min_screen_size: f32,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodLevel<M> {
+    pub mesh: M,
+    /// Smallest projected size, as a fraction of screen height, the level is used at
+    pub min_screen_size: f32,
+}
+/// Mesh with several levels of detail, picks a level based on how large the mesh
+/// appears on screen. `M` is usually a `Mesh` or a handle to one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodMesh<M> {
+    /// sorted from most to least detailed
+    levels: Vec<LodLevel<M>>,
+    sphere: BoundingSphere,
+}
+impl<M> LodMesh<M> {
+    /// `sphere` bounds the mesh in model space. Panics if `levels` is empty.
+    pub fn new(mut levels: Vec<LodLevel<M>>, sphere: BoundingSphere) -> Self {
+        assert!(!levels.is_empty(), "LodMesh needs at least one level");
+        levels.sort_by(|a, b| b.min_screen_size.total_cmp(&a.min_screen_size));
+        Self { levels, sphere }
+    }
+    /// Simplifies `asset` for every threshold and passes each level to `build`,
+    /// usually a closure calling `ContextTrait::build_mesh`.
+    pub fn build<F: FnMut(MeshAsset) -> Result<M>>(
+        asset: &MeshAsset,
+        thresholds: &[LodThreshold],
+        mut build: F,
+    ) -> Result<Self> {
+        if thresholds.is_empty() {
+            bail!("at least one level of detail is needed")
+        }
+        let sphere = asset
+            .bounding_sphere()
+            .ok_or_else(|| anyhow!("mesh does not have positions to bound"))?;
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_by(|a, b| b.ratio.total_cmp(&a.ratio));
+        let ratios: Vec<f32> = thresholds.iter().map(|t| t.ratio).collect();
+        let levels = mesh_ops::lod_chain(asset, &ratios)?
+            .drain(..)
+            .zip(thresholds.iter())
+            .map(|(mesh, threshold)| {
+                Ok(LodLevel {
+                    mesh: build(mesh)?,
+                    min_screen_size: threshold.min_screen_size,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(levels, sphere))
+    }
+    pub fn levels(&self) -> &[LodLevel<M>] {
+        &self.levels
+    }
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        self.sphere
+    }
+    /// Approximate height of the bounding sphere on screen as a fraction of the
+    /// screen height. Returns infinity if the camera is inside of the sphere.
+    pub fn screen_size(&self, model_view_projection: &Matrix4<f32>) -> f32 {
+        let center = Vector4::new(
+            self.sphere.center.x,
+            self.sphere.center.y,
+            self.sphere.center.z,
+            1.0,
+        );
+        let w = model_view_projection.row(3).transpose().dot(&center);
+        // scale of the y axis in clip space, includes the model's scale
+        let scale = model_view_projection.row(1).transpose().xyz().norm();
+        let radius = self.sphere.radius * scale;
+        if w <= radius {
+            f32::INFINITY
+        } else {
+            radius / w
+        }
+    }
+    /// Index of the least detailed level that is large enough
+    pub fn select_level(&self, model_view_projection: &Matrix4<f32>) -> usize {
+        let size = self.screen_size(model_view_projection);
+        self.levels
+            .iter()
+            .position(|level| size >= level.min_screen_size)
+            .unwrap_or(self.levels.len() - 1)
+    }
+    pub fn select(&self, model_view_projection: &Matrix4<f32>) -> &M {
+        &self.levels[self.select_level(model_view_projection)].mesh
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Perspective3, Vector3};
+    #[test]
+    fn selects_by_distance() {
+        let asset = MeshAsset::new_uv_sphere(1.0, 32, 16);
+        let lod = LodMesh::build(
+            &asset,
+            &[
+                LodThreshold {
+                    ratio: 1.0,
+                    min_screen_size: 0.2,
+                },
+                LodThreshold {
+                    ratio: 0.3,
+                    min_screen_size: 0.05,
+                },
+                LodThreshold {
+                    ratio: 0.1,
+                    min_screen_size: 0.0,
+                },
+            ],
+            |mesh| Ok(mesh.indices.len()),
+        )
+        .unwrap();
+        assert_eq!(lod.levels()[0].mesh, asset.indices.len());
+        let projection = Perspective3::new(1.0, std::f32::consts::FRAC_PI_2, 0.1, 1000.0);
+        let at = |distance: f32| {
+            projection.as_matrix() * Matrix4::new_translation(&Vector3::new(0.0, 0.0, -distance))
+        };
+        assert_eq!(lod.select_level(&at(0.5)), 0);
+        assert_eq!(lod.select_level(&at(3.0)), 0);
+        assert_eq!(lod.select_level(&at(10.0)), 1);
+        assert_eq!(lod.select_level(&at(100.0)), 2);
+        assert!(lod.levels()[2].mesh <= lod.levels()[1].mesh);
+    }
+}
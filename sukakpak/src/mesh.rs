@@ -1,90 +1,273 @@
-use super::{VertexComponent, VertexLayout};
-use anyhow::Result;
-use nalgebra::{Vector2, Vector3};
-use std::{
-    boxed::Box,
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+mod cache;
+mod gltf;
+mod obj;
+pub mod ops;
+mod primitives;
+mod simplify;
+mod validation;
+use super::{Aabb, BoundingSphere, Bounds, VertexComponent, VertexLayout, VertexTrait};
+use anyhow::{bail, Result};
+use ass_types::VertexInput;
+pub use cache::{MeshCacheError, MeshCompression, MESH_CACHE_VERSION};
+pub use gltf::{
+    GltfAnimation, GltfError, GltfMaterial, GltfMesh, GltfNode, GltfPrimitive, GltfScene, GltfSkin,
 };
-use tobj::{load_mtl_buf, load_obj_buf, LoadOptions, MTLLoadResult};
+use nalgebra::{Vector2, Vector3};
+pub use obj::{ObjMaterial, ObjModel, ObjScene};
+use std::convert::TryFrom;
+use std::{io::BufRead, path::Path};
+pub use validation::MeshError;
+/// How indices are assembled into primitives
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    /// Every three indices form a triangle
+    #[default]
+    TriangleList,
+    /// Every index after the first two forms a triangle with the previous two
+    TriangleStrip,
+    /// Every two indices form a line
+    LineList,
+    /// Every index after the first forms a line with the previous one
+    LineStrip,
+    /// Every index is a point. Vulkan shaders must write `gl_PointSize`
+    PointList,
+}
+impl Topology {
+    /// Whether `count` indices make up whole primitives
+    pub fn is_valid_index_count(&self, count: usize) -> bool {
+        match self {
+            Self::TriangleList => count.is_multiple_of(3),
+            Self::TriangleStrip => count == 0 || count >= 3,
+            Self::LineList => count.is_multiple_of(2),
+            Self::LineStrip => count != 1,
+            Self::PointList => true,
+        }
+    }
+    pub fn is_strip(&self) -> bool {
+        matches!(self, Self::TriangleStrip | Self::LineStrip)
+    }
+}
+/// Width of the indices in a GPU index buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IndexWidth {
+    U16,
+    U32,
+}
+impl IndexWidth {
+    /// Narrowest width that holds every index in `indices`
+    pub fn for_indices(indices: &[u32]) -> Self {
+        if indices.iter().all(|i| *i <= u16::MAX as u32) {
+            Self::U16
+        } else {
+            Self::U32
+        }
+    }
+    /// Size in bytes of a single index
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U16 => std::mem::size_of::<u16>(),
+            Self::U32 => std::mem::size_of::<u32>(),
+        }
+    }
+    /// Writes `indices` as native endian bytes of this width, indices must fit
+    pub fn index_bytes(&self, indices: &[u32]) -> Vec<u8> {
+        match self {
+            Self::U16 => indices
+                .iter()
+                .flat_map(|i| (*i as u16).to_ne_bytes())
+                .collect(),
+            Self::U32 => indices.iter().flat_map(|i| i.to_ne_bytes()).collect(),
+        }
+    }
+}
+/// Vertex data for the components a shader reads from one vertex buffer binding
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexStream {
+    pub binding: u32,
+    /// Shader location of each component in `vertex_layout`
+    pub locations: Vec<u32>,
+    pub vertex_layout: VertexLayout,
+    pub vertices: Vec<u8>,
+}
 #[derive(Clone, Debug, PartialEq)]
 pub struct Mesh {
     pub vertices: Vec<u8>,
     pub indices: Vec<u32>,
     pub vertex_layout: VertexLayout,
-}
-fn get_buffer_from_fs(path: &Path) -> Box<dyn BufRead> {
-    let file = File::open(path).expect("failed to open file");
-    Box::new(BufReader::new(file))
+    pub topology: Topology,
 }
 impl Mesh {
+    /// Number of whole vertices in `vertices`
     pub fn num_vertices(&self) -> usize {
+        match self.vertex_layout.vertex_size() {
+            0 => 0,
+            vertex_size => self.vertices.len() / vertex_size,
+        }
+    }
+    pub fn num_indices(&self) -> usize {
         self.indices.len()
     }
-    pub fn from_obj_buf<B: BufRead, M: Fn(&Path) -> Box<dyn BufRead>>(
-        mut buffer: B,
-        material_loader: M,
-    ) -> Result<Self> {
-        let loader: Box<dyn Fn(&Path) -> MTLLoadResult> =
-            Box::new(|path| load_mtl_buf(&mut material_loader(path)));
-        let (model, _mtl) = load_obj_buf(
-            &mut buffer,
-            &LoadOptions {
-                triangulate: true,
-
-                single_index: true,
-                ..Default::default()
-            },
-            loader,
-        )?;
-
-        let mesh = &model[0].mesh;
-        let num_vertices = mesh.positions.len() / 3;
-        let vertices = (0..num_vertices)
-            .map(|i| {
-                (
-                    [
-                        mesh.positions[i * 3],
-                        mesh.positions[i * 3 + 1],
-                        mesh.positions[i * 3 + 2],
-                    ],
-                    [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]],
-                    [
-                        mesh.normals[i * 3],
-                        mesh.normals[i * 3 + 1],
-                        mesh.normals[i * 3 + 2],
-                    ],
-                )
+    /// Width of the mesh's index buffer, `u16` if every index fits
+    pub fn index_width(&self) -> IndexWidth {
+        IndexWidth::for_indices(&self.indices)
+    }
+    /// Bindings and layouts of the vertex buffers a shader with `input` reads,
+    /// sorted by binding. Component `i` is read by location `i`, components
+    /// without a matching field are read from `input.binding`.
+    pub fn stream_layouts(&self, input: &VertexInput) -> Vec<(u32, VertexLayout)> {
+        let bindings = self.component_bindings(input);
+        let mut streams: Vec<u32> = bindings.clone();
+        streams.sort_unstable();
+        streams.dedup();
+        streams
+            .into_iter()
+            .map(|binding| {
+                let components = self
+                    .vertex_layout
+                    .components
+                    .iter()
+                    .zip(bindings.iter())
+                    .filter(|(_, b)| **b == binding)
+                    .map(|(c, _)| c.clone())
+                    .collect();
+                (binding, VertexLayout { components })
             })
-            .map(|(pos, uv, norm)| {
-                [
-                    pos[0], pos[1], pos[2], uv[0], uv[1], norm[0], norm[1], norm[2],
-                ]
+            .collect()
+    }
+    /// Splits the interleaved vertices into one stream per vertex buffer binding
+    /// read by a shader with `input`, see `stream_layouts`
+    pub fn vertex_streams(&self, input: &VertexInput) -> Vec<VertexStream> {
+        let bindings = self.component_bindings(input);
+        let mut streams: Vec<VertexStream> = self
+            .stream_layouts(input)
+            .into_iter()
+            .map(|(binding, vertex_layout)| VertexStream {
+                binding,
+                locations: (0..bindings.len() as u32)
+                    .filter(|location| bindings[*location as usize] == binding)
+                    .collect(),
+                vertices: Vec::with_capacity(self.num_vertices() * vertex_layout.vertex_size()),
+                vertex_layout,
             })
-            .flatten()
-            .map(|f| f.to_ne_bytes())
-            .flatten()
             .collect();
-        let indices = mesh.indices.clone();
-
-        Ok(Self {
+        if streams.len() == 1 {
+            streams[0].vertices = self.vertices.clone();
+            return streams;
+        }
+        let vertex_size = self.vertex_layout.vertex_size();
+        for vertex in self.vertices.chunks_exact(vertex_size) {
+            let mut offset = 0;
+            for (component, binding) in self.vertex_layout.components.iter().zip(bindings.iter()) {
+                let stream = streams.iter_mut().find(|s| s.binding == *binding).unwrap();
+                stream
+                    .vertices
+                    .extend_from_slice(&vertex[offset..offset + component.size()]);
+                offset += component.size();
+            }
+        }
+        streams
+    }
+    /// Binding of every vertex component
+    fn component_bindings(&self, input: &VertexInput) -> Vec<u32> {
+        (0..self.vertex_layout.components.len() as u32)
+            .map(|location| {
+                input
+                    .fields
+                    .iter()
+                    .find(|f| f.location == location)
+                    .map(|f| f.binding)
+                    .unwrap_or(input.binding)
+            })
+            .collect()
+    }
+    /// Loads the first object of an OBJ, use `ObjScene` to get every object
+    pub fn from_obj_buf<B: BufRead, L: Fn(&Path) -> Result<Vec<u8>>>(
+        buffer: B,
+        file_loader: L,
+    ) -> Result<Self> {
+        Self::first_obj_model(ObjScene::from_obj_buf(buffer, file_loader)?)
+    }
+    /// Loads the first object of an OBJ, use `ObjScene` to get every object
+    pub fn from_obj(path: &str) -> Result<Self> {
+        Self::first_obj_model(ObjScene::from_obj(path)?)
+    }
+    /// Builds a mesh from vertices with a layout known at compile time
+    pub fn from_vertices<V: VertexTrait>(vertices: &[V], indices: Vec<u32>) -> Self {
+        let layout = V::vertex_layout();
+        let mut bytes = Vec::with_capacity(vertices.len() * layout.vertex_size());
+        for vertex in vertices.iter() {
+            vertex.write_bytes(&mut bytes);
+        }
+        Self {
+            vertices: bytes,
             indices,
-            vertices,
-            vertex_layout: VertexLayout {
-                components: vec![
-                    VertexComponent::Vec3F32,
-                    VertexComponent::Vec2F32,
-                    VertexComponent::Vec3F32,
-                ],
-            },
+            vertex_layout: layout,
+            topology: Topology::TriangleList,
+        }
+    }
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+    /// Builds a line strip through `points` with the same layout as `EasyMesh`,
+    /// normals point up and uvs run from 0 to 1 along the line
+    pub fn new_line_strip(points: &[Vector3<f32>]) -> Self {
+        let last = points.len().saturating_sub(1).max(1) as f32;
+        let vertices: Vec<Vertex> = points
+            .iter()
+            .enumerate()
+            .map(|(i, position)| Vertex {
+                position: *position,
+                uv: Vector2::new(i as f32 / last, 0.0),
+                normal: Vector3::new(0.0, 1.0, 0.0),
+            })
+            .collect();
+        Self::from_vertices(&vertices, (0..points.len() as u32).collect())
+            .with_topology(Topology::LineStrip)
+    }
+    /// Reads positions from the first vertex component, which by convention is
+    /// the position. Returns None if the first component is not 2 to 4 `f32`s.
+    pub fn positions(&self) -> Option<Vec<Vector3<f32>>> {
+        let count = match self.vertex_layout.components.first()? {
+            VertexComponent::Vec2F32 => 2,
+            VertexComponent::Vec3F32 | VertexComponent::Vec4F32 => 3,
+            _ => return None,
+        };
+        let vertex_size = self.vertex_layout.vertex_size();
+        Some(
+            self.vertices
+                .chunks_exact(vertex_size)
+                .map(|v| {
+                    let mut p = Vector3::zeros();
+                    for i in 0..count {
+                        let b = &v[i * 4..i * 4 + 4];
+                        p[i] = f32::from_ne_bytes([b[0], b[1], b[2], b[3]]);
+                    }
+                    p
+                })
+                .collect(),
+        )
+    }
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(self.positions()?.iter())
+    }
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        BoundingSphere::from_points(&self.positions()?)
+    }
+    /// Computes bounding box and sphere of positions, returns None if the mesh is
+    /// empty or does not have a position component
+    pub fn bounds(&self) -> Option<Bounds> {
+        let positions = self.positions()?;
+        Some(Bounds {
+            aabb: Aabb::from_points(positions.iter())?,
+            sphere: BoundingSphere::from_points(&positions)?,
         })
     }
-    pub fn from_obj(path: &str) -> Result<Self> {
-        return Self::from_obj_buf(
-            BufReader::new(File::open(Path::new(path))?),
-            get_buffer_from_fs,
-        );
+    fn first_obj_model(mut scene: ObjScene) -> Result<Self> {
+        if scene.models.is_empty() {
+            bail!("obj does not contain any faces")
+        }
+        Ok(scene.models.swap_remove(0).mesh)
     }
     pub fn new_triangle() -> Self {
         EasyMesh {
@@ -144,43 +327,43 @@ impl Mesh {
                 Vertex {
                     position: Vector3::new(1.0, 0.0, 1.0),
                     uv: Vector2::new(2.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(1.0, 0.0, 0.0),
                 },
                 Vertex {
                     position: Vector3::new(1.0, 0.0, 0.0),
                     uv: Vector2::new(2.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(1.0, 0.0, 0.0),
                 },
                 Vertex {
                     position: Vector3::new(1.0, 1.0, 0.0),
                     uv: Vector2::new(1.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(1.0, 0.0, 0.0),
                 },
                 Vertex {
                     position: Vector3::new(1.0, 1.0, 1.0),
                     uv: Vector2::new(1.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(1.0, 0.0, 0.0),
                 },
                 //face 1
                 Vertex {
                     position: Vector3::new(1.0, 0.0, 0.0),
                     uv: Vector2::new(3.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, -1.0),
                 },
                 Vertex {
                     position: Vector3::new(0.0, 0.0, 0.0),
                     uv: Vector2::new(3.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, -1.0),
                 },
                 Vertex {
                     position: Vector3::new(1.0, 1.0, 0.0),
                     uv: Vector2::new(2.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, -1.0),
                 },
                 Vertex {
                     position: Vector3::new(0.0, 1.0, 0.0),
                     uv: Vector2::new(2.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, -1.0),
                 },
                 //Face 2
                 //8
@@ -212,75 +395,75 @@ impl Mesh {
                 Vertex {
                     position: Vector3::new(0.0, 0.0, 1.0),
                     uv: Vector2::new(5.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, 1.0),
                 },
                 //13
                 Vertex {
                     position: Vector3::new(1.0, 0.0, 1.0),
                     uv: Vector2::new(5.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, 1.0),
                 },
                 //14
                 Vertex {
                     position: Vector3::new(0.0, 1.0, 1.0),
                     uv: Vector2::new(4.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, 1.0),
                 },
                 //15
                 Vertex {
                     position: Vector3::new(1.0, 1.0, 1.0),
                     uv: Vector2::new(4.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 0.0, 1.0),
                 },
                 //face 4
                 //16
                 Vertex {
                     position: Vector3::new(1.0, 1.0, 1.0),
                     uv: Vector2::new(0.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 1.0, 0.0),
                 },
                 //17
                 Vertex {
                     position: Vector3::new(1.0, 1.0, 0.0),
                     uv: Vector2::new(1.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 1.0, 0.0),
                 },
                 //18
                 Vertex {
                     position: Vector3::new(0.0, 1.0, 1.0),
                     uv: Vector2::new(0.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 1.0, 0.0),
                 },
                 //19
                 Vertex {
                     position: Vector3::new(0.0, 1.0, 0.0),
                     uv: Vector2::new(1.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, 1.0, 0.0),
                 },
                 //face 5
                 //20
                 Vertex {
                     position: Vector3::new(1.0, 0.0, 1.0),
                     uv: Vector2::new(5.0 / 6.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, -1.0, 0.0),
                 },
                 //21
                 Vertex {
                     position: Vector3::new(1.0, 0.0, 0.0),
                     uv: Vector2::new(5.0 / 6.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, -1.0, 0.0),
                 },
                 //22
                 Vertex {
                     position: Vector3::new(0.0, 0.0, 1.0),
                     uv: Vector2::new(1.0, 0.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, -1.0, 0.0),
                 },
                 //23
                 Vertex {
                     position: Vector3::new(0.0, 0.0, 0.0),
                     uv: Vector2::new(1.0, 1.0),
-                    normal: Vector3::new(-1.0, 0.0, 0.0),
+                    normal: Vector3::new(0.0, -1.0, 0.0),
                 },
             ],
             indices: vec![
@@ -305,6 +488,7 @@ impl Mesh {
         .into()
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, sukakpak_derive::Vertex)]
 pub struct Vertex {
     pub position: Vector3<f32>,
     pub uv: Vector2<f32>,
@@ -312,27 +496,145 @@ pub struct Vertex {
 }
 impl From<EasyMesh> for Mesh {
     fn from(mesh: EasyMesh) -> Self {
-        let len = std::mem::size_of::<Vertex>() * mesh.vertices.len();
-        let vertices: Vec<u8> = vec![0; len];
-        let mesh_ptr = mesh.vertices.as_ptr();
-        let data_ptr = vertices.as_ptr();
-        unsafe {
-            std::ptr::copy_nonoverlapping(mesh_ptr as *const u8, data_ptr as *mut u8, len);
-        }
-        Mesh {
-            vertices,
-            indices: mesh.indices,
-            vertex_layout: VertexLayout {
-                components: vec![
-                    VertexComponent::Vec3F32,
-                    VertexComponent::Vec2F32,
-                    VertexComponent::Vec3F32,
-                ],
-            },
-        }
+        Mesh::from_vertices(&mesh.vertices, mesh.indices)
     }
 }
+#[derive(Clone, Debug, PartialEq)]
 pub struct EasyMesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
 }
+impl EasyMesh {
+    /// Layout of meshes converted from `EasyMesh`: position, uv, normal
+    pub fn vertex_layout() -> VertexLayout {
+        Vertex::vertex_layout()
+    }
+    /// Builds an `EasyMesh` from positions and indices, uvs are set to zero and
+    /// normals are computed with `smooth_normals`
+    pub fn from_positions(positions: &[Vector3<f32>], indices: Vec<u32>) -> Self {
+        let mut mesh = Self {
+            vertices: positions
+                .iter()
+                .map(|position| Vertex {
+                    position: *position,
+                    uv: Vector2::zeros(),
+                    normal: Vector3::zeros(),
+                })
+                .collect(),
+            indices,
+        };
+        ops::smooth_normals(&mut mesh);
+        mesh
+    }
+}
+impl TryFrom<Mesh> for EasyMesh {
+    type Error = anyhow::Error;
+    /// Converts mesh with layout position, uv, normal
+    fn try_from(mesh: Mesh) -> Result<Self> {
+        if mesh.topology != Topology::TriangleList {
+            bail!("topology {:?} is not a triangle list", mesh.topology);
+        }
+        if mesh.vertex_layout != EasyMesh::vertex_layout() {
+            bail!(
+                "vertex layout {:?} is not position, uv, normal",
+                mesh.vertex_layout
+            );
+        }
+        let vertex_size = mesh.vertex_layout.vertex_size();
+        if !mesh.vertices.len().is_multiple_of(vertex_size) {
+            bail!(
+                "vertex buffer length {} is not a multiple of vertex size {}",
+                mesh.vertices.len(),
+                vertex_size
+            );
+        }
+        let vertices = mesh
+            .vertices
+            .chunks_exact(vertex_size)
+            .map(|v| {
+                let f: Vec<f32> = v
+                    .chunks_exact(4)
+                    .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                Vertex {
+                    position: Vector3::new(f[0], f[1], f[2]),
+                    uv: Vector2::new(f[3], f[4]),
+                    normal: Vector3::new(f[5], f[6], f[7]),
+                }
+            })
+            .collect();
+        Ok(Self {
+            vertices,
+            indices: mesh.indices,
+        })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    #[test]
+    fn cube_bounds() {
+        let bounds = Mesh::new_cube().bounds().unwrap();
+        assert_eq!(bounds.aabb.min, Vector3::zeros());
+        assert_eq!(bounds.aabb.max, Vector3::new(1.0, 1.0, 1.0));
+        assert!((bounds.sphere.center - Vector3::new(0.5, 0.5, 0.5)).norm() < 1e-4);
+    }
+    #[test]
+    fn cube_normals_point_outward() {
+        let cube: EasyMesh = Mesh::new_cube().try_into().unwrap();
+        let center = Vector3::new(0.5, 0.5, 0.5);
+        for vertex in cube.vertices.iter() {
+            assert!((vertex.position - center).dot(&vertex.normal) > 0.0);
+        }
+    }
+    #[test]
+    fn splits_vertex_streams() {
+        use ass_types::{Scalar, ShaderType, VertexField};
+        let cube = Mesh::new_cube();
+        assert_eq!(cube.index_width(), IndexWidth::U16);
+        assert_eq!(
+            cube.index_width().index_bytes(&cube.indices).len(),
+            cube.num_indices() * 2
+        );
+        assert_eq!(IndexWidth::for_indices(&[0, 70_000]), IndexWidth::U32);
+
+        let field = |location: u32, ty: ShaderType, binding: u32| VertexField {
+            ty,
+            location,
+            name: format!("input_{}", location),
+            binding,
+        };
+        let mut input = VertexInput {
+            binding: 0,
+            fields: vec![
+                field(0, ShaderType::Vec3(Scalar::F32), 0),
+                field(1, ShaderType::Vec2(Scalar::F32), 0),
+                field(2, ShaderType::Vec3(Scalar::F32), 0),
+            ],
+        };
+        let streams = cube.vertex_streams(&input);
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].vertices, cube.vertices);
+
+        // positions in their own buffer, uv and normal in a second one
+        input.fields[1].binding = 1;
+        input.fields[2].binding = 1;
+        let streams = cube.vertex_streams(&input);
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].locations, vec![0]);
+        assert_eq!(streams[1].locations, vec![1, 2]);
+        assert_eq!(streams[0].vertices.len(), cube.num_vertices() * 12);
+        assert_eq!(streams[1].vertices.len(), cube.num_vertices() * 20);
+        let vertex_size = cube.vertex_layout.vertex_size();
+        let last = cube.num_vertices() - 1;
+        assert_eq!(
+            &streams[0].vertices[last * 12..],
+            &cube.vertices[last * vertex_size..last * vertex_size + 12]
+        );
+        assert_eq!(
+            &streams[1].vertices[last * 20..],
+            &cube.vertices[last * vertex_size + 12..]
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Binary mesh format used to skip parsing OBJ and glTF files at startup.
+//!
+//! All header fields are little endian:
+//!
+//! | field            | type                                            |
+//! |------------------|-------------------------------------------------|
+//! | magic            | `b"SKPM"`                                       |
+//! | version          | `u32`                                           |
+//! | flags            | `u8`, see `FLAG_DEFLATE` and `FLAG_BIG_ENDIAN`  |
+//! | index width      | `u8`, 2 or 4 bytes                              |
+//! | topology         | `u8`, absent in version 1 which is triangles    |
+//! | component count  | `u16`                                           |
+//! | components       | one `u8` tag per `VertexComponent`              |
+//! | vertex blob size | `u64` in bytes                                  |
+//! | index count      | `u64`                                           |
+//!
+//! The header is followed by the vertex blob and then the index blob, both
+//! deflated together if `FLAG_DEFLATE` is set. Blobs are written in the
+//! endianness of the machine that saved them so loading on the same kind of
+//! machine is a copy.
+use super::{Mesh, Topology};
+use crate::{VertexComponent, VertexLayout};
+use anyhow::{Context, Result};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+    path::Path,
+};
+use thiserror::Error;
+const MAGIC: &[u8; 4] = b"SKPM";
+pub const MESH_CACHE_VERSION: u32 = 2;
+const FLAG_DEFLATE: u8 = 1;
+const FLAG_BIG_ENDIAN: u8 = 2;
+#[derive(Debug, Error)]
+pub enum MeshCacheError {
+    #[error("file is not a mesh cache")]
+    BadMagic,
+    #[error("mesh cache version {0} is not supported")]
+    UnsupportedVersion(u32),
+    #[error("unknown vertex component tag {0}")]
+    UnknownComponent(u8),
+    #[error("invalid index width {0}")]
+    InvalidIndexWidth(u8),
+    #[error("unknown topology tag {0}")]
+    UnknownTopology(u8),
+    #[error("mesh cache is truncated")]
+    Truncated,
+    #[error("vertex blob of {size} bytes is not a multiple of vertex size {vertex_size}")]
+    InvalidVertexBlob { size: usize, vertex_size: usize },
+    #[error("compressed payload does not inflate to the {expected} bytes given in the header")]
+    InflatedSizeMismatch { expected: usize },
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshCompression {
+    None,
+    /// Deflate, smaller files at the cost of slower loading
+    Deflate,
+}
+fn component_tag(component: &VertexComponent) -> u8 {
+    match component {
+        VertexComponent::Vec1F32 => 0,
+        VertexComponent::Vec2F32 => 1,
+        VertexComponent::Vec3F32 => 2,
+        VertexComponent::Vec4F32 => 3,
+        VertexComponent::Vec4U8Norm => 4,
+        VertexComponent::Vec2U16 => 5,
+        VertexComponent::Vec1U32 => 6,
+        VertexComponent::Vec1I32 => 7,
+        VertexComponent::Vec1F16 => 8,
+        VertexComponent::Vec2F16 => 9,
+        VertexComponent::Vec4F16 => 10,
+        VertexComponent::Vec4U16 => 11,
+    }
+}
+fn component_from_tag(tag: u8) -> Result<VertexComponent, MeshCacheError> {
+    Ok(match tag {
+        0 => VertexComponent::Vec1F32,
+        1 => VertexComponent::Vec2F32,
+        2 => VertexComponent::Vec3F32,
+        3 => VertexComponent::Vec4F32,
+        4 => VertexComponent::Vec4U8Norm,
+        5 => VertexComponent::Vec2U16,
+        6 => VertexComponent::Vec1U32,
+        7 => VertexComponent::Vec1I32,
+        8 => VertexComponent::Vec1F16,
+        9 => VertexComponent::Vec2F16,
+        10 => VertexComponent::Vec4F16,
+        11 => VertexComponent::Vec4U16,
+        _ => return Err(MeshCacheError::UnknownComponent(tag)),
+    })
+}
+fn topology_tag(topology: Topology) -> u8 {
+    match topology {
+        Topology::TriangleList => 0,
+        Topology::TriangleStrip => 1,
+        Topology::LineList => 2,
+        Topology::LineStrip => 3,
+        Topology::PointList => 4,
+    }
+}
+fn topology_from_tag(tag: u8) -> Result<Topology, MeshCacheError> {
+    Ok(match tag {
+        0 => Topology::TriangleList,
+        1 => Topology::TriangleStrip,
+        2 => Topology::LineList,
+        3 => Topology::LineStrip,
+        4 => Topology::PointList,
+        _ => return Err(MeshCacheError::UnknownTopology(tag)),
+    })
+}
+/// Reverses byte order of every scalar in `vertices`, used when the cache was
+/// written on a machine with different endianness
+fn swap_vertex_endianness(vertices: &mut [u8], layout: &VertexLayout) {
+    let vertex_size = layout.vertex_size();
+    for vertex in vertices.chunks_exact_mut(vertex_size) {
+        let mut offset = 0;
+        for component in layout.components.iter() {
+            let scalar_size = component.size() / component.num_components();
+            for scalar in vertex[offset..offset + component.size()].chunks_exact_mut(scalar_size) {
+                scalar.reverse();
+            }
+            offset += component.size();
+        }
+    }
+}
+struct Reader<'a> {
+    data: &'a [u8],
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MeshCacheError> {
+        if self.data.len() < len {
+            return Err(MeshCacheError::Truncated);
+        }
+        let (out, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(out)
+    }
+    fn u8(&mut self) -> Result<u8, MeshCacheError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, MeshCacheError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32, MeshCacheError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn u64(&mut self) -> Result<usize, MeshCacheError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()) as usize)
+    }
+}
+impl Mesh {
+    /// Serializes mesh into the mesh cache format. Indices are stored as `u16` if
+    /// they all fit.
+    pub fn to_cache_bytes(&self, compression: MeshCompression) -> Result<Vec<u8>> {
+        let index_width = self.index_width();
+        let mut flags = 0;
+        if compression == MeshCompression::Deflate {
+            flags |= FLAG_DEFLATE;
+        }
+        if cfg!(target_endian = "big") {
+            flags |= FLAG_BIG_ENDIAN;
+        }
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&MESH_CACHE_VERSION.to_le_bytes());
+        out.push(flags);
+        out.push(index_width.size() as u8);
+        out.push(topology_tag(self.topology));
+        out.extend_from_slice(&(self.vertex_layout.components.len() as u16).to_le_bytes());
+        out.extend(self.vertex_layout.components.iter().map(component_tag));
+        out.extend_from_slice(&(self.vertices.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(self.indices.len() as u64).to_le_bytes());
+
+        let mut payload = Vec::with_capacity(self.vertices.len() + self.indices.len() * 4);
+        payload.extend_from_slice(&self.vertices);
+        payload.append(&mut index_width.index_bytes(&self.indices));
+        match compression {
+            MeshCompression::None => out.append(&mut payload),
+            MeshCompression::Deflate => {
+                let mut encoder = DeflateEncoder::new(out, flate2::Compression::default());
+                encoder.write_all(&payload)?;
+                out = encoder.finish()?;
+            }
+        }
+        Ok(out)
+    }
+    /// Parses mesh from the mesh cache format
+    pub fn from_cache_bytes(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader { data };
+        if reader.take(4)? != MAGIC {
+            return Err(MeshCacheError::BadMagic.into());
+        }
+        let version = reader.u32()?;
+        if version != 1 && version != MESH_CACHE_VERSION {
+            return Err(MeshCacheError::UnsupportedVersion(version).into());
+        }
+        let flags = reader.u8()?;
+        let index_width = reader.u8()?;
+        if index_width != 2 && index_width != 4 {
+            return Err(MeshCacheError::InvalidIndexWidth(index_width).into());
+        }
+        let topology = if version == 1 {
+            Topology::TriangleList
+        } else {
+            topology_from_tag(reader.u8()?)?
+        };
+        let num_components = reader.u16()?;
+        let vertex_layout = VertexLayout {
+            components: (0..num_components)
+                .map(|_| component_from_tag(reader.u8()?))
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        let vertex_size = reader.u64()?;
+        let num_indices = reader.u64()?;
+        let layout_size = vertex_layout.vertex_size();
+        if layout_size == 0 || vertex_size % layout_size != 0 {
+            return Err(MeshCacheError::InvalidVertexBlob {
+                size: vertex_size,
+                vertex_size: layout_size,
+            }
+            .into());
+        }
+        let index_size = num_indices
+            .checked_mul(index_width as usize)
+            .ok_or(MeshCacheError::Truncated)?;
+        let payload_size = vertex_size
+            .checked_add(index_size)
+            .ok_or(MeshCacheError::Truncated)?;
+        let decompressed;
+        let payload = if flags & FLAG_DEFLATE != 0 {
+            // the header sizes bound the output so a corrupt stream can not inflate without limit
+            let mut buffer = Vec::new();
+            DeflateDecoder::new(reader.data)
+                .take(payload_size as u64 + 1)
+                .read_to_end(&mut buffer)
+                .context("failed to decompress mesh cache")?;
+            if buffer.len() != payload_size {
+                return Err(MeshCacheError::InflatedSizeMismatch {
+                    expected: payload_size,
+                }
+                .into());
+            }
+            decompressed = buffer;
+            &decompressed[..]
+        } else {
+            reader.data
+        };
+        let mut reader = Reader { data: payload };
+        let mut vertices = reader.take(vertex_size)?.to_vec();
+        let index_bytes = reader.take(index_size)?;
+        let swap = (flags & FLAG_BIG_ENDIAN != 0) != cfg!(target_endian = "big");
+        if swap {
+            swap_vertex_endianness(&mut vertices, &vertex_layout);
+        }
+        let indices = if index_width == 2 {
+            index_bytes
+                .chunks_exact(2)
+                .map(|b| {
+                    let b = [b[0], b[1]];
+                    if swap {
+                        u16::from_ne_bytes(b).swap_bytes() as u32
+                    } else {
+                        u16::from_ne_bytes(b) as u32
+                    }
+                })
+                .collect()
+        } else {
+            index_bytes
+                .chunks_exact(4)
+                .map(|b| {
+                    let index = u32::from_ne_bytes(b.try_into().unwrap());
+                    if swap {
+                        index.swap_bytes()
+                    } else {
+                        index
+                    }
+                })
+                .collect()
+        };
+        Ok(Self {
+            vertices,
+            indices,
+            vertex_layout,
+            topology,
+        })
+    }
+    /// Saves mesh in the mesh cache format
+    pub fn save<P: AsRef<Path>>(&self, path: P, compression: MeshCompression) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_cache_bytes(compression)?)
+            .with_context(|| format!("failed to write mesh cache: {}", path.display()))
+    }
+    /// Loads mesh saved with `Mesh::save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .with_context(|| format!("failed to read mesh cache: {}", path.display()))?;
+        Self::from_cache_bytes(&data)
+            .with_context(|| format!("failed to load mesh cache: {}", path.display()))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector3;
+    #[test]
+    fn round_trip() {
+        let mesh = Mesh::new_uv_sphere(1.0, 16, 8);
+        for compression in [MeshCompression::None, MeshCompression::Deflate] {
+            let bytes = mesh.to_cache_bytes(compression).unwrap();
+            assert_eq!(Mesh::from_cache_bytes(&bytes).unwrap(), mesh);
+        }
+        let uncompressed = mesh.to_cache_bytes(MeshCompression::None).unwrap();
+        let header = 4 + 4 + 1 + 1 + 1 + 2 + 3 + 8 + 8;
+        assert_eq!(
+            uncompressed.len(),
+            header + mesh.vertices.len() + mesh.indices.len() * 2
+        );
+        let mut wide = mesh.clone();
+        wide.indices[0] = 70_000;
+        let bytes = wide.to_cache_bytes(MeshCompression::None).unwrap();
+        assert_eq!(Mesh::from_cache_bytes(&bytes).unwrap(), wide);
+
+        let line = Mesh::new_line_strip(&[Vector3::zeros(), Vector3::new(1.0, 0.0, 0.0)]);
+        let bytes = line.to_cache_bytes(MeshCompression::None).unwrap();
+        assert_eq!(Mesh::from_cache_bytes(&bytes).unwrap(), line);
+        // version 1 caches have no topology byte and are always triangles
+        let mut version_1 = uncompressed.clone();
+        version_1[4] = 1;
+        version_1.remove(10);
+        assert_eq!(Mesh::from_cache_bytes(&version_1).unwrap(), mesh);
+    }
+    #[test]
+    fn rejects_invalid() {
+        let bytes = Mesh::new_cube()
+            .to_cache_bytes(MeshCompression::None)
+            .unwrap();
+        assert!(Mesh::from_cache_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Mesh::from_cache_bytes(b"OBJ\n").is_err());
+        let mut version = bytes.clone();
+        version[4] = 99;
+        assert!(matches!(
+            Mesh::from_cache_bytes(&version)
+                .unwrap_err()
+                .downcast_ref::<MeshCacheError>(),
+            Some(MeshCacheError::UnsupportedVersion(99))
+        ));
+    }
+    #[test]
+    fn inflated_size_mismatch() {
+        let mesh = Mesh::new_cube();
+        let bytes = mesh.to_cache_bytes(MeshCompression::Deflate).unwrap();
+        let num_indices = 4 + 4 + 1 + 1 + 2 + mesh.vertex_layout.components.len() + 8;
+        for delta in [-1, 1] {
+            let mut corrupt = bytes.clone();
+            let count = mesh.indices.len() as i64 + delta;
+            corrupt[num_indices..num_indices + 8].copy_from_slice(&(count as u64).to_le_bytes());
+            assert!(matches!(
+                Mesh::from_cache_bytes(&corrupt)
+                    .unwrap_err()
+                    .downcast_ref::<MeshCacheError>(),
+                Some(MeshCacheError::InflatedSizeMismatch { .. })
+            ));
+        }
+    }
+}
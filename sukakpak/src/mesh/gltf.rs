@@ -0,0 +1,729 @@
+use super::{Mesh, Topology, VertexComponent, VertexLayout};
+use crate::{
+    AnimationClip, Channel, ChannelProperty, Interpolation, Joint, JointTransform, Skeleton,
+};
+use anyhow::{anyhow, Context, Result};
+use image::RgbaImage;
+use nalgebra::{Matrix4, Quaternion, UnitQuaternion, Vector3, Vector4};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GltfError {
+    #[error("glb file references BIN chunk but it is missing")]
+    MissingBlob,
+    #[error("primitive {primitive} of mesh {mesh} has no POSITION attribute")]
+    MissingPositions { mesh: usize, primitive: usize },
+    #[error("invalid data uri: {uri}")]
+    InvalidDataUri { uri: String },
+    #[error("buffer view for image {image} is out of range")]
+    ImageOutOfRange { image: usize },
+    #[error("{attribute} of primitive {primitive} of mesh {mesh} has {count} elements but POSITION has {positions}")]
+    AttributeCountMismatch {
+        mesh: usize,
+        primitive: usize,
+        attribute: &'static str,
+        count: usize,
+        positions: usize,
+    },
+    #[error("node {node} is reached more than once from the scene roots, nodes must form a tree")]
+    NodeRevisited { node: usize },
+    #[error("skin {skin} has {joints} joints but {matrices} inverse bind matrices")]
+    InverseBindMatrixCountMismatch {
+        skin: usize,
+        joints: usize,
+        matrices: usize,
+    },
+}
+/// Converts a glTF primitive mode, line loops are closed into strips and
+/// triangle fans are expanded into lists
+fn topology_from_mode(mode: gltf::mesh::Mode, indices: Vec<u32>) -> (Topology, Vec<u32>) {
+    use gltf::mesh::Mode;
+    match mode {
+        Mode::Points => (Topology::PointList, indices),
+        Mode::Lines => (Topology::LineList, indices),
+        Mode::LineStrip => (Topology::LineStrip, indices),
+        Mode::LineLoop => {
+            let mut indices = indices;
+            if let Some(first) = indices.first().copied() {
+                indices.push(first);
+            }
+            (Topology::LineStrip, indices)
+        }
+        Mode::Triangles => (Topology::TriangleList, indices),
+        Mode::TriangleStrip => (Topology::TriangleStrip, indices),
+        Mode::TriangleFan => (
+            Topology::TriangleList,
+            (2..indices.len())
+                .flat_map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+        ),
+    }
+}
+/// Primitive of a glTF mesh. Vertices are interleaved in the order
+/// position, uv (if present), normal (if present), joints and weights (if skinned).
+/// Joints are `Vec4U16` indices into `GltfSkin::joints` and weights are `Vec4F32`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfPrimitive {
+    pub mesh: Mesh,
+    /// Index into `GltfScene::materials`
+    pub material: Option<usize>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color_factor: Vector4<f32>,
+    /// Index into `GltfScene::textures`
+    pub base_color_texture: Option<usize>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// Transform relative to parent
+    pub transform: Matrix4<f32>,
+    /// `transform` split into translation, rotation and scale
+    pub local_transform: JointTransform,
+    /// Transform relative to scene root
+    pub world_transform: Matrix4<f32>,
+    /// Index into `GltfScene::meshes`
+    pub mesh: Option<usize>,
+    /// Index into `GltfScene::skins`
+    pub skin: Option<usize>,
+    pub children: Vec<usize>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfSkin {
+    pub name: Option<String>,
+    /// Node indices of joints, vertex joint attributes index into this list
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+    pub skeleton: Option<usize>,
+}
+/// Contents of a glTF or GLB file. All cross references are indices into the
+/// vectors of the scene and match the indices used in the source file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub nodes: Vec<GltfNode>,
+    /// Root nodes of the default scene
+    pub roots: Vec<usize>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<RgbaImage>,
+    pub skins: Vec<GltfSkin>,
+    pub animations: Vec<GltfAnimation>,
+}
+/// Animation with channels targeting nodes, use `GltfScene::animation_clip` to
+/// get an animation for a skin
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfAnimation {
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+}
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>> {
+    let data = uri
+        .split_once(";base64,")
+        .map(|(_header, data)| data)
+        .ok_or_else(|| GltfError::InvalidDataUri {
+            uri: uri.chars().take(32).collect(),
+        })?;
+    Ok(base64::decode(data)?)
+}
+/// decodes %XX escapes used in relative uris
+fn decode_uri_path(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        if let Some(byte) = escape {
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+fn load_uri<L: Fn(&str) -> Result<Vec<u8>>>(uri: &str, uri_loader: &L) -> Result<Vec<u8>> {
+    if uri.starts_with("data:") {
+        decode_data_uri(uri)
+    } else {
+        let path = decode_uri_path(uri);
+        uri_loader(&path).with_context(|| format!("failed to load uri: {}", path))
+    }
+}
+fn matrix_from_cols(cols: [[f32; 4]; 4]) -> Matrix4<f32> {
+    Matrix4::from_iterator(cols.iter().flatten().copied())
+}
+impl GltfScene {
+    /// Loads glTF or GLB file, external buffers and images are resolved relative to the file
+    pub fn from_gltf(path: &str) -> Result<Self> {
+        let path = Path::new(path);
+        let data = std::fs::read(path)?;
+        let directory = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        Self::from_gltf_buf(&data, |uri| Ok(std::fs::read(directory.join(uri))?))
+    }
+    /// Loads glTF or GLB from memory. `uri_loader` is called for every external
+    /// buffer or image, data uris are decoded without calling it.
+    pub fn from_gltf_buf<L: Fn(&str) -> Result<Vec<u8>>>(
+        data: &[u8],
+        uri_loader: L,
+    ) -> Result<Self> {
+        let gltf = ::gltf::Gltf::from_slice(data)?;
+        let mut blob = gltf.blob.clone();
+        let buffers = gltf
+            .buffers()
+            .map(|buffer| match buffer.source() {
+                ::gltf::buffer::Source::Bin => blob.take().ok_or(GltfError::MissingBlob.into()),
+                ::gltf::buffer::Source::Uri(uri) => load_uri(uri, &uri_loader),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let get_buffer = |buffer: ::gltf::Buffer| buffers.get(buffer.index()).map(|b| b.as_slice());
+
+        let textures = gltf
+            .images()
+            .map(|image| {
+                let bytes = match image.source() {
+                    ::gltf::image::Source::View { view, .. } => {
+                        let buffer = &buffers[view.buffer().index()];
+                        buffer
+                            .get(view.offset()..view.offset() + view.length())
+                            .ok_or(GltfError::ImageOutOfRange {
+                                image: image.index(),
+                            })?
+                            .to_vec()
+                    }
+                    ::gltf::image::Source::Uri { uri, .. } => load_uri(uri, &uri_loader)?,
+                };
+                Ok(image::load_from_memory(&bytes)
+                    .with_context(|| format!("failed to decode image {}", image.index()))?
+                    .to_rgba8())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let materials = gltf
+            .materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+                GltfMaterial {
+                    name: material.name().map(|n| n.to_string()),
+                    base_color_factor: pbr.base_color_factor().into(),
+                    base_color_texture: pbr
+                        .base_color_texture()
+                        .map(|info| info.texture().source().index()),
+                }
+            })
+            .collect();
+        let meshes = gltf
+            .meshes()
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives()
+                    .map(|primitive| {
+                        let reader = primitive.reader(get_buffer);
+                        let positions: Vec<[f32; 3]> = reader
+                            .read_positions()
+                            .ok_or(GltfError::MissingPositions {
+                                mesh: mesh.index(),
+                                primitive: primitive.index(),
+                            })?
+                            .collect();
+                        let uvs: Option<Vec<[f32; 2]>> =
+                            reader.read_tex_coords(0).map(|uv| uv.into_f32().collect());
+                        let normals: Option<Vec<[f32; 3]>> =
+                            reader.read_normals().map(|n| n.collect());
+                        let joints: Option<Vec<[u16; 4]>> =
+                            reader.read_joints(0).map(|j| j.into_u16().collect());
+                        let weights: Option<Vec<[f32; 4]>> =
+                            reader.read_weights(0).map(|w| w.into_f32().collect());
+                        let attribute_counts = [
+                            ("TEXCOORD_0", uvs.as_ref().map(|a| a.len())),
+                            ("NORMAL", normals.as_ref().map(|a| a.len())),
+                            ("JOINTS_0", joints.as_ref().map(|a| a.len())),
+                            ("WEIGHTS_0", weights.as_ref().map(|a| a.len())),
+                        ];
+                        for (attribute, count) in attribute_counts.iter() {
+                            if let Some(count) = *count {
+                                if count != positions.len() {
+                                    return Err(GltfError::AttributeCountMismatch {
+                                        mesh: mesh.index(),
+                                        primitive: primitive.index(),
+                                        attribute,
+                                        count,
+                                        positions: positions.len(),
+                                    }
+                                    .into());
+                                }
+                            }
+                        }
+                        let skinning = joints.zip(weights);
+                        let indices = reader
+                            .read_indices()
+                            .map(|i| i.into_u32().collect())
+                            .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+                        let mut components = vec![VertexComponent::Vec3F32];
+                        if uvs.is_some() {
+                            components.push(VertexComponent::Vec2F32);
+                        }
+                        if normals.is_some() {
+                            components.push(VertexComponent::Vec3F32);
+                        }
+                        if skinning.is_some() {
+                            components.push(VertexComponent::Vec4U16);
+                            components.push(VertexComponent::Vec4F32);
+                        }
+                        let mut vertex_data: Vec<u8> = vec![];
+                        let push_floats = |floats: &[f32], data: &mut Vec<u8>| {
+                            data.extend(floats.iter().flat_map(|f| f.to_ne_bytes()))
+                        };
+                        for i in 0..positions.len() {
+                            push_floats(&positions[i], &mut vertex_data);
+                            if let Some(uvs) = &uvs {
+                                push_floats(&uvs[i], &mut vertex_data);
+                            }
+                            if let Some(normals) = &normals {
+                                push_floats(&normals[i], &mut vertex_data);
+                            }
+                            if let Some((joints, weights)) = &skinning {
+                                vertex_data.extend(joints[i].iter().flat_map(|j| j.to_ne_bytes()));
+                                push_floats(&weights[i], &mut vertex_data);
+                            }
+                        }
+                        let (topology, indices) = topology_from_mode(primitive.mode(), indices);
+                        Ok(GltfPrimitive {
+                            mesh: Mesh {
+                                vertices: vertex_data,
+                                indices,
+                                vertex_layout: VertexLayout { components },
+                                topology,
+                            },
+                            material: primitive.material().index(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(GltfMesh {
+                    name: mesh.name().map(|n| n.to_string()),
+                    primitives,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut nodes: Vec<GltfNode> = gltf
+            .nodes()
+            .map(|node| {
+                let transform = matrix_from_cols(node.transform().matrix());
+                let (translation, rotation, scale) = node.transform().decomposed();
+                GltfNode {
+                    name: node.name().map(|n| n.to_string()),
+                    transform,
+                    local_transform: JointTransform {
+                        translation: translation.into(),
+                        rotation: UnitQuaternion::from_quaternion(Quaternion::from(Vector4::from(
+                            rotation,
+                        ))),
+                        scale: scale.into(),
+                    },
+                    world_transform: transform,
+                    mesh: node.mesh().map(|m| m.index()),
+                    skin: node.skin().map(|s| s.index()),
+                    children: node.children().map(|c| c.index()).collect(),
+                }
+            })
+            .collect();
+        let roots: Vec<usize> = gltf
+            .default_scene()
+            .or_else(|| gltf.scenes().next())
+            .map(|scene| scene.nodes().map(|n| n.index()).collect())
+            .unwrap_or_default();
+        let mut stack: Vec<(usize, Matrix4<f32>)> =
+            roots.iter().map(|r| (*r, Matrix4::identity())).collect();
+        // a cycle would never end and a node with two parents would only keep one transform
+        let mut visited = vec![false; nodes.len()];
+        while let Some((index, parent)) = stack.pop() {
+            if std::mem::replace(&mut visited[index], true) {
+                return Err(GltfError::NodeRevisited { node: index }.into());
+            }
+            let world = parent * nodes[index].transform;
+            nodes[index].world_transform = world;
+            stack.extend(nodes[index].children.iter().map(|c| (*c, world)));
+        }
+        let skins = gltf
+            .skins()
+            .map(|skin| {
+                let joints: Vec<usize> = skin.joints().map(|j| j.index()).collect();
+                let inverse_bind_matrices = skin
+                    .reader(get_buffer)
+                    .read_inverse_bind_matrices()
+                    .map(|m| m.map(matrix_from_cols).collect())
+                    .unwrap_or_else(|| vec![Matrix4::identity(); joints.len()]);
+                GltfSkin {
+                    name: skin.name().map(|n| n.to_string()),
+                    joints,
+                    inverse_bind_matrices,
+                    skeleton: skin.skeleton().map(|s| s.index()),
+                }
+            })
+            .collect();
+        let animations = gltf
+            .animations()
+            .map(|animation| GltfAnimation {
+                name: animation.name().map(|n| n.to_string()),
+                channels: animation
+                    .channels()
+                    .filter_map(|channel| {
+                        let reader = channel.reader(get_buffer);
+                        let times: Vec<f32> = reader.read_inputs()?.collect();
+                        let (property, values): (ChannelProperty, Vec<Vector4<f32>>) =
+                            match reader.read_outputs()? {
+                                ::gltf::animation::util::ReadOutputs::Translations(t) => (
+                                    ChannelProperty::Translation,
+                                    t.map(|v| Vector3::from(v).push(0.0)).collect(),
+                                ),
+                                ::gltf::animation::util::ReadOutputs::Rotations(r) => (
+                                    ChannelProperty::Rotation,
+                                    r.into_f32().map(Vector4::from).collect(),
+                                ),
+                                ::gltf::animation::util::ReadOutputs::Scales(s) => (
+                                    ChannelProperty::Scale,
+                                    s.map(|v| Vector3::from(v).push(0.0)).collect(),
+                                ),
+                                // morph targets are not supported
+                                ::gltf::animation::util::ReadOutputs::MorphTargetWeights(_) => {
+                                    return None
+                                }
+                            };
+                        Some(Channel {
+                            target: channel.target().node().index(),
+                            property,
+                            interpolation: match channel.sampler().interpolation() {
+                                ::gltf::animation::Interpolation::Step => Interpolation::Step,
+                                ::gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                                ::gltf::animation::Interpolation::CubicSpline => {
+                                    Interpolation::CubicSpline
+                                }
+                            },
+                            times,
+                            values,
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(Self {
+            meshes,
+            nodes,
+            roots,
+            materials,
+            textures,
+            skins,
+            animations,
+        })
+    }
+    /// Index of the parent of every node
+    fn node_parents(&self) -> Vec<Option<usize>> {
+        let mut parents = vec![None; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            for child in node.children.iter() {
+                parents[*child] = Some(index);
+            }
+        }
+        parents
+    }
+    /// Builds skeleton of a skin. Joints whose parent node is not a joint are
+    /// roots and the world transform of the first root's parent is used as the
+    /// skeleton's root transform.
+    pub fn skeleton(&self, skin: usize) -> Result<Skeleton> {
+        let skin_index = skin;
+        let skin = self
+            .skins
+            .get(skin)
+            .ok_or_else(|| anyhow!("skin {} does not exist", skin))?;
+        if skin.joints.len() != skin.inverse_bind_matrices.len() {
+            return Err(GltfError::InverseBindMatrixCountMismatch {
+                skin: skin_index,
+                joints: skin.joints.len(),
+                matrices: skin.inverse_bind_matrices.len(),
+            }
+            .into());
+        }
+        let parents = self.node_parents();
+        let joint_of = |node: usize| skin.joints.iter().position(|j| *j == node);
+        let mut root_transform = None;
+        let joints = skin
+            .joints
+            .iter()
+            .zip(skin.inverse_bind_matrices.iter())
+            .map(|(node, inverse_bind_matrix)| {
+                let parent = parents[*node].and_then(joint_of);
+                if parent.is_none() && root_transform.is_none() {
+                    root_transform = Some(
+                        parents[*node]
+                            .map(|p| self.nodes[p].world_transform)
+                            .unwrap_or_else(Matrix4::identity),
+                    );
+                }
+                Joint {
+                    name: self.nodes[*node].name.clone(),
+                    parent,
+                    rest: self.nodes[*node].local_transform,
+                    inverse_bind_matrix: *inverse_bind_matrix,
+                }
+            })
+            .collect();
+        Skeleton::new(joints, root_transform.unwrap_or_else(Matrix4::identity))
+    }
+    /// Animation retargeted to the joints of a skin, channels targeting nodes
+    /// outside of the skin are dropped
+    pub fn animation_clip(&self, animation: usize, skin: usize) -> Result<AnimationClip> {
+        let gltf_animation = self
+            .animations
+            .get(animation)
+            .ok_or_else(|| anyhow!("animation {} does not exist", animation))?;
+        let joints = &self
+            .skins
+            .get(skin)
+            .ok_or_else(|| anyhow!("skin {} does not exist", skin))?
+            .joints;
+        AnimationClip::new(
+            gltf_animation.name.clone(),
+            gltf_animation
+                .channels
+                .iter()
+                .filter_map(|channel| {
+                    let target = joints.iter().position(|j| *j == channel.target)?;
+                    Some(Channel {
+                        target,
+                        ..channel.clone()
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn triangle_gltf() -> String {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let indices: [u32; 3] = [0, 1, 2];
+        let bytes: Vec<u8> = positions
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .chain(indices.iter().flat_map(|i| i.to_le_bytes()))
+            .collect();
+        format!(
+            r#"{{
+            "asset": {{"version": "2.0"}},
+            "scene": 0,
+            "scenes": [{{"nodes": [0]}}],
+            "nodes": [
+                {{"name": "root", "translation": [1.0, 0.0, 0.0], "children": [1]}},
+                {{"name": "child", "mesh": 0, "translation": [0.0, 2.0, 0.0]}}
+            ],
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}],
+            "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{data}"}}],
+            "bufferViews": [
+                {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                {{"buffer": 0, "byteOffset": 36, "byteLength": 12}}
+            ],
+            "accessors": [
+                {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+                {{"bufferView": 1, "componentType": 5125, "count": 3, "type": "SCALAR"}}
+            ]
+        }}"#,
+            len = bytes.len(),
+            data = base64::encode(&bytes)
+        )
+    }
+    #[test]
+    fn load_triangle() {
+        let scene =
+            GltfScene::from_gltf_buf(triangle_gltf().as_bytes(), |_| panic!("no external uris"))
+                .unwrap();
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(
+            mesh.vertex_layout.components,
+            vec![VertexComponent::Vec3F32]
+        );
+        assert_eq!(mesh.vertices.len(), 9 * std::mem::size_of::<f32>());
+        assert_eq!(mesh.topology, Topology::TriangleList);
+        assert_eq!(
+            topology_from_mode(gltf::mesh::Mode::TriangleFan, vec![0, 1, 2, 3]),
+            (Topology::TriangleList, vec![0, 1, 2, 0, 2, 3])
+        );
+        assert_eq!(
+            topology_from_mode(gltf::mesh::Mode::LineLoop, vec![0, 1, 2]),
+            (Topology::LineStrip, vec![0, 1, 2, 0])
+        );
+        let child = &scene.nodes[1];
+        assert_eq!(
+            child
+                .world_transform
+                .transform_point(&nalgebra::Point3::origin()),
+            nalgebra::Point3::new(1.0, 2.0, 0.0)
+        );
+    }
+    #[test]
+    fn attribute_count_mismatch() {
+        // NORMAL accessor only covers two of the three positions
+        let gltf = triangle_gltf()
+            .replace(
+                r#""attributes": {"POSITION": 0}"#,
+                r#""attributes": {"POSITION": 0, "NORMAL": 2}"#,
+            )
+            .replace(
+                r#""type": "SCALAR"}"#,
+                r#""type": "SCALAR"},
+                {"bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3"}"#,
+            );
+        let err =
+            GltfScene::from_gltf_buf(gltf.as_bytes(), |_| panic!("no external uris")).unwrap_err();
+        match err.downcast_ref::<GltfError>() {
+            Some(GltfError::AttributeCountMismatch {
+                attribute,
+                count,
+                positions,
+                ..
+            }) => {
+                assert_eq!(*attribute, "NORMAL");
+                assert_eq!(*count, 2);
+                assert_eq!(*positions, 3);
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+    #[test]
+    fn node_cycle() {
+        let gltf = triangle_gltf().replace(
+            r#""mesh": 0, "translation": [0.0, 2.0, 0.0]}"#,
+            r#""mesh": 0, "translation": [0.0, 2.0, 0.0], "children": [0]}"#,
+        );
+        let err =
+            GltfScene::from_gltf_buf(gltf.as_bytes(), |_| panic!("no external uris")).unwrap_err();
+        match err.downcast_ref::<GltfError>() {
+            Some(GltfError::NodeRevisited { node }) => assert_eq!(*node, 0),
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+    /// Two joint arm with a skinned mesh and an animation bending the elbow by a quarter turn
+    fn skinned_gltf() -> String {
+        let quarter =
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2);
+        let mut bytes: Vec<u8> = vec![];
+        for f in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0] {
+            bytes.extend_from_slice(&f.to_le_bytes());
+        }
+        for j in [0u16, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0] {
+            bytes.extend_from_slice(&j.to_le_bytes());
+        }
+        for w in [1.0f32, 0.0, 0.0, 0.0].repeat(3) {
+            bytes.extend_from_slice(&w.to_le_bytes());
+        }
+        for f in [0.0f32, 1.0] {
+            bytes.extend_from_slice(&f.to_le_bytes());
+        }
+        for f in UnitQuaternion::identity()
+            .coords
+            .iter()
+            .chain(quarter.coords.iter())
+        {
+            bytes.extend_from_slice(&f.to_le_bytes());
+        }
+        format!(
+            r#"{{
+            "asset": {{"version": "2.0"}},
+            "scenes": [{{"nodes": [0, 3]}}],
+            "nodes": [
+                {{"name": "armature", "translation": [0.0, 0.0, 5.0], "children": [1]}},
+                {{"name": "shoulder", "children": [2]}},
+                {{"name": "elbow", "translation": [0.0, 1.0, 0.0]}},
+                {{"mesh": 0, "skin": 0}}
+            ],
+            "skins": [{{"joints": [1, 2]}}],
+            "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2}}}}]}}],
+            "animations": [{{
+                "name": "bend",
+                "channels": [{{"sampler": 0, "target": {{"node": 2, "path": "rotation"}}}}],
+                "samplers": [{{"input": 3, "output": 4}}]
+            }}],
+            "buffers": [{{"byteLength": {len}, "uri": "data:application/octet-stream;base64,{data}"}}],
+            "bufferViews": [
+                {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                {{"buffer": 0, "byteOffset": 36, "byteLength": 24}},
+                {{"buffer": 0, "byteOffset": 60, "byteLength": 48}},
+                {{"buffer": 0, "byteOffset": 108, "byteLength": 8}},
+                {{"buffer": 0, "byteOffset": 116, "byteLength": 32}}
+            ],
+            "accessors": [
+                {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 2.0, 0.0]}},
+                {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "VEC4"}},
+                {{"bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4"}},
+                {{"bufferView": 3, "componentType": 5126, "count": 2, "type": "SCALAR",
+                  "min": [0.0], "max": [1.0]}},
+                {{"bufferView": 4, "componentType": 5126, "count": 2, "type": "VEC4"}}
+            ]
+        }}"#,
+            len = bytes.len(),
+            data = base64::encode(&bytes)
+        )
+    }
+    #[test]
+    fn load_skin_and_animation() {
+        let quarter =
+            UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2);
+        let gltf = skinned_gltf();
+        let scene = GltfScene::from_gltf_buf(gltf.as_bytes(), |_| unreachable!()).unwrap();
+        let mesh = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(
+            mesh.vertex_layout.components,
+            vec![
+                VertexComponent::Vec3F32,
+                VertexComponent::Vec4U16,
+                VertexComponent::Vec4F32
+            ]
+        );
+        let skeleton = scene.skeleton(0).unwrap();
+        let parents: Vec<Option<usize>> = skeleton.joints().iter().map(|j| j.parent).collect();
+        assert_eq!(parents, vec![None, Some(0)]);
+        assert_eq!(
+            skeleton.root_transform(),
+            Matrix4::new_translation(&Vector3::new(0.0, 0.0, 5.0))
+        );
+        let clip = scene.animation_clip(0, 0).unwrap();
+        assert_eq!(clip.channels[0].target, 1);
+        assert_eq!(clip.duration, 1.0);
+        let pose = clip.sample(&skeleton, 1.0, false);
+        assert!(pose.joints[1].rotation.angle_to(&quarter) < 1e-5);
+        assert_eq!(pose.joints[1].translation, Vector3::new(0.0, 1.0, 0.0));
+    }
+    #[test]
+    fn inverse_bind_matrix_count_mismatch() {
+        let mut scene =
+            GltfScene::from_gltf_buf(skinned_gltf().as_bytes(), |_| unreachable!()).unwrap();
+        scene.skins[0].inverse_bind_matrices.pop();
+        let err = scene.skeleton(0).unwrap_err();
+        match err.downcast_ref::<GltfError>() {
+            Some(GltfError::InverseBindMatrixCountMismatch {
+                skin,
+                joints,
+                matrices,
+            }) => assert_eq!((*skin, *joints, *matrices), (0, 2, 1)),
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use super::{ops, EasyMesh, Mesh, Vertex};
+use anyhow::{anyhow, Context, Result};
+use image::RgbaImage;
+use nalgebra::{Vector2, Vector3};
+use std::{
+    io::{BufRead, BufReader, Cursor},
+    path::{Path, PathBuf},
+};
+use tobj::{load_mtl_buf, load_obj_buf, LoadError, LoadOptions, MTLLoadResult};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub diffuse: Vector3<f32>,
+    pub diffuse_texture: Option<RgbaImage>,
+}
+/// Object in an OBJ file. Vertices always have the layout position, uv, normal,
+/// missing uvs are set to zero and missing normals are generated from the faces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjModel {
+    pub name: String,
+    pub mesh: Mesh,
+    /// Index into `ObjScene::materials`
+    pub material: Option<usize>,
+}
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjScene {
+    pub models: Vec<ObjModel>,
+    pub materials: Vec<ObjMaterial>,
+}
+impl ObjScene {
+    /// Loads OBJ file, `.mtl` files and textures are resolved relative to the OBJ
+    pub fn from_obj(path: &str) -> Result<Self> {
+        let path = Path::new(path);
+        let directory = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("failed to open obj: {}", path.display()))?;
+        Self::from_obj_buf(BufReader::new(file), |p| {
+            std::fs::read(directory.join(p))
+                .with_context(|| format!("failed to read: {}", directory.join(p).display()))
+        })
+    }
+    /// Loads OBJ from a buffer. `file_loader` is used to load `.mtl` files and
+    /// diffuse textures referenced by the OBJ.
+    pub fn from_obj_buf<B: BufRead, L: Fn(&Path) -> Result<Vec<u8>>>(
+        mut buffer: B,
+        file_loader: L,
+    ) -> Result<Self> {
+        let mtl_loader = |path: &Path| -> MTLLoadResult {
+            let data = file_loader(path).map_err(|_| LoadError::OpenFileFailed)?;
+            load_mtl_buf(&mut Cursor::new(data))
+        };
+        let (models, materials) = load_obj_buf(
+            &mut buffer,
+            &LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+            mtl_loader,
+        )?;
+        let materials = materials
+            .map_err(|e| anyhow!("failed to load materials: {}", e))?
+            .drain(..)
+            .map(|material| {
+                let diffuse_texture = match &material.diffuse_texture {
+                    Some(texture) => {
+                        let path = PathBuf::from(texture);
+                        let data = file_loader(&path)?;
+                        Some(
+                            image::load_from_memory(&data)
+                                .with_context(|| format!("failed to decode: {}", texture))?
+                                .to_rgba8(),
+                        )
+                    }
+                    None => None,
+                };
+                Ok(ObjMaterial {
+                    name: material.name,
+                    diffuse: material.diffuse.unwrap_or([1.0; 3]).into(),
+                    diffuse_texture,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let models = models
+            .iter()
+            .filter(|model| !model.mesh.indices.is_empty())
+            .map(|model| {
+                let mesh = &model.mesh;
+                let num_vertices = mesh.positions.len() / 3;
+                let has_normals = mesh.normals.len() == num_vertices * 3;
+                let has_uv = mesh.texcoords.len() == num_vertices * 2;
+                let mut easy_mesh = EasyMesh {
+                    vertices: (0..num_vertices)
+                        .map(|i| Vertex {
+                            position: Vector3::new(
+                                mesh.positions[i * 3],
+                                mesh.positions[i * 3 + 1],
+                                mesh.positions[i * 3 + 2],
+                            ),
+                            uv: if has_uv {
+                                Vector2::new(mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1])
+                            } else {
+                                Vector2::zeros()
+                            },
+                            normal: if has_normals {
+                                Vector3::new(
+                                    mesh.normals[i * 3],
+                                    mesh.normals[i * 3 + 1],
+                                    mesh.normals[i * 3 + 2],
+                                )
+                            } else {
+                                Vector3::zeros()
+                            },
+                        })
+                        .collect(),
+                    indices: mesh.indices.clone(),
+                };
+                if !has_normals {
+                    ops::smooth_normals(&mut easy_mesh);
+                }
+                ObjModel {
+                    name: model.name.clone(),
+                    mesh: easy_mesh.into(),
+                    material: mesh.material_id,
+                }
+            })
+            .collect();
+        Ok(Self { models, materials })
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const TWO_OBJECTS: &str = "o first\n\
+        v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+        f 1 2 3\n\
+        o second\n\
+        v 0 0 1\nv 1 0 1\nv 0 1 1\n\
+        vt 0 0\nvt 1 0\nvt 0 1\n\
+        f 4/1 5/2 6/3\n";
+    fn floats(mesh: &Mesh) -> Vec<f32> {
+        mesh.vertices
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect()
+    }
+    #[test]
+    fn loads_all_objects() {
+        let scene =
+            ObjScene::from_obj_buf(Cursor::new(TWO_OBJECTS), |_| Err(anyhow!("no files"))).unwrap();
+        let names: Vec<&str> = scene.models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["first", "second"]);
+        for model in scene.models.iter() {
+            assert_eq!(model.mesh.vertices.len(), 3 * 8 * 4);
+            let data = floats(&model.mesh);
+            for vertex in data.chunks_exact(8) {
+                assert_eq!(&vertex[5..8], &[0.0, 0.0, 1.0]);
+            }
+        }
+        let second = floats(&scene.models[1].mesh);
+        assert_eq!(&second[8 + 3..8 + 5], &[1.0, 0.0]);
+    }
+    #[test]
+    fn missing_material_is_error() {
+        let obj = format!("mtllib missing.mtl\n{}", TWO_OBJECTS);
+        assert!(ObjScene::from_obj_buf(Cursor::new(obj), |_| Err(anyhow!("no files"))).is_err());
+    }
+}
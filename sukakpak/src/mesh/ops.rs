@@ -0,0 +1,463 @@
+//! Processing operations for meshes. Operations that need to know what the vertex
+//! attributes mean work on `EasyMesh`, layout independent operations work on `Mesh`.
+pub use super::simplify::{lod_chain, simplify};
+use super::{EasyMesh, Mesh, Topology, Vertex};
+use super::{VertexComponent, VertexLayout};
+use anyhow::{bail, Result};
+use nalgebra::{Matrix3, Matrix4, Point3, Vector3, Vector4};
+use std::collections::HashMap;
+
+fn triangle_normal(mesh: &EasyMesh, tri: &[u32]) -> Vector3<f32> {
+    let p0 = mesh.vertices[tri[0] as usize].position;
+    let p1 = mesh.vertices[tri[1] as usize].position;
+    let p2 = mesh.vertices[tri[2] as usize].position;
+    (p1 - p0).cross(&(p2 - p0))
+}
+/// Recomputes normals as the area weighted average of the faces sharing each vertex.
+/// Faces are assumed to be wound counter clockwise.
+pub fn smooth_normals(mesh: &mut EasyMesh) {
+    let mut normals = vec![Vector3::<f32>::zeros(); mesh.vertices.len()];
+    for tri in mesh.indices.chunks_exact(3) {
+        let normal = triangle_normal(mesh, tri);
+        for i in tri {
+            normals[*i as usize] += normal;
+        }
+    }
+    for (vertex, normal) in mesh.vertices.iter_mut().zip(normals.iter()) {
+        vertex.normal = normal
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+    }
+}
+/// Gives every triangle its own vertices with the face normal. Increases vertex count
+/// to the number of indices.
+pub fn flat_normals(mesh: &mut EasyMesh) {
+    let mut vertices = Vec::with_capacity(mesh.indices.len());
+    for tri in mesh.indices.chunks_exact(3) {
+        let normal = triangle_normal(mesh, tri)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+        for i in tri {
+            vertices.push(Vertex {
+                normal,
+                ..mesh.vertices[*i as usize]
+            });
+        }
+    }
+    mesh.indices = (0..vertices.len() as u32).collect();
+    mesh.vertices = vertices;
+}
+/// Computes per vertex tangents from uv derivatives, following the conventions of
+/// MikkTSpace: tangents are orthogonalized against the normal and `w` stores the
+/// handedness so that `bitangent = cross(normal, tangent.xyz) * tangent.w`.
+pub fn compute_tangents(mesh: &EasyMesh) -> Vec<Vector4<f32>> {
+    let mut tangents = vec![Vector3::<f32>::zeros(); mesh.vertices.len()];
+    let mut bitangents = vec![Vector3::<f32>::zeros(); mesh.vertices.len()];
+    for tri in mesh.indices.chunks_exact(3) {
+        let v0 = &mesh.vertices[tri[0] as usize];
+        let v1 = &mesh.vertices[tri[1] as usize];
+        let v2 = &mesh.vertices[tri[2] as usize];
+        let edge1 = v1.position - v0.position;
+        let edge2 = v2.position - v0.position;
+        let duv1 = v1.uv - v0.uv;
+        let duv2 = v2.uv - v0.uv;
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() <= f32::EPSILON {
+            continue;
+        }
+        let r = 1.0 / det;
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) * r;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) * r;
+        for i in tri {
+            tangents[*i as usize] += tangent;
+            bitangents[*i as usize] += bitangent;
+        }
+    }
+    mesh.vertices
+        .iter()
+        .zip(tangents.iter().zip(bitangents.iter()))
+        .map(|(vertex, (tangent, bitangent))| {
+            let n = vertex.normal;
+            let t = (tangent - n * n.dot(tangent))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(|| {
+                    // degenerate uvs, pick any vector perpendicular to the normal
+                    let axis = if n.x.abs() < 0.9 {
+                        Vector3::x()
+                    } else {
+                        Vector3::y()
+                    };
+                    n.cross(&axis).normalize()
+                });
+            let w = if n.cross(&t).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            Vector4::new(t.x, t.y, t.z, w)
+        })
+        .collect()
+}
+/// Builds mesh with layout position, uv, normal, tangent
+pub fn with_tangents(mesh: &EasyMesh) -> Mesh {
+    let tangents = compute_tangents(mesh);
+    let vertices = mesh
+        .vertices
+        .iter()
+        .zip(tangents.iter())
+        .flat_map(|(v, t)| {
+            [
+                v.position.x,
+                v.position.y,
+                v.position.z,
+                v.uv.x,
+                v.uv.y,
+                v.normal.x,
+                v.normal.y,
+                v.normal.z,
+                t.x,
+                t.y,
+                t.z,
+                t.w,
+            ]
+        })
+        .flat_map(|f| f.to_ne_bytes())
+        .collect();
+    Mesh {
+        vertices,
+        indices: mesh.indices.clone(),
+        vertex_layout: VertexLayout {
+            components: vec![
+                VertexComponent::Vec3F32,
+                VertexComponent::Vec2F32,
+                VertexComponent::Vec3F32,
+                VertexComponent::Vec4F32,
+            ],
+        },
+        topology: Topology::TriangleList,
+    }
+}
+/// Merges vertices whose attributes are equal after snapping to a grid of size
+/// `epsilon`. An epsilon of zero only merges exactly equal vertices.
+pub fn weld(mesh: &mut EasyMesh, epsilon: f32) {
+    let quantize = |f: f32| -> i64 {
+        if epsilon > 0.0 {
+            (f / epsilon).round() as i64
+        } else {
+            // normalize -0.0 so it welds with 0.0
+            (f + 0.0).to_bits() as i64
+        }
+    };
+    let mut map: HashMap<[i64; 8], u32> = HashMap::new();
+    let mut vertices = vec![];
+    let remap: Vec<u32> = mesh
+        .vertices
+        .iter()
+        .map(|v| {
+            let key = [
+                v.position.x,
+                v.position.y,
+                v.position.z,
+                v.uv.x,
+                v.uv.y,
+                v.normal.x,
+                v.normal.y,
+                v.normal.z,
+            ]
+            .map(quantize);
+            *map.entry(key).or_insert_with(|| {
+                vertices.push(*v);
+                vertices.len() as u32 - 1
+            })
+        })
+        .collect();
+    mesh.indices = mesh.indices.iter().map(|i| remap[*i as usize]).collect();
+    mesh.vertices = vertices;
+}
+/// Merges vertices that are bitwise identical. Works with any vertex layout.
+pub fn weld_exact(mesh: &mut Mesh) {
+    let vertex_size = mesh.vertex_layout.vertex_size();
+    let mut map: HashMap<&[u8], u32> = HashMap::new();
+    let mut vertices = vec![];
+    let remap: Vec<u32> = mesh
+        .vertices
+        .chunks_exact(vertex_size)
+        .map(|v| {
+            *map.entry(v).or_insert_with(|| {
+                vertices.extend_from_slice(v);
+                (vertices.len() / vertex_size) as u32 - 1
+            })
+        })
+        .collect();
+    mesh.indices = mesh.indices.iter().map(|i| remap[*i as usize]).collect();
+    mesh.vertices = vertices;
+}
+/// Applies transform to positions and normals. Winding is flipped for mirroring
+/// transforms so faces stay front facing.
+pub fn transform(mesh: &mut EasyMesh, transform: &Matrix4<f32>) {
+    let linear: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into();
+    let normal_matrix = linear
+        .try_inverse()
+        .map(|m| m.transpose())
+        .unwrap_or(linear);
+    for vertex in mesh.vertices.iter_mut() {
+        vertex.position = transform
+            .transform_point(&Point3::from(vertex.position))
+            .coords;
+        vertex.normal = (normal_matrix * vertex.normal)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(vertex.normal);
+    }
+    if linear.determinant() < 0.0 {
+        for tri in mesh.indices.chunks_exact_mut(3) {
+            tri.swap(1, 2);
+        }
+    }
+}
+/// Concatenates meshes into one. All meshes must have the same vertex layout and
+/// topology, strips can not be merged.
+pub fn merge(meshes: &[Mesh]) -> Result<Mesh> {
+    let first = match meshes.first() {
+        Some(m) => m,
+        None => bail!("no meshes to merge"),
+    };
+    if first.topology.is_strip() {
+        bail!("{:?} meshes can not be merged", first.topology);
+    }
+    let vertex_size = first.vertex_layout.vertex_size();
+    let mut out = Mesh {
+        vertices: vec![],
+        indices: vec![],
+        vertex_layout: first.vertex_layout.clone(),
+        topology: first.topology,
+    };
+    for mesh in meshes.iter() {
+        if mesh.vertex_layout != first.vertex_layout {
+            bail!(
+                "vertex layout {:?} does not match {:?}",
+                mesh.vertex_layout,
+                first.vertex_layout
+            );
+        }
+        if mesh.topology != first.topology {
+            bail!(
+                "topology {:?} does not match {:?}",
+                mesh.topology,
+                first.topology
+            );
+        }
+        let offset = (out.vertices.len() / vertex_size) as u32;
+        out.indices.extend(mesh.indices.iter().map(|i| i + offset));
+        out.vertices.extend_from_slice(&mesh.vertices);
+    }
+    Ok(out)
+}
+const CACHE_SIZE: usize = 32;
+/// Vertex score from Tom Forsyth's linear speed vertex cache optimization
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(p) if p < 3 => 0.75,
+        Some(p) => (1.0 - (p - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    cache_score + 2.0 * (remaining as f32).powf(-0.5)
+}
+/// Reorders triangles for post transform cache locality, then reorders vertices
+/// in order of first use. Unreferenced vertices are removed. Meshes that are not
+/// triangle lists are left unchanged.
+pub fn optimize_vertex_cache(mesh: &mut Mesh) {
+    if mesh.topology != Topology::TriangleList {
+        return;
+    }
+    let num_vertices = mesh.vertices.len() / mesh.vertex_layout.vertex_size();
+    let num_triangles = mesh.indices.len() / 3;
+    let mut vertex_triangles: Vec<Vec<usize>> = vec![vec![]; num_vertices];
+    for (t, tri) in mesh.indices.chunks_exact(3).enumerate() {
+        for i in tri {
+            vertex_triangles[*i as usize].push(t);
+        }
+    }
+    let mut remaining: Vec<u32> = vertex_triangles.iter().map(|t| t.len() as u32).collect();
+    let mut vertex_scores: Vec<f32> = remaining.iter().map(|r| vertex_score(None, *r)).collect();
+    let triangle_score = |t: usize, scores: &[f32], indices: &[u32]| -> f32 {
+        indices[t * 3..t * 3 + 3]
+            .iter()
+            .map(|i| scores[*i as usize])
+            .sum()
+    };
+    let mut triangle_scores: Vec<f32> = (0..num_triangles)
+        .map(|t| triangle_score(t, &vertex_scores, &mesh.indices))
+        .collect();
+    let mut emitted = vec![false; num_triangles];
+    let mut cache: Vec<u32> = vec![];
+    let mut out_indices = Vec::with_capacity(mesh.indices.len());
+    let mut scan_start = 0;
+    let mut best: Option<usize> = None;
+    for _ in 0..num_triangles {
+        let triangle = match best {
+            Some(t) => t,
+            None => {
+                while emitted[scan_start] {
+                    scan_start += 1;
+                }
+                (scan_start..num_triangles)
+                    .filter(|t| !emitted[*t])
+                    .max_by(|a, b| triangle_scores[*a].total_cmp(&triangle_scores[*b]))
+                    .unwrap()
+            }
+        };
+        emitted[triangle] = true;
+        let tri = [
+            mesh.indices[triangle * 3],
+            mesh.indices[triangle * 3 + 1],
+            mesh.indices[triangle * 3 + 2],
+        ];
+        out_indices.extend_from_slice(&tri);
+        for i in tri.iter() {
+            remaining[*i as usize] -= 1;
+        }
+        let old_cache = std::mem::take(&mut cache);
+        cache.extend_from_slice(&tri);
+        cache.extend(old_cache.iter().filter(|v| !tri.contains(v)));
+        let touched: Vec<u32> = cache.clone();
+        cache.truncate(CACHE_SIZE);
+        for (position, vertex) in touched.iter().enumerate() {
+            let position = if position < CACHE_SIZE {
+                Some(position)
+            } else {
+                None
+            };
+            vertex_scores[*vertex as usize] = vertex_score(position, remaining[*vertex as usize]);
+        }
+        best = None;
+        let mut best_score = f32::MIN;
+        for vertex in touched.iter() {
+            for t in vertex_triangles[*vertex as usize].iter() {
+                if emitted[*t] {
+                    continue;
+                }
+                triangle_scores[*t] = triangle_score(*t, &vertex_scores, &mesh.indices);
+                if triangle_scores[*t] > best_score {
+                    best_score = triangle_scores[*t];
+                    best = Some(*t);
+                }
+            }
+        }
+    }
+    // reorder vertices in order of first use
+    let vertex_size = mesh.vertex_layout.vertex_size();
+    let mut remap = vec![u32::MAX; num_vertices];
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+    for index in out_indices.iter_mut() {
+        let old = *index as usize;
+        if remap[old] == u32::MAX {
+            remap[old] = (vertices.len() / vertex_size) as u32;
+            vertices.extend_from_slice(&mesh.vertices[old * vertex_size..(old + 1) * vertex_size]);
+        }
+        *index = remap[old];
+    }
+    mesh.indices = out_indices;
+    mesh.vertices = vertices;
+}
+/// Average post transform cache miss ratio of a FIFO cache of `cache_size`
+pub fn average_cache_miss_ratio(indices: &[u32], cache_size: usize) -> f32 {
+    let mut cache: std::collections::VecDeque<u32> = Default::default();
+    let mut misses = 0;
+    for index in indices.iter() {
+        if !cache.contains(index) {
+            misses += 1;
+            cache.push_back(*index);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / (indices.len() / 3).max(1) as f32
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    fn grid(size: u32) -> EasyMesh {
+        let positions: Vec<Vector3<f32>> = (0..=size)
+            .flat_map(|x| (0..=size).map(move |y| Vector3::new(x as f32, 0.0, y as f32)))
+            .collect();
+        let mut indices = vec![];
+        for x in 0..size {
+            for y in 0..size {
+                let i = x * (size + 1) + y;
+                indices.extend_from_slice(&[i, i + 1, i + size + 1]);
+                indices.extend_from_slice(&[i + 1, i + size + 2, i + size + 1]);
+            }
+        }
+        EasyMesh::from_positions(&positions, indices)
+    }
+    #[test]
+    fn smooth_normals_point_up() {
+        for v in grid(4).vertices.iter() {
+            assert!((v.normal - Vector3::y()).norm() < 1e-6);
+        }
+    }
+    #[test]
+    fn flat_then_weld_restores_vertex_count() {
+        let mut mesh = grid(3);
+        flat_normals(&mut mesh);
+        assert_eq!(mesh.vertices.len(), 3 * 3 * 6);
+        weld(&mut mesh, 0.0);
+        assert_eq!(mesh.vertices.len(), 4 * 4);
+    }
+    #[test]
+    fn tangents_follow_u() {
+        let mesh: EasyMesh = Mesh::new_plane().try_into().unwrap();
+        for t in compute_tangents(&mesh) {
+            assert!((t - Vector4::new(1.0, 0.0, 0.0, 1.0)).norm() < 1e-6);
+        }
+    }
+    #[test]
+    fn mirror_transform_flips_winding() {
+        let mut mesh = grid(1);
+        transform(
+            &mut mesh,
+            &Matrix4::new_nonuniform_scaling(&Vector3::new(-1.0, 1.0, 1.0)),
+        );
+        smooth_normals(&mut mesh);
+        for v in mesh.vertices.iter() {
+            assert!((v.normal - Vector3::y()).norm() < 1e-6);
+        }
+    }
+    #[test]
+    fn merge_offsets_indices() {
+        let merged = merge(&[Mesh::new_triangle(), Mesh::new_triangle()]).unwrap();
+        assert_eq!(merged.indices, vec![0, 1, 2, 3, 4, 5]);
+        assert!(merge(&[Mesh::new_triangle(), with_tangents(&grid(1))]).is_err());
+    }
+    #[test]
+    fn cache_optimization_keeps_triangles() {
+        let mut mesh: Mesh = grid(16).into();
+        let before = average_cache_miss_ratio(&mesh.indices, 16);
+        let triangles = |mesh: &Mesh| {
+            let easy: EasyMesh = mesh.clone().try_into().unwrap();
+            let mut tris: Vec<[i32; 9]> = easy
+                .indices
+                .chunks_exact(3)
+                .map(|t| {
+                    let p: Vec<i32> = t
+                        .iter()
+                        .flat_map(|i| easy.vertices[*i as usize].position.iter().copied())
+                        .map(|f| f as i32)
+                        .collect();
+                    [p[0], p[1], p[2], p[3], p[4], p[5], p[6], p[7], p[8]]
+                })
+                .collect();
+            tris.sort();
+            tris
+        };
+        let expected = triangles(&mesh);
+        optimize_vertex_cache(&mut mesh);
+        assert_eq!(triangles(&mesh), expected);
+        assert!(average_cache_miss_ratio(&mesh.indices, 16) <= before);
+    }
+}
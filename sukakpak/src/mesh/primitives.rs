@@ -0,0 +1,430 @@
+//! Procedural primitives. All primitives are y up, wound counter clockwise when
+//! viewed from outside and have unit length normals.
+use super::{ops, EasyMesh, Mesh, Vertex};
+use nalgebra::{Matrix4, Vector2, Vector3};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Row of a surface of revolution around the y axis
+struct ProfileRow {
+    radius: f32,
+    y: f32,
+    /// normal in the (radial, y) plane
+    normal: Vector2<f32>,
+    v: f32,
+}
+/// Revolves profile around the y axis. Rows must be ordered top to bottom along
+/// the outside of the surface.
+fn lathe(profile: &[ProfileRow], segments: u32) -> EasyMesh {
+    let segments = segments.max(3);
+    let mut vertices = vec![];
+    for row in profile.iter() {
+        for j in 0..=segments {
+            let u = j as f32 / segments as f32;
+            let (sin, cos) = (u * 2.0 * PI).sin_cos();
+            vertices.push(Vertex {
+                position: Vector3::new(row.radius * cos, row.y, row.radius * sin),
+                uv: Vector2::new(u, row.v),
+                normal: Vector3::new(row.normal.x * cos, row.normal.y, row.normal.x * sin)
+                    .normalize(),
+            });
+        }
+    }
+    let stride = segments + 1;
+    let mut indices = vec![];
+    for i in 0..profile.len().saturating_sub(1) as u32 {
+        for j in 0..segments {
+            let a = i * stride + j;
+            let b = a + 1;
+            let c = a + stride;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, b, c, b, d, c]);
+        }
+    }
+    EasyMesh { vertices, indices }
+}
+/// Flat disc at height `y` facing up or down
+fn disc(radius: f32, y: f32, segments: u32, up: bool) -> EasyMesh {
+    let segments = segments.max(3);
+    let normal = if up { Vector3::y() } else { -Vector3::y() };
+    let mut vertices = vec![Vertex {
+        position: Vector3::new(0.0, y, 0.0),
+        uv: Vector2::new(0.5, 0.5),
+        normal,
+    }];
+    for j in 0..=segments {
+        let (sin, cos) = (j as f32 / segments as f32 * 2.0 * PI).sin_cos();
+        vertices.push(Vertex {
+            position: Vector3::new(radius * cos, y, radius * sin),
+            uv: Vector2::new(0.5 + 0.5 * cos, 0.5 + 0.5 * sin),
+            normal,
+        });
+    }
+    let indices = (1..=segments)
+        .flat_map(|j| if up { [0, j + 1, j] } else { [0, j, j + 1] })
+        .collect();
+    EasyMesh { vertices, indices }
+}
+fn merge(meshes: Vec<EasyMesh>) -> EasyMesh {
+    let mut out = EasyMesh {
+        vertices: vec![],
+        indices: vec![],
+    };
+    for mesh in meshes {
+        let offset = out.vertices.len() as u32;
+        out.indices.extend(mesh.indices.iter().map(|i| i + offset));
+        out.vertices.extend(mesh.vertices);
+    }
+    out
+}
+/// Sphere rows from `start` to `end` polar angle, offset vertically by `y_offset`
+fn sphere_rows(
+    radius: f32,
+    rings: u32,
+    start: f32,
+    end: f32,
+    y_offset: f32,
+    v: impl Fn(f32) -> f32,
+) -> Vec<ProfileRow> {
+    (0..=rings)
+        .map(|i| {
+            let theta = start + (end - start) * i as f32 / rings as f32;
+            let (sin, cos) = theta.sin_cos();
+            let y = radius * cos + y_offset;
+            ProfileRow {
+                radius: radius * sin,
+                y,
+                normal: Vector2::new(sin, cos),
+                v: v(y),
+            }
+        })
+        .collect()
+}
+impl EasyMesh {
+    /// Sphere centered at origin made of `sectors` slices around y and `stacks` rings
+    pub fn new_uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        let stacks = stacks.max(2);
+        lathe(
+            &sphere_rows(radius, stacks, 0.0, PI, 0.0, |y| 0.5 - y / (2.0 * radius)),
+            sectors,
+        )
+    }
+    /// Sphere centered at origin made by subdividing an icosahedron. uvs use
+    /// spherical coordinates, vertices on the seam are duplicated so u may go past 1.
+    pub fn new_icosphere(radius: f32, subdivisions: u32) -> Self {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vector3<f32>> = [
+            [-1.0, t, 0.0],
+            [1.0, t, 0.0],
+            [-1.0, -t, 0.0],
+            [1.0, -t, 0.0],
+            [0.0, -1.0, t],
+            [0.0, 1.0, t],
+            [0.0, -1.0, -t],
+            [0.0, 1.0, -t],
+            [t, 0.0, -1.0],
+            [t, 0.0, 1.0],
+            [-t, 0.0, -1.0],
+            [-t, 0.0, 1.0],
+        ]
+        .iter()
+        .map(|p| Vector3::new(p[0], p[1], p[2]).normalize())
+        .collect();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32, positions: &mut Vec<Vector3<f32>>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    positions
+                        .push(((positions[a as usize] + positions[b as usize]) / 2.0).normalize());
+                    positions.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|[a, b, c]| {
+                    let ab = midpoint(*a, *b, &mut positions);
+                    let bc = midpoint(*b, *c, &mut positions);
+                    let ca = midpoint(*c, *a, &mut positions);
+                    [[*a, ab, ca], [*b, bc, ab], [*c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+        let mut vertices: Vec<Vertex> = positions
+            .iter()
+            .map(|p| Vertex {
+                position: p * radius,
+                uv: Vector2::new(
+                    0.5 - p.z.atan2(p.x) / (2.0 * PI),
+                    p.y.clamp(-1.0, 1.0).acos() / PI,
+                ),
+                normal: *p,
+            })
+            .collect();
+        // triangles crossing the seam wrap around u, give them copies shifted by one
+        let mut seam_copies: HashMap<u32, u32> = HashMap::new();
+        for tri in triangles.iter_mut() {
+            let max_u = tri
+                .iter()
+                .map(|i| vertices[*i as usize].uv.x)
+                .fold(f32::MIN, f32::max);
+            for index in tri.iter_mut() {
+                if max_u - vertices[*index as usize].uv.x > 0.5 {
+                    *index = *seam_copies.entry(*index).or_insert_with(|| {
+                        let mut copy = vertices[*index as usize];
+                        copy.uv.x += 1.0;
+                        vertices.push(copy);
+                        vertices.len() as u32 - 1
+                    });
+                }
+            }
+        }
+        Self {
+            vertices,
+            indices: triangles.iter().flatten().copied().collect(),
+        }
+    }
+    /// Closed cylinder with its base centered at the origin extending up to `height`
+    pub fn new_cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let side = lathe(
+            &[
+                ProfileRow {
+                    radius,
+                    y: height,
+                    normal: Vector2::new(1.0, 0.0),
+                    v: 0.0,
+                },
+                ProfileRow {
+                    radius,
+                    y: 0.0,
+                    normal: Vector2::new(1.0, 0.0),
+                    v: 1.0,
+                },
+            ],
+            segments,
+        );
+        merge(vec![
+            side,
+            disc(radius, height, segments, true),
+            disc(radius, 0.0, segments, false),
+        ])
+    }
+    /// Closed cone with its base centered at the origin and apex at `height`
+    pub fn new_cone(radius: f32, height: f32, segments: u32) -> Self {
+        let normal = Vector2::new(height, radius).normalize();
+        let side = lathe(
+            &[
+                ProfileRow {
+                    radius: 0.0,
+                    y: height,
+                    normal,
+                    v: 0.0,
+                },
+                ProfileRow {
+                    radius,
+                    y: 0.0,
+                    normal,
+                    v: 1.0,
+                },
+            ],
+            segments,
+        );
+        merge(vec![side, disc(radius, 0.0, segments, false)])
+    }
+    /// Capsule centered at the origin. `height` is the length of the cylindrical
+    /// section, total height is `height + 2 * radius`.
+    pub fn new_capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Self {
+        let rings = rings.max(1);
+        let half = height / 2.0;
+        let total = height + 2.0 * radius;
+        let v = |y: f32| (half + radius - y) / total;
+        let mut profile = sphere_rows(radius, rings, 0.0, PI / 2.0, half, v);
+        profile.append(&mut sphere_rows(radius, rings, PI / 2.0, PI, -half, v));
+        lathe(&profile, segments)
+    }
+    /// Torus in the xz plane centered at the origin
+    pub fn new_torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Self {
+        let minor_segments = minor_segments.max(3);
+        // the profile circle is walked clockwise so rows go along the outside
+        let profile: Vec<ProfileRow> = (0..=minor_segments)
+            .map(|i| {
+                let v = i as f32 / minor_segments as f32;
+                let (sin, cos) = (2.0 * PI * (1.0 - v)).sin_cos();
+                ProfileRow {
+                    radius: major_radius + minor_radius * cos,
+                    y: minor_radius * sin,
+                    normal: Vector2::new(cos, sin),
+                    v,
+                }
+            })
+            .collect();
+        lathe(&profile, major_segments)
+    }
+    /// Grid in the xz plane facing up spanning from the origin to `size`,
+    /// with `divisions` quads along each axis
+    pub fn new_grid(size: Vector2<f32>, divisions: Vector2<u32>) -> Self {
+        let divisions = Vector2::new(divisions.x.max(1), divisions.y.max(1));
+        let mut vertices = vec![];
+        for x in 0..=divisions.x {
+            for z in 0..=divisions.y {
+                let uv = Vector2::new(x as f32 / divisions.x as f32, z as f32 / divisions.y as f32);
+                vertices.push(Vertex {
+                    position: Vector3::new(uv.x * size.x, 0.0, uv.y * size.y),
+                    uv,
+                    normal: Vector3::y(),
+                });
+            }
+        }
+        let stride = divisions.y + 1;
+        let mut indices = vec![];
+        for x in 0..divisions.x {
+            for z in 0..divisions.y {
+                let a = x * stride + z;
+                let b = a + 1;
+                let c = a + stride;
+                let d = c + 1;
+                indices.extend_from_slice(&[a, b, c, b, d, c]);
+            }
+        }
+        Self { vertices, indices }
+    }
+    /// Arrow pointing along +y starting at the origin with total length `length`
+    pub fn new_arrow(
+        length: f32,
+        shaft_radius: f32,
+        head_radius: f32,
+        head_length: f32,
+        segments: u32,
+    ) -> Self {
+        let head_length = head_length.min(length);
+        let shaft_length = length - head_length;
+        let mut head = Self::new_cone(head_radius, head_length, segments);
+        ops::transform(
+            &mut head,
+            &Matrix4::new_translation(&Vector3::new(0.0, shaft_length, 0.0)),
+        );
+        merge(vec![
+            Self::new_cylinder(shaft_radius, shaft_length, segments),
+            head,
+        ])
+    }
+    /// Three arrows of `length` along +x, +y and +z
+    pub fn new_gizmo(length: f32) -> Self {
+        let arrow = Self::new_arrow(length, length * 0.02, length * 0.06, length * 0.2, 12);
+        let mut x = arrow.clone();
+        ops::transform(&mut x, &Matrix4::from_euler_angles(0.0, 0.0, -PI / 2.0));
+        let mut z = arrow.clone();
+        ops::transform(&mut z, &Matrix4::from_euler_angles(PI / 2.0, 0.0, 0.0));
+        merge(vec![x, arrow, z])
+    }
+}
+impl Mesh {
+    pub fn new_uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        EasyMesh::new_uv_sphere(radius, sectors, stacks).into()
+    }
+    pub fn new_icosphere(radius: f32, subdivisions: u32) -> Self {
+        EasyMesh::new_icosphere(radius, subdivisions).into()
+    }
+    pub fn new_cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        EasyMesh::new_cylinder(radius, height, segments).into()
+    }
+    pub fn new_cone(radius: f32, height: f32, segments: u32) -> Self {
+        EasyMesh::new_cone(radius, height, segments).into()
+    }
+    pub fn new_capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Self {
+        EasyMesh::new_capsule(radius, height, segments, rings).into()
+    }
+    pub fn new_torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Self {
+        EasyMesh::new_torus(major_radius, minor_radius, major_segments, minor_segments).into()
+    }
+    pub fn new_grid(size: Vector2<f32>, divisions: Vector2<u32>) -> Self {
+        EasyMesh::new_grid(size, divisions).into()
+    }
+    pub fn new_arrow(
+        length: f32,
+        shaft_radius: f32,
+        head_radius: f32,
+        head_length: f32,
+        segments: u32,
+    ) -> Self {
+        EasyMesh::new_arrow(length, shaft_radius, head_radius, head_length, segments).into()
+    }
+    pub fn new_gizmo(length: f32) -> Self {
+        EasyMesh::new_gizmo(length).into()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn check(mesh: &EasyMesh, uv_max: f32) {
+        assert_eq!(mesh.indices.len() % 3, 0);
+        for v in mesh.vertices.iter() {
+            assert!((v.normal.norm() - 1.0).abs() < 1e-4, "{:?}", v);
+            assert!(v.uv.x >= 0.0 && v.uv.x <= uv_max && v.uv.y >= 0.0 && v.uv.y <= 1.0);
+        }
+        for tri in mesh.indices.chunks_exact(3) {
+            let p: Vec<&Vertex> = tri.iter().map(|i| &mesh.vertices[*i as usize]).collect();
+            let face = (p[1].position - p[0].position).cross(&(p[2].position - p[0].position));
+            if face.norm() < 1e-6 {
+                continue;
+            }
+            for vertex in p.iter() {
+                assert!(face.dot(&vertex.normal) > 0.0, "{:?} {:?}", face, vertex);
+            }
+        }
+    }
+    #[test]
+    fn primitives_face_outward() {
+        check(&EasyMesh::new_uv_sphere(1.0, 16, 8), 1.0);
+        check(&EasyMesh::new_icosphere(2.0, 2), 2.0);
+        check(&EasyMesh::new_cylinder(1.0, 2.0, 8), 1.0);
+        check(&EasyMesh::new_cone(1.0, 2.0, 8), 1.0);
+        check(&EasyMesh::new_capsule(0.5, 1.0, 8, 4), 1.0);
+        check(&EasyMesh::new_torus(1.0, 0.25, 16, 8), 1.0);
+        check(
+            &EasyMesh::new_grid(Vector2::new(2.0, 3.0), Vector2::new(4, 5)),
+            1.0,
+        );
+        check(&EasyMesh::new_arrow(1.0, 0.05, 0.1, 0.3, 8), 1.0);
+        check(&EasyMesh::new_gizmo(1.0), 1.0);
+    }
+    #[test]
+    fn icosphere_is_on_sphere() {
+        let sphere = EasyMesh::new_icosphere(2.0, 3);
+        assert_eq!(sphere.indices.len(), 20 * 4usize.pow(3) * 3);
+        for v in sphere.vertices.iter() {
+            assert!((v.position.norm() - 2.0).abs() < 1e-4);
+        }
+    }
+}
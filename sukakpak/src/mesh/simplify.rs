@@ -0,0 +1,360 @@
+//! Quadric error metric simplification (Garland and Heckbert). Edges are collapsed
+//! onto one of their endpoints so vertex attributes are kept exactly and any
+//! `VertexLayout` is supported. Vertices on borders, which includes uv and normal
+//! seams, are never moved so meshes keep their outline.
+use super::{Mesh, Topology};
+use anyhow::{anyhow, bail, Result};
+use nalgebra::Vector3;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+/// Symmetric 4x4 matrix measuring squared distance to a set of planes
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric([f64; 10]);
+impl Quadric {
+    fn from_plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+        )
+    }
+    fn add(&self, other: &Self) -> Self {
+        let mut out = *self;
+        for (o, v) in out.0.iter_mut().zip(other.0.iter()) {
+            *o += v;
+        }
+        out
+    }
+    fn error(&self, p: &Vector3<f64>) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+/// Candidate collapse of `from` onto `to`, stale if either vertex changed since
+/// it was pushed
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    from_version: u32,
+    to_version: u32,
+}
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Collapse {}
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Collapse {
+    /// reversed so `BinaryHeap` pops the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+struct Simplifier {
+    positions: Vec<Vector3<f64>>,
+    quadrics: Vec<Quadric>,
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    /// triangles using each vertex, may contain removed triangles
+    adjacency: Vec<Vec<usize>>,
+    locked: Vec<bool>,
+    collapsed: Vec<bool>,
+    version: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+    live: usize,
+}
+impl Simplifier {
+    fn new(positions: Vec<Vector3<f64>>, indices: &[u32]) -> Self {
+        let num_vertices = positions.len();
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let mut quadrics = vec![Quadric::default(); num_vertices];
+        let mut adjacency = vec![vec![]; num_vertices];
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+        for (index, tri) in triangles.iter().enumerate() {
+            let [p0, p1, p2] = tri.map(|i| positions[i as usize]);
+            let cross = (p1 - p0).cross(&(p2 - p0));
+            let area = cross.norm() / 2.0;
+            if let Some(normal) = cross.try_normalize(f64::EPSILON) {
+                let quadric = Quadric::from_plane(normal, -normal.dot(&p0), area);
+                for i in tri.iter() {
+                    quadrics[*i as usize] = quadrics[*i as usize].add(&quadric);
+                }
+            }
+            for j in 0..3 {
+                adjacency[tri[j] as usize].push(index);
+                let (a, b) = (tri[j], tri[(j + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        let mut locked = vec![false; num_vertices];
+        for ((a, b), count) in edges.iter() {
+            if *count != 2 {
+                locked[*a as usize] = true;
+                locked[*b as usize] = true;
+            }
+        }
+        let live = triangles.len();
+        let mut simplifier = Self {
+            positions,
+            quadrics,
+            removed: vec![false; triangles.len()],
+            triangles,
+            adjacency,
+            locked,
+            collapsed: vec![false; num_vertices],
+            version: vec![0; num_vertices],
+            heap: BinaryHeap::new(),
+            live,
+        };
+        for vertex in 0..num_vertices as u32 {
+            simplifier.push_collapses(vertex);
+        }
+        simplifier
+    }
+    fn neighbors(&self, vertex: u32) -> HashSet<u32> {
+        self.adjacency[vertex as usize]
+            .iter()
+            .filter(|t| !self.removed[**t])
+            .flat_map(|t| self.triangles[*t].iter().copied())
+            .filter(|v| *v != vertex)
+            .collect()
+    }
+    fn push(&mut self, from: u32, to: u32) {
+        if self.locked[from as usize] {
+            return;
+        }
+        let quadric = self.quadrics[from as usize].add(&self.quadrics[to as usize]);
+        self.heap.push(Collapse {
+            cost: quadric.error(&self.positions[to as usize]),
+            from,
+            to,
+            from_version: self.version[from as usize],
+            to_version: self.version[to as usize],
+        });
+    }
+    /// Pushes collapses along every edge touching `vertex` in both directions
+    fn push_collapses(&mut self, vertex: u32) {
+        for neighbor in self.neighbors(vertex) {
+            self.push(vertex, neighbor);
+            self.push(neighbor, vertex);
+        }
+    }
+    /// Checks that collapsing does not flip triangles or make the mesh non manifold
+    fn is_valid(&self, from: u32, to: u32) -> bool {
+        let shared_triangles = self.adjacency[from as usize]
+            .iter()
+            .filter(|t| !self.removed[**t] && self.triangles[**t].contains(&to))
+            .count();
+        let to_neighbors = self.neighbors(to);
+        let shared_neighbors = self
+            .neighbors(from)
+            .iter()
+            .filter(|v| to_neighbors.contains(v))
+            .count();
+        if shared_neighbors != shared_triangles {
+            return false;
+        }
+        let normal = |tri: [u32; 3]| {
+            let [p0, p1, p2] = tri.map(|i| self.positions[i as usize]);
+            (p1 - p0).cross(&(p2 - p0))
+        };
+        self.adjacency[from as usize]
+            .iter()
+            .filter(|t| !self.removed[**t] && !self.triangles[**t].contains(&to))
+            .all(|t| {
+                let before = self.triangles[*t];
+                let after = before.map(|i| if i == from { to } else { i });
+                let new_normal = normal(after);
+                new_normal.norm_squared() > f64::EPSILON && normal(before).dot(&new_normal) > 0.0
+            })
+    }
+    fn collapse(&mut self, from: u32, to: u32) {
+        for t in std::mem::take(&mut self.adjacency[from as usize]) {
+            if self.removed[t] {
+                continue;
+            }
+            if self.triangles[t].contains(&to) {
+                self.removed[t] = true;
+                self.live -= 1;
+            } else {
+                for i in self.triangles[t].iter_mut() {
+                    if *i == from {
+                        *i = to;
+                    }
+                }
+                self.adjacency[to as usize].push(t);
+            }
+        }
+        let removed = &self.removed;
+        self.adjacency[to as usize].retain(|t| !removed[*t]);
+        self.quadrics[to as usize] = self.quadrics[to as usize].add(&self.quadrics[from as usize]);
+        self.collapsed[from as usize] = true;
+        self.version[from as usize] += 1;
+        self.version[to as usize] += 1;
+        self.push_collapses(to);
+    }
+    fn run(&mut self, target: usize) {
+        while self.live > target {
+            let candidate = match self.heap.pop() {
+                Some(c) => c,
+                None => break,
+            };
+            let (from, to) = (candidate.from as usize, candidate.to as usize);
+            if self.collapsed[from]
+                || self.collapsed[to]
+                || self.version[from] != candidate.from_version
+                || self.version[to] != candidate.to_version
+            {
+                continue;
+            }
+            if self.is_valid(candidate.from, candidate.to) {
+                self.collapse(candidate.from, candidate.to);
+            }
+        }
+    }
+}
+/// Reduces triangle count to about `target_ratio` of the original. The ratio may
+/// not be reached if every remaining collapse would damage the mesh. Unused
+/// vertices are removed. Fails if the mesh does not have a position component or
+/// is not a triangle list.
+pub fn simplify(mesh: &Mesh, target_ratio: f32) -> Result<Mesh> {
+    if mesh.topology != Topology::TriangleList {
+        bail!(
+            "only triangle lists can be simplified, got {:?}",
+            mesh.topology
+        );
+    }
+    let positions = mesh
+        .positions()
+        .ok_or_else(|| anyhow!("mesh does not have a position component"))?;
+    let mut simplifier = Simplifier::new(
+        positions.iter().map(|p| p.cast::<f64>()).collect(),
+        &mesh.indices,
+    );
+    let num_triangles = mesh.indices.len() / 3;
+    let target = (num_triangles as f32 * target_ratio.clamp(0.0, 1.0)).ceil() as usize;
+    simplifier.run(target);
+
+    let vertex_size = mesh.vertex_layout.vertex_size();
+    let mut remap: Vec<Option<u32>> = vec![None; positions.len()];
+    let mut vertices = vec![];
+    let mut indices = Vec::with_capacity(simplifier.live * 3);
+    for (tri, removed) in simplifier.triangles.iter().zip(simplifier.removed.iter()) {
+        if *removed {
+            continue;
+        }
+        for i in tri.iter() {
+            let index = *remap[*i as usize].get_or_insert_with(|| {
+                let start = *i as usize * vertex_size;
+                vertices.extend_from_slice(&mesh.vertices[start..start + vertex_size]);
+                (vertices.len() / vertex_size - 1) as u32
+            });
+            indices.push(index);
+        }
+    }
+    Ok(Mesh {
+        vertices,
+        indices,
+        vertex_layout: mesh.vertex_layout.clone(),
+        topology: Topology::TriangleList,
+    })
+}
+/// Builds a level of detail for each ratio of the original triangle count, each
+/// level is simplified from the previous one. Ratios of 1 or more give a copy of
+/// `mesh`.
+pub fn lod_chain(mesh: &Mesh, ratios: &[f32]) -> Result<Vec<Mesh>> {
+    let num_triangles = (mesh.indices.len() / 3).max(1) as f32;
+    let mut levels: Vec<Mesh> = Vec::with_capacity(ratios.len());
+    for ratio in ratios.iter() {
+        let level = if *ratio >= 1.0 {
+            mesh.clone()
+        } else {
+            let previous = levels.last().unwrap_or(mesh);
+            let previous_triangles = (previous.indices.len() / 3).max(1) as f32;
+            simplify(previous, ratio * num_triangles / previous_triangles)?
+        };
+        levels.push(level);
+    }
+    Ok(levels)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EasyMesh;
+    use nalgebra::Vector2;
+    fn bumpy_grid() -> Mesh {
+        let mut mesh = EasyMesh::new_grid(Vector2::new(10.0, 10.0), Vector2::new(20, 20));
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.position.y = (vertex.position.x * 0.3).sin() + (vertex.position.z * 0.2).cos();
+        }
+        mesh.into()
+    }
+    #[test]
+    fn reduces_triangles() {
+        let mesh = bumpy_grid();
+        let simplified = simplify(&mesh, 0.25).unwrap();
+        let triangles = simplified.indices.len() / 3;
+        assert!(triangles <= mesh.indices.len() / 3 / 4 + 1);
+        assert!(simplified.vertices.len() < mesh.vertices.len());
+        // borders are locked so the outline is unchanged
+        assert_eq!(
+            simplified.aabb().unwrap().min.xz(),
+            mesh.aabb().unwrap().min.xz()
+        );
+        assert_eq!(
+            simplified.aabb().unwrap().max.xz(),
+            mesh.aabb().unwrap().max.xz()
+        );
+        assert!(simplified
+            .indices
+            .iter()
+            .all(|i| (*i as usize) < simplified.vertices.len() / 32));
+    }
+    #[test]
+    fn flat_grid_stays_flat() {
+        let mesh: Mesh = EasyMesh::new_grid(Vector2::new(4.0, 4.0), Vector2::new(16, 16)).into();
+        let levels = lod_chain(&mesh, &[1.0, 0.5, 0.1]).unwrap();
+        assert_eq!(levels[0], mesh);
+        assert!(levels[1].indices.len() < levels[0].indices.len());
+        assert!(levels[2].indices.len() < levels[1].indices.len());
+        for level in levels.iter() {
+            for tri in level.indices.chunks_exact(3) {
+                let p = level.positions().unwrap();
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| p[i as usize]);
+                assert!((b - a).cross(&(c - a)).y > 0.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Checks run by every backend's `build_mesh`. Cheap checks, which only look
+//! at buffer lengths, always run. Checks that scan every index and vertex or
+//! compare against the bound shader only run with the `state_validation`
+//! feature.
+use super::{Mesh, Topology};
+use crate::VertexComponent;
+use ass_types::{Scalar, ShaderType, VertexInput};
+use thiserror::Error;
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum MeshError {
+    #[error("vertex layout has no components")]
+    EmptyLayout,
+    #[error("vertex buffer of {length} bytes is not a multiple of vertex size {vertex_size}")]
+    VertexLengthMismatch { length: usize, vertex_size: usize },
+    #[error("index count {count} does not make whole primitives of {topology:?}")]
+    IndexCountMismatch { count: usize, topology: Topology },
+    #[error("index {index} at position {position} is out of range for {num_vertices} vertices")]
+    IndexOutOfRange {
+        position: usize,
+        index: u32,
+        num_vertices: usize,
+    },
+    #[error("vertex {vertex} has a non finite value in component {component}")]
+    NonFinite { vertex: usize, component: usize },
+    #[error("mesh has {mesh} vertex components but shader \"{shader}\" takes {shader_inputs}")]
+    ComponentCountMismatch {
+        shader: String,
+        mesh: usize,
+        shader_inputs: usize,
+    },
+    #[error(
+        "vertex component {location} is {component:?} but shader \"{shader}\" expects {expected:?}"
+    )]
+    LayoutMismatch {
+        shader: String,
+        location: u32,
+        component: VertexComponent,
+        expected: ShaderType,
+    },
+}
+/// Whether `component` can be read by a shader input of type `ty`. Only the
+/// scalar kind has to match: normalized and half float components are read as
+/// floats and 16 bit integers as 32 bit integers. Missing vector elements are
+/// filled in and extra ones are dropped.
+fn component_matches(component: &VertexComponent, ty: &ShaderType) -> bool {
+    let scalar = match ty {
+        ShaderType::Scalar(s) | ShaderType::Vec2(s) | ShaderType::Vec3(s) | ShaderType::Vec4(s) => {
+            s
+        }
+        ShaderType::Mat4x4(_) | ShaderType::Struct(_) => return false,
+    };
+    match scalar {
+        Scalar::F32 | Scalar::F16 => !component.is_integer(),
+        Scalar::U32 => matches!(
+            component,
+            VertexComponent::Vec2U16 | VertexComponent::Vec4U16 | VertexComponent::Vec1U32
+        ),
+        Scalar::I32 => matches!(component, VertexComponent::Vec1I32),
+    }
+}
+impl Mesh {
+    /// Checks that run on every `build_mesh`, only look at buffer lengths
+    pub fn validate_lengths(&self) -> Result<(), MeshError> {
+        let vertex_size = self.vertex_layout.vertex_size();
+        if vertex_size == 0 {
+            return Err(MeshError::EmptyLayout);
+        }
+        if !self.vertices.len().is_multiple_of(vertex_size) {
+            return Err(MeshError::VertexLengthMismatch {
+                length: self.vertices.len(),
+                vertex_size,
+            });
+        }
+        if !self.topology.is_valid_index_count(self.indices.len()) {
+            return Err(MeshError::IndexCountMismatch {
+                count: self.indices.len(),
+                topology: self.topology,
+            });
+        }
+        Ok(())
+    }
+    /// Runs `validate_lengths` then checks that every index is in range and
+    /// every float component is finite
+    pub fn validate(&self) -> Result<(), MeshError> {
+        self.validate_lengths()?;
+        let num_vertices = self.num_vertices();
+        if let Some((position, index)) = self
+            .indices
+            .iter()
+            .enumerate()
+            .find(|(_, index)| **index as usize >= num_vertices)
+        {
+            return Err(MeshError::IndexOutOfRange {
+                position,
+                index: *index,
+                num_vertices,
+            });
+        }
+        let vertex_size = self.vertex_layout.vertex_size();
+        for (vertex, data) in self.vertices.chunks_exact(vertex_size).enumerate() {
+            let mut offset = 0;
+            for (component_index, component) in self.vertex_layout.components.iter().enumerate() {
+                let bytes = &data[offset..offset + component.size()];
+                offset += component.size();
+                let finite = match component {
+                    VertexComponent::Vec1F32
+                    | VertexComponent::Vec2F32
+                    | VertexComponent::Vec3F32
+                    | VertexComponent::Vec4F32 => bytes
+                        .chunks_exact(4)
+                        .all(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]).is_finite()),
+                    VertexComponent::Vec1F16
+                    | VertexComponent::Vec2F16
+                    | VertexComponent::Vec4F16 => bytes
+                        .chunks_exact(2)
+                        .all(|b| half::f16::from_ne_bytes([b[0], b[1]]).is_finite()),
+                    _ => true,
+                };
+                if !finite {
+                    return Err(MeshError::NonFinite {
+                        vertex,
+                        component: component_index,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Checks that the vertex layout matches the vertex input of a shader,
+    /// component `i` of the layout is fed to location `i`
+    pub fn validate_vertex_input(
+        &self,
+        shader: &str,
+        input: &VertexInput,
+    ) -> Result<(), MeshError> {
+        let components = &self.vertex_layout.components;
+        if components.len() != input.fields.len() {
+            return Err(MeshError::ComponentCountMismatch {
+                shader: shader.to_string(),
+                mesh: components.len(),
+                shader_inputs: input.fields.len(),
+            });
+        }
+        for field in input.fields.iter() {
+            let component = components.get(field.location as usize).ok_or_else(|| {
+                MeshError::ComponentCountMismatch {
+                    shader: shader.to_string(),
+                    mesh: components.len(),
+                    shader_inputs: field.location as usize + 1,
+                }
+            })?;
+            if !component_matches(component, &field.ty) {
+                return Err(MeshError::LayoutMismatch {
+                    shader: shader.to_string(),
+                    location: field.location,
+                    component: component.clone(),
+                    expected: field.ty.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ass_types::VertexField;
+    #[test]
+    fn reports_invalid_meshes() {
+        let cube = Mesh::new_cube();
+        assert_eq!(cube.validate(), Ok(()));
+
+        let mut short = cube.clone();
+        short.vertices.pop();
+        assert!(matches!(
+            short.validate_lengths(),
+            Err(MeshError::VertexLengthMismatch { .. })
+        ));
+
+        let mut lines = cube.clone().with_topology(Topology::LineList);
+        assert_eq!(lines.validate_lengths(), Ok(()));
+        lines.indices.pop();
+        assert_eq!(
+            lines.validate_lengths(),
+            Err(MeshError::IndexCountMismatch {
+                count: 35,
+                topology: Topology::LineList
+            })
+        );
+
+        let mut out_of_range = cube.clone();
+        out_of_range.indices[4] = cube.num_vertices() as u32;
+        assert_eq!(
+            out_of_range.validate(),
+            Err(MeshError::IndexOutOfRange {
+                position: 4,
+                index: cube.num_vertices() as u32,
+                num_vertices: cube.num_vertices(),
+            })
+        );
+
+        let mut nan = cube.clone();
+        nan.vertices[4..8].copy_from_slice(&f32::NAN.to_ne_bytes());
+        assert_eq!(
+            nan.validate(),
+            Err(MeshError::NonFinite {
+                vertex: 0,
+                component: 0
+            })
+        );
+    }
+    #[test]
+    fn checks_shader_input() {
+        let mesh = Mesh::new_cube();
+        let field = |location: u32, ty: ShaderType| VertexField {
+            ty,
+            location,
+            name: format!("input_{}", location),
+            binding: 0,
+        };
+        let mut input = VertexInput {
+            binding: 0,
+            fields: mesh
+                .vertex_layout
+                .components
+                .iter()
+                .enumerate()
+                .map(|(location, component)| {
+                    let ty = match component.num_components() {
+                        1 => ShaderType::Scalar(Scalar::F32),
+                        2 => ShaderType::Vec2(Scalar::F32),
+                        3 => ShaderType::Vec3(Scalar::F32),
+                        _ => ShaderType::Vec4(Scalar::F32),
+                    };
+                    field(location as u32, ty)
+                })
+                .collect(),
+        };
+        assert_eq!(mesh.validate_vertex_input("test", &input), Ok(()));
+        let mut colored = mesh.clone();
+        colored.vertex_layout.components[1] = VertexComponent::Vec4U8Norm;
+        assert_eq!(colored.validate_vertex_input("test", &input), Ok(()));
+        input.fields[0] = field(0, ShaderType::Vec3(Scalar::U32));
+        assert!(matches!(
+            mesh.validate_vertex_input("test", &input),
+            Err(MeshError::LayoutMismatch { location: 0, .. })
+        ));
+        input.fields.pop();
+        assert!(matches!(
+            mesh.validate_vertex_input("test", &input),
+            Err(MeshError::ComponentCountMismatch { .. })
+        ));
+    }
+}
@@ -1,11 +1,13 @@
 use super::{
-    BackendTrait, ContextTrait, ControlFlow, CreateInfo, EventLoopTrait, GenericBindable,
-    GenericDrawableTexture, MeshAsset, Timer, WindowEvent,
+    AdapterInfo, BackendTrait, Bounds, BufferKind, ComputeReference, ContextTrait, ControlFlow,
+    CreateInfo, DebugDraw, DrawQueue, DrawStats, EventLoopTrait, FramebufferDesc, GenericBindable,
+    GenericDrawableTexture, MeshAsset, PresentMode, Timer, WindowEvent,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use image::RgbaImage;
-use nalgebra::Vector2;
+use nalgebra::{Matrix4, Vector2, Vector3};
 use std::{
+    collections::HashMap,
     path::Path,
     sync::{Arc, Mutex},
     time::{Duration, Instant},
@@ -32,15 +34,34 @@ impl BackendTrait for Backend {
         Self {}
     }
 }
+/// The stub has no gpus
+pub fn list_adapters() -> Result<Vec<AdapterInfo>> {
+    Ok(vec![])
+}
 pub struct Context {
     quit: Arc<Mutex<bool>>,
+    /// run by `dispatch` in place of compute shaders
+    compute_references: Arc<Mutex<HashMap<String, ComputeReference>>>,
 }
 #[derive(Debug)]
-pub struct Mesh {}
+pub struct Mesh {
+    bounds: Option<Bounds>,
+}
+impl Mesh {
+    /// Bounds of the mesh in model space
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+}
 #[derive(Debug)]
 pub struct Framebuffer {}
 #[derive(Debug)]
 pub struct Texture {}
+/// Buffers keep their data so compute references can run on it
+#[derive(Debug)]
+pub struct Buffer {
+    data: Arc<Mutex<Vec<u8>>>,
+}
 pub struct TimerContainer {
     instant: Instant,
 }
@@ -59,10 +80,12 @@ impl ContextTrait for Context {
     type Mesh = Mesh;
     type Framebuffer = Framebuffer;
     type Texture = Texture;
+    type Buffer = Buffer;
     type Timer = TimerContainer;
     fn new(_: Self::Backend) -> Self {
         Self {
             quit: Arc::new(Mutex::new(false)),
+            compute_references: Arc::new(Mutex::new(HashMap::new())),
         }
     }
     fn begin_render(&mut self) -> Result<()> {
@@ -73,10 +96,15 @@ impl ContextTrait for Context {
     }
     fn build_mesh(
         &mut self,
-        _: MeshAsset,
+        mesh: MeshAsset,
         _: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
     ) -> Result<Self::Mesh> {
-        Ok(Mesh {})
+        mesh.validate_lengths()?;
+        #[cfg(feature = "state_validation")]
+        mesh.validate()?;
+        Ok(Mesh {
+            bounds: mesh.bounds(),
+        })
     }
     fn bind_texture(
         &mut self,
@@ -85,31 +113,152 @@ impl ContextTrait for Context {
     ) -> Result<()> {
         Ok(())
     }
+    fn build_mesh_named(
+        &mut self,
+        mesh: MeshAsset,
+        texture: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
+        _: &str,
+    ) -> Result<Self::Mesh> {
+        self.build_mesh(mesh, texture)
+    }
     fn build_texture(&mut self, _: &RgbaImage) -> Result<Self::Texture> {
         Ok(Texture {})
     }
+    fn build_texture_named(&mut self, _: &RgbaImage, _: &str) -> Result<Self::Texture> {
+        Ok(Texture {})
+    }
     fn draw_mesh(&mut self, _: Vec<u8>, _: &Self::Mesh) -> Result<()> {
         Ok(())
     }
+    fn draw_mesh_sorted(&mut self, _: Vec<u8>, _: &Self::Mesh, _: DrawQueue, _: f32) -> Result<()> {
+        Ok(())
+    }
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats::default()
+    }
+    fn draw_debug(&mut self, _: &DebugDraw, _: Matrix4<f32>) -> Result<()> {
+        Ok(())
+    }
 
-    fn build_framebuffer(&mut self, _: Vector2<u32>) -> Result<Self::Framebuffer> {
+    fn build_framebuffer(&mut self, _: FramebufferDesc) -> Result<Self::Framebuffer> {
+        Ok(Framebuffer {})
+    }
+    fn build_framebuffer_named(
+        &mut self,
+        _: FramebufferDesc,
+        _: &str,
+    ) -> Result<Self::Framebuffer> {
         Ok(Framebuffer {})
     }
+    fn build_buffer(&mut self, _: BufferKind, data: &[u8]) -> Result<Self::Buffer> {
+        Ok(Buffer {
+            data: Arc::new(Mutex::new(data.to_vec())),
+        })
+    }
+    fn update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]) -> Result<()> {
+        let mut buffer_data = buffer.data.lock().expect("failed to get lock");
+        if data.len() > buffer_data.len() {
+            bail!(
+                "update of {} bytes does not fit in buffer of {} bytes",
+                data.len(),
+                buffer_data.len()
+            )
+        }
+        buffer_data[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+    fn bind_shader_buffer(&mut self, _: &str, _: &str, _: &Self::Buffer) -> Result<()> {
+        Ok(())
+    }
+    fn bind_mesh_buffer(&mut self, _: &mut Self::Mesh, _: &str, _: &Self::Buffer) -> Result<()> {
+        Ok(())
+    }
     fn bind_shader(&mut self, _: GenericBindable<Self::Framebuffer>, _: &str) -> Result<()> {
         Ok(())
     }
     fn bind_framebuffer(&mut self, _: GenericBindable<Self::Framebuffer>) -> Result<()> {
         Ok(())
     }
+    fn push_debug_group(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn pop_debug_group(&mut self) -> Result<()> {
+        Ok(())
+    }
     fn get_screen_size(&self) -> Vector2<u32> {
         Vector2::new(100, 100)
     }
+    fn set_present_mode(&mut self, _: PresentMode) -> Result<()> {
+        Ok(())
+    }
     fn load_shader<P: AsRef<Path>>(&mut self, _: P, _: &str) -> Result<()> {
         Ok(())
     }
+    fn watch_shader(&mut self, _: &Path, _: &str) -> Result<()> {
+        Ok(())
+    }
     fn load_shader_v2(&mut self, shader: &str, shader_name: &str) -> Result<()> {
         Ok(())
     }
+    fn load_compute_shader(&mut self, _: &str, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn set_compute_reference(
+        &mut self,
+        shader_name: &str,
+        reference: ComputeReference,
+    ) -> Result<()> {
+        self.compute_references
+            .lock()
+            .expect("failed to get lock")
+            .insert(shader_name.to_string(), reference);
+        Ok(())
+    }
+    /// Runs the compute reference of `shader`
+    fn dispatch(
+        &mut self,
+        shader: &str,
+        groups: Vector3<u32>,
+        bindings: &[(&str, &Self::Buffer)],
+    ) -> Result<()> {
+        let reference = match self
+            .compute_references
+            .lock()
+            .expect("failed to get lock")
+            .get(shader)
+        {
+            Some(reference) => *reference,
+            None => bail!("compute shader {} has no compute reference", shader),
+        };
+        for (i, (name, buffer)) in bindings.iter().enumerate() {
+            if bindings[..i]
+                .iter()
+                .any(|(_name, other)| Arc::ptr_eq(&buffer.data, &other.data))
+            {
+                bail!(
+                    "buffer bound to {} is bound to shader {} more than once",
+                    name,
+                    shader
+                )
+            }
+        }
+        let mut data = bindings
+            .iter()
+            .map(|(_name, buffer)| buffer.data.lock().expect("failed to get lock").clone())
+            .collect::<Vec<_>>();
+        reference(groups, &mut data);
+        for ((_name, buffer), data) in bindings.iter().zip(data) {
+            let mut buffer_data = buffer.data.lock().expect("failed to get lock");
+            if data.len() != buffer_data.len() {
+                bail!("compute reference of {} resized a buffer", shader)
+            }
+            *buffer_data = data;
+        }
+        Ok(())
+    }
+    fn read_buffer(&mut self, buffer: &Self::Buffer) -> Result<Vec<u8>> {
+        Ok(buffer.data.lock().expect("failed to get lock").clone())
+    }
     fn quit(&mut self) {
         *self.quit.lock().expect("failed to get lock") = true
     }
@@ -118,7 +267,65 @@ impl ContextTrait for Context {
     }
     fn check_state(&mut self) {}
     fn clone(&self) -> Self {
-        let quit = self.quit.clone();
-        Self { quit }
+        Self {
+            quit: self.quit.clone(),
+            compute_references: self.compute_references.clone(),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    /// Adds the rain amount in buffer 1 to every height in buffer 0
+    fn add_rain(groups: Vector3<u32>, buffers: &mut [Vec<u8>]) {
+        let rain = f32::from_ne_bytes(buffers[1][..4].try_into().unwrap());
+        for i in 0..(groups.x * 4) as usize {
+            let height = &mut buffers[0][i * 4..i * 4 + 4];
+            let value = f32::from_ne_bytes((&*height).try_into().unwrap()) + rain;
+            height.copy_from_slice(&value.to_ne_bytes());
+        }
+    }
+    fn to_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_ne_bytes()).collect()
+    }
+    #[test]
+    fn dispatch_runs_compute_reference() {
+        let mut context = Context::new(Backend {});
+        let heights = context
+            .build_buffer(BufferKind::Storage, &to_bytes(&[0.0; 8]))
+            .unwrap();
+        let rain = context
+            .build_buffer(BufferKind::Storage, &to_bytes(&[0.5]))
+            .unwrap();
+        context.load_compute_shader("", "erosion").unwrap();
+        let bindings = [("heights", &heights), ("rain", &rain)];
+        assert!(context
+            .dispatch("erosion", Vector3::new(1, 1, 1), &bindings)
+            .is_err());
+
+        context.set_compute_reference("erosion", add_rain).unwrap();
+        context
+            .dispatch("erosion", Vector3::new(1, 1, 1), &bindings)
+            .unwrap();
+        let mut expected = [0.0; 8];
+        expected[..4].copy_from_slice(&[0.5; 4]);
+        assert_eq!(context.read_buffer(&heights).unwrap(), to_bytes(&expected));
+
+        context.update_buffer(&rain, &to_bytes(&[1.0])).unwrap();
+        context
+            .clone()
+            .dispatch("erosion", Vector3::new(2, 1, 1), &bindings)
+            .unwrap();
+        expected[..4].copy_from_slice(&[1.5; 4]);
+        expected[4..].copy_from_slice(&[1.0; 4]);
+        assert_eq!(context.read_buffer(&heights).unwrap(), to_bytes(&expected));
+        assert!(context
+            .dispatch(
+                "erosion",
+                Vector3::new(1, 1, 1),
+                &[("heights", &heights), ("rain", &heights)]
+            )
+            .is_err());
     }
 }
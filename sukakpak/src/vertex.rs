@@ -1,10 +1,24 @@
+use half::f16;
+use nalgebra::{Vector2, Vector3, Vector4};
 use std::mem::size_of;
-#[derive(Clone, Debug, PartialEq)]
+/// Type of a single vertex input
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum VertexComponent {
     Vec1F32,
     Vec2F32,
     Vec3F32,
     Vec4F32,
+    /// Four unsigned bytes read by the shader as floats in the range 0 to 1,
+    /// used for colors
+    Vec4U8Norm,
+    Vec2U16,
+    /// Four unsigned shorts, used for joint indices
+    Vec4U16,
+    Vec1U32,
+    Vec1I32,
+    Vec1F16,
+    Vec2F16,
+    Vec4F16,
 }
 impl VertexComponent {
     /// Gets number of components in vertex
@@ -14,6 +28,14 @@ impl VertexComponent {
             Self::Vec2F32 => 2,
             Self::Vec3F32 => 3,
             Self::Vec4F32 => 4,
+            Self::Vec4U8Norm => 4,
+            Self::Vec2U16 => 2,
+            Self::Vec4U16 => 4,
+            Self::Vec1U32 => 1,
+            Self::Vec1I32 => 1,
+            Self::Vec1F16 => 1,
+            Self::Vec2F16 => 2,
+            Self::Vec4F16 => 4,
         }
     }
     /// Gets size in bytes of each component
@@ -23,12 +45,126 @@ impl VertexComponent {
             Self::Vec2F32 => 2 * size_of::<f32>(),
             Self::Vec3F32 => 3 * size_of::<f32>(),
             Self::Vec4F32 => 4 * size_of::<f32>(),
+            Self::Vec4U8Norm => 4 * size_of::<u8>(),
+            Self::Vec2U16 => 2 * size_of::<u16>(),
+            Self::Vec4U16 => 4 * size_of::<u16>(),
+            Self::Vec1U32 => size_of::<u32>(),
+            Self::Vec1I32 => size_of::<i32>(),
+            Self::Vec1F16 => size_of::<f16>(),
+            Self::Vec2F16 => 2 * size_of::<f16>(),
+            Self::Vec4F16 => 4 * size_of::<f16>(),
         }
     }
+    /// Whether the shader reads the component as integers rather than floats
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Self::Vec2U16 | Self::Vec4U16 | Self::Vec1U32 | Self::Vec1I32)
+    }
+    /// Whether integer data is converted to floats in the range 0 to 1
+    pub fn is_normalized(&self) -> bool {
+        matches!(self, Self::Vec4U8Norm)
+    }
 }
 /// Layout of vertex in mesh, order reperesents `location` in
 /// Webgl and Vulkan backends
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct VertexLayout {
     pub components: Vec<VertexComponent>,
 }
+impl VertexLayout {
+    /// Gets size in bytes of a single vertex
+    pub fn vertex_size(&self) -> usize {
+        self.components.iter().map(|c| c.size()).sum()
+    }
+    /// Gets offset in bytes of component at `index` from the start of the vertex
+    pub fn component_offset(&self, index: usize) -> usize {
+        self.components[..index].iter().map(|c| c.size()).sum()
+    }
+}
+/// Type that can be stored as a single component of a vertex. Bytes are
+/// written in native endianness.
+pub trait VertexAttribute {
+    fn component() -> VertexComponent;
+    fn write_bytes(&self, out: &mut Vec<u8>);
+}
+/// Vertex with a fixed layout, usually implemented with `#[derive(Vertex)]`
+pub trait VertexTrait {
+    fn vertex_layout() -> VertexLayout;
+    /// Appends the vertex to `out` in the order given by `vertex_layout`
+    fn write_bytes(&self, out: &mut Vec<u8>);
+}
+macro_rules! vertex_attribute {
+    ($ty:ty, $component:ident, |$value:ident| $slice:expr) => {
+        impl VertexAttribute for $ty {
+            fn component() -> VertexComponent {
+                VertexComponent::$component
+            }
+            fn write_bytes(&self, out: &mut Vec<u8>) {
+                let $value = self;
+                for v in $slice.iter() {
+                    out.extend_from_slice(&v.to_ne_bytes());
+                }
+            }
+        }
+    };
+}
+vertex_attribute!(f32, Vec1F32, |v| [*v]);
+vertex_attribute!([f32; 2], Vec2F32, |v| v);
+vertex_attribute!([f32; 3], Vec3F32, |v| v);
+vertex_attribute!([f32; 4], Vec4F32, |v| v);
+vertex_attribute!(Vector2<f32>, Vec2F32, |v| v);
+vertex_attribute!(Vector3<f32>, Vec3F32, |v| v);
+vertex_attribute!(Vector4<f32>, Vec4F32, |v| v);
+vertex_attribute!([u8; 4], Vec4U8Norm, |v| v);
+vertex_attribute!([u16; 2], Vec2U16, |v| v);
+vertex_attribute!([u16; 4], Vec4U16, |v| v);
+vertex_attribute!(u32, Vec1U32, |v| [*v]);
+vertex_attribute!(i32, Vec1I32, |v| [*v]);
+vertex_attribute!(f16, Vec1F16, |v| [*v]);
+vertex_attribute!([f16; 2], Vec2F16, |v| v);
+vertex_attribute!([f16; 4], Vec4F16, |v| v);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Vertex;
+    #[derive(Vertex)]
+    struct ColorVertex {
+        position: Vector3<f32>,
+        color: [u8; 4],
+        id: u32,
+        weight: f16,
+    }
+    #[derive(Vertex)]
+    struct Tuple(f32, [u16; 2]);
+    #[test]
+    fn derive_vertex() {
+        let layout = ColorVertex::vertex_layout();
+        assert_eq!(
+            layout.components,
+            vec![
+                VertexComponent::Vec3F32,
+                VertexComponent::Vec4U8Norm,
+                VertexComponent::Vec1U32,
+                VertexComponent::Vec1F16
+            ]
+        );
+        assert_eq!(layout.vertex_size(), 12 + 4 + 4 + 2);
+        let mut bytes = vec![];
+        ColorVertex {
+            position: Vector3::new(1.0, 2.0, 3.0),
+            color: [255, 0, 128, 1],
+            id: 7,
+            weight: f16::from_f32(0.5),
+        }
+        .write_bytes(&mut bytes);
+        assert_eq!(bytes.len(), layout.vertex_size());
+        assert_eq!(&bytes[4..8], &2.0f32.to_ne_bytes());
+        assert_eq!(&bytes[12..16], &[255, 0, 128, 1]);
+        assert_eq!(&bytes[16..20], &7u32.to_ne_bytes());
+        assert_eq!(&bytes[20..22], &f16::from_f32(0.5).to_ne_bytes());
+
+        let mut bytes = vec![];
+        Tuple(1.0, [3, 4]).write_bytes(&mut bytes);
+        assert_eq!(Tuple::vertex_layout().vertex_size(), bytes.len());
+        assert_eq!(&bytes[4..6], &3u16.to_ne_bytes());
+    }
+}
@@ -1,18 +1,20 @@
 mod backend;
 pub mod events;
 use anyhow::{Context as EContext, Result};
-use backend::{Backend, BoundFramebuffer, FramebufferID, MeshID, TextureID};
+use backend::{Backend, BoundFramebuffer, BufferID, FramebufferID, MeshID, TextureID};
 
 use super::{
-    mesh::Mesh as MeshAsset, CreateInfo, Event, MouseButton, ScrollDelta, SemanticKeyCode, Timer,
+    mesh::Mesh as MeshAsset, Bounds, BufferKind, ComputeReference, CreateInfo, DebugDraw,
+    DrawQueue, DrawStats, Event, FramebufferDesc, MouseButton, PresentMode, ScrollDelta,
+    SemanticKeyCode, Timer,
 };
 use super::{VertexComponent, VertexLayout};
-pub use backend::MeshTexture;
+pub use backend::{list_adapters, MeshTexture};
 use image;
 use image::RgbaImage;
 use nalgebra;
 
-use nalgebra::Vector2;
+use nalgebra::{Matrix4, Vector2, Vector3};
 use std::{
     path::Path,
     sync::{Arc, Mutex},
@@ -35,8 +37,15 @@ impl Timer for TimerContainer {
 unsafe impl Send for Mesh {}
 pub struct Mesh {
     mesh: MeshID,
+    bounds: Option<Bounds>,
     backend: Arc<Mutex<Backend>>,
 }
+impl Mesh {
+    /// Bounds of the mesh in model space
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+}
 impl std::fmt::Debug for Mesh {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Mesh").field("mesh", &self.mesh).finish()
@@ -72,6 +81,27 @@ impl Drop for Texture {
             .expect("failed to free texture");
     }
 }
+unsafe impl Send for Buffer {}
+pub struct Buffer {
+    buffer: BufferID,
+    backend: Arc<Mutex<Backend>>,
+}
+impl std::fmt::Debug for Buffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Buffer")
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .free_buffer(self.buffer)
+            .expect("failed to free buffer");
+    }
+}
 unsafe impl Send for Framebuffer {}
 pub struct Framebuffer {
     framebuffer: FramebufferID,
@@ -117,11 +147,29 @@ impl super::BackendTrait for BackendArc {
         Self(Arc::new(Mutex::new(backend)))
     }
 }
+impl BackendArc {
+    /// Backend rendering to offscreen images without a window or event loop,
+    /// used to run the renderer on CI with a software driver
+    pub fn new_headless(create_info: CreateInfo) -> Result<Self> {
+        let backend = Backend::new_headless(create_info)?;
+        Ok(Self(Arc::new(Mutex::new(backend))))
+    }
+}
+impl Context {
+    /// Reads the last finished frame, only works with a headless backend
+    pub fn read_screen(&mut self) -> Result<RgbaImage> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .read_screen()
+    }
+}
 impl super::ContextTrait for Context {
     type Backend = BackendArc;
     type Mesh = Mesh;
     type Framebuffer = Framebuffer;
     type Texture = Texture;
+    type Buffer = Buffer;
     type Timer = TimerContainer;
     fn new(backend: BackendArc) -> Self {
         Self {
@@ -152,20 +200,26 @@ impl super::ContextTrait for Context {
         texture: super::GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
     ) -> Result<Self::Mesh> {
         self.check_state();
+        mesh.validate_lengths()?;
+        #[cfg(feature = "state_validation")]
+        {
+            mesh.validate()?;
+            self.backend
+                .lock()
+                .expect("failed to get lock")
+                .validate_mesh(&mesh)?;
+        }
+        let bounds = mesh.bounds();
         let mesh = self
             .backend
             .lock()
             .expect("failed to get lock")
-            .build_mesh(
-                mesh.vertices,
-                mesh.vertex_layout,
-                mesh.indices,
-                texture.into(),
-            )?;
+            .build_mesh(mesh, texture.into())?;
 
         self.check_state();
         Ok(Mesh {
             mesh,
+            bounds,
             backend: self.backend.clone(),
         })
     }
@@ -185,6 +239,19 @@ impl super::ContextTrait for Context {
         self.check_state();
         Ok(())
     }
+    fn build_mesh_named(
+        &mut self,
+        mesh: MeshAsset,
+        texture: super::GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
+        name: &str,
+    ) -> Result<Self::Mesh> {
+        let mesh = self.build_mesh(mesh, texture)?;
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .set_mesh_name(&mesh.mesh, name)?;
+        Ok(mesh)
+    }
     fn build_texture(&mut self, image: &RgbaImage) -> Result<Self::Texture> {
         self.check_state();
         let texture = self
@@ -200,6 +267,14 @@ impl super::ContextTrait for Context {
             backend: self.backend.clone(),
         })
     }
+    fn build_texture_named(&mut self, image: &RgbaImage, name: &str) -> Result<Self::Texture> {
+        let texture = self.build_texture(image)?;
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .set_texture_name(&texture.texture, name)?;
+        Ok(texture)
+    }
     fn draw_mesh(&mut self, push: Vec<u8>, mesh: &Self::Mesh) -> Result<()> {
         self.check_state();
         self.backend
@@ -209,18 +284,107 @@ impl super::ContextTrait for Context {
         self.check_state();
         Ok(())
     }
-    fn build_framebuffer(&mut self, resolution: Vector2<u32>) -> Result<Framebuffer> {
+    fn draw_mesh_sorted(
+        &mut self,
+        push: Vec<u8>,
+        mesh: &Self::Mesh,
+        queue: DrawQueue,
+        depth: f32,
+    ) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .draw_mesh_sorted(push, &mesh.mesh, queue, depth)
+    }
+    fn draw_stats(&self) -> DrawStats {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .draw_stats()
+    }
+    fn draw_debug(&mut self, debug: &DebugDraw, view_projection: Matrix4<f32>) -> Result<()> {
+        if debug.is_empty() {
+            return Ok(());
+        }
+        let push = view_projection
+            .as_slice()
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect();
+        self.check_state();
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .draw_debug(debug.line_mesh(&view_projection), push)?;
+        self.check_state();
+        Ok(())
+    }
+    fn build_framebuffer(&mut self, desc: FramebufferDesc) -> Result<Framebuffer> {
         let framebuffer = self
             .backend
             .lock()
             .expect("failed to get lock")
-            .build_framebuffer(resolution)
+            .build_framebuffer(desc)
             .expect("failed to build framebuffer");
         Ok(Framebuffer {
             framebuffer,
             backend: self.backend.clone(),
         })
     }
+    fn build_framebuffer_named(
+        &mut self,
+        desc: FramebufferDesc,
+        name: &str,
+    ) -> Result<Framebuffer> {
+        let framebuffer = self.build_framebuffer(desc)?;
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .set_framebuffer_name(&framebuffer.framebuffer, name)?;
+        Ok(framebuffer)
+    }
+    fn build_buffer(&mut self, kind: BufferKind, data: &[u8]) -> Result<Self::Buffer> {
+        let buffer = self
+            .backend
+            .lock()
+            .expect("failed to get lock")
+            .build_buffer(kind, data)?;
+        Ok(Buffer {
+            buffer,
+            backend: self.backend.clone(),
+        })
+    }
+    fn update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .update_buffer(&buffer.buffer, data)
+    }
+    fn bind_shader_buffer(
+        &mut self,
+        shader: &str,
+        name: &str,
+        buffer: &Self::Buffer,
+    ) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .bind_shader_buffer(shader, name, buffer.buffer)
+    }
+    fn bind_mesh_buffer(
+        &mut self,
+        mesh: &mut Self::Mesh,
+        name: &str,
+        buffer: &Self::Buffer,
+    ) -> Result<()> {
+        self.check_state();
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .bind_mesh_buffer(&mesh.mesh, name, buffer.buffer)?;
+        self.check_state();
+        Ok(())
+    }
     fn bind_shader(
         &mut self,
         framebuffer: super::GenericBindable<Self::Framebuffer>,
@@ -246,12 +410,30 @@ impl super::ContextTrait for Context {
         self.check_state();
         Ok(())
     }
+    fn push_debug_group(&mut self, label: &str) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .push_debug_group(label)
+    }
+    fn pop_debug_group(&mut self) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .pop_debug_group()
+    }
     fn get_screen_size(&self) -> Vector2<u32> {
         self.backend
             .lock()
             .expect("failed to get lock")
             .get_screen_size()
     }
+    fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .set_present_mode(present_mode)
+    }
     fn load_shader(&mut self, shader: &str, shader_name: &str) -> Result<()> {
         self.check_state();
         self.backend
@@ -262,6 +444,45 @@ impl super::ContextTrait for Context {
         self.check_state();
         Ok(())
     }
+    fn watch_shader(&mut self, project_dir: &Path, shader_name: &str) -> Result<()> {
+        self.check_state();
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .watch_shader(project_dir, shader_name)?;
+        self.check_state();
+        Ok(())
+    }
+    fn load_compute_shader(&mut self, shader: &str, shader_name: &str) -> Result<()> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .load_compute_shader(shader, shader_name)
+    }
+    fn set_compute_reference(&mut self, _: &str, _: ComputeReference) -> Result<()> {
+        Ok(())
+    }
+    fn dispatch(
+        &mut self,
+        shader: &str,
+        groups: Vector3<u32>,
+        bindings: &[(&str, &Self::Buffer)],
+    ) -> Result<()> {
+        let bindings = bindings
+            .iter()
+            .map(|(name, buffer)| (*name, buffer.buffer))
+            .collect::<Vec<_>>();
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .dispatch(shader, groups, &bindings)
+    }
+    fn read_buffer(&mut self, buffer: &Self::Buffer) -> Result<Vec<u8>> {
+        self.backend
+            .lock()
+            .expect("failed to get lock")
+            .read_buffer(&buffer.buffer)
+    }
     fn quit(&mut self) {
         *self.quit.lock().unwrap() = true;
     }
@@ -282,3 +503,189 @@ impl super::ContextTrait for Context {
         }
     }
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContextTrait, DevicePreference, SampleCount};
+    use std::convert::TryInto;
+    fn headless_context(size: Vector2<u32>) -> Context {
+        let backend = BackendArc::new_headless(CreateInfo {
+            default_size: size,
+            window_id: String::new(),
+            name: "headless test".to_string(),
+            vulkan_sdk_path: None,
+            pipeline_cache_dir: None,
+            present_mode: PresentMode::Vsync,
+            swapchain_images: None,
+            max_frames_in_flight: 2,
+            sample_count: SampleCount::X4,
+            device_preference: DevicePreference::Software,
+        })
+        .expect("failed to create headless backend");
+        Context::new(backend)
+    }
+    #[test]
+    #[ignore = "needs a vulkan driver such as lavapipe"]
+    fn renders_headless() {
+        let size = Vector2::new(64, 48);
+        let mut context = headless_context(size);
+        let texture = context
+            .build_texture(&RgbaImage::from_pixel(
+                1,
+                1,
+                image::Rgba([255, 255, 255, 255]),
+            ))
+            .unwrap();
+        let mesh = context
+            .build_mesh_named(
+                MeshAsset::new_cube(),
+                super::super::GenericDrawableTexture::Texture(&texture),
+                "cube",
+            )
+            .unwrap();
+        // cube edges land on pixel borders so the image does not depend on the
+        // multisample pattern
+        let push: Vec<u8> = Matrix4::<f32>::new_scaling(0.25)
+            .as_slice()
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect();
+        for _ in 0..3 {
+            context.begin_render().unwrap();
+            context.push_debug_group("cube").unwrap();
+            context.draw_mesh(push.clone(), &mesh).unwrap();
+            context.pop_debug_group().unwrap();
+            assert!(context.pop_debug_group().is_err());
+            context.finish_render().unwrap();
+        }
+        let screen = context.read_screen().unwrap();
+        assert_eq!((screen.width(), screen.height()), (size.x, size.y));
+        // run with SUKAKPAK_BLESS=1 to overwrite the reference with this render, e.g. after
+        // changing the scene, and check the new image by eye before committing it
+        if std::env::var_os("SUKAKPAK_BLESS").is_some() {
+            screen
+                .save(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/test_data/renders_headless.png"
+                ))
+                .unwrap();
+            return;
+        }
+        // white cube over the 0.1 gray clear color
+        let reference =
+            image::load_from_memory(include_bytes!("../test_data/renders_headless.png"))
+                .unwrap()
+                .to_rgba8();
+        assert_eq!(screen.dimensions(), reference.dimensions());
+        for (x, y, expected) in reference.enumerate_pixels() {
+            let pixel = screen.get_pixel(x, y);
+            for (channel, expected) in pixel.0.iter().zip(expected.0.iter()) {
+                assert!(
+                    (*channel as i32 - *expected as i32).abs() <= 2,
+                    "pixel ({}, {}) is {:?}, expected {:?}",
+                    x,
+                    y,
+                    pixel,
+                    expected
+                );
+            }
+        }
+    }
+    #[test]
+    #[ignore = "needs a vulkan driver such as lavapipe"]
+    fn sorted_draws_group_textures() {
+        let mut context = headless_context(Vector2::new(32, 32));
+        let textures = [[255, 0, 0, 255], [0, 0, 255, 255]]
+            .iter()
+            .map(|color| {
+                context
+                    .build_texture(&RgbaImage::from_pixel(1, 1, image::Rgba(*color)))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        // alternates textures in submission order
+        let meshes = (0..4)
+            .map(|i| {
+                context
+                    .build_mesh(
+                        MeshAsset::new_cube(),
+                        super::super::GenericDrawableTexture::Texture(&textures[i % 2]),
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let push: Vec<u8> = Matrix4::<f32>::new_scaling(0.1)
+            .as_slice()
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect();
+        context.begin_render().unwrap();
+        for (i, mesh) in meshes.iter().enumerate() {
+            context
+                .draw_mesh_sorted(push.clone(), mesh, DrawQueue::Opaque, i as f32)
+                .unwrap();
+        }
+        context.finish_render().unwrap();
+        let stats = context.draw_stats();
+        assert_eq!(stats.draws, 4);
+        assert_eq!(stats.texture_binds, 2);
+        assert_eq!(stats.texture_binds_saved, 2);
+    }
+    /// Adds the rain amount in buffer 1 to every height in buffer 0
+    fn add_rain(groups: Vector3<u32>, buffers: &mut [Vec<u8>]) {
+        let rain = f32::from_ne_bytes(buffers[1][..4].try_into().unwrap());
+        for i in 0..(groups.x * 64) as usize {
+            let height = &mut buffers[0][i * 4..i * 4 + 4];
+            let value = f32::from_ne_bytes((&*height).try_into().unwrap()) + rain;
+            height.copy_from_slice(&value.to_ne_bytes());
+        }
+    }
+    #[test]
+    #[ignore = "needs a vulkan driver such as lavapipe"]
+    fn dispatch_matches_compute_reference() {
+        let ir = ass_lib::ShaderIR::from_wgsl(
+            r#"
+[[block]]
+struct Heights{
+    values: array<f32>;
+};
+[[block]]
+struct Rain{
+    amount: f32;
+};
+[[group(0),binding(0)]]
+var<storage, read_write> heights: Heights;
+[[group(0),binding(1)]]
+var<storage, read> rain: Rain;
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>){
+    heights.values[id.x] = heights.values[id.x] + rain.amount;
+}
+"#,
+            vec![],
+        )
+        .unwrap();
+        let shader = ass_vk::ComputeShader::from_ir(ir, ass_vk::Options::default()).unwrap();
+        let mut context = headless_context(Vector2::new(16, 16));
+        context
+            .load_compute_shader(&shader.to_json_string().unwrap(), "rain")
+            .unwrap();
+        let heights: Vec<u8> = (0..256).flat_map(|i| (i as f32).to_ne_bytes()).collect();
+        let mut expected = vec![heights.clone(), 0.25f32.to_ne_bytes().to_vec()];
+        let height_buffer = context.build_buffer(BufferKind::Storage, &heights).unwrap();
+        let rain_buffer = context
+            .build_buffer(BufferKind::Storage, &expected[1])
+            .unwrap();
+        let groups = Vector3::new(2, 1, 1);
+        context
+            .dispatch(
+                "rain",
+                groups,
+                &[("heights", &height_buffer), ("rain", &rain_buffer)],
+            )
+            .unwrap();
+        add_rain(groups, &mut expected);
+        assert_eq!(context.read_buffer(&height_buffer).unwrap(), expected[0]);
+        assert_eq!(context.read_buffer(&rain_buffer).unwrap(), expected[1]);
+    }
+}
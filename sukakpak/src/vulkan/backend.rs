@@ -2,7 +2,7 @@ use anyhow::{anyhow, Context as AContext, Result};
 use ash::vk;
 
 use image::RgbaImage;
-use nalgebra::Vector2;
+use nalgebra::{Vector2, Vector3};
 use thiserror::Error;
 mod command_pool;
 mod framebuffer;
@@ -10,25 +10,41 @@ mod ref_counter;
 mod render_core;
 mod renderpass;
 mod resource_pool;
+mod shader_watcher;
 mod vertex_layout;
 use super::CreateInfo;
-use super::{VertexComponent, VertexLayout};
+use super::VertexComponent;
+use crate::{
+    draw_list::{DrawList, DrawState},
+    BufferKind, DrawQueue, DrawStats, FramebufferDesc, MeshAsset, PresentMode, Topology,
+};
+#[cfg(feature = "state_validation")]
+use crate::MeshError;
 use command_pool::CommandPool;
 use framebuffer::{
     AttachableFramebuffer, AttachmentType, DepthBuffer, FrameBufferTarget, Framebuffer,
     TextureAttachment,
 };
 use generational_arena::{Arena, Index as ArenaIndex};
-use pipeline::{basic_shader, GraphicsPipeline, PipelineType, ShaderDescription};
+use pipeline::{
+    basic_shader, compile_shader_project, debug_shader, ComputePipeline, DrawPipelines,
+    GraphicsPipeline, PipelineType, ShaderDescription,
+};
 use ref_counter::RefCounter;
+pub use render_core::list_adapters;
 use render_core::Core;
 mod pipeline;
 use renderpass::{ClearOp, RenderMesh, RenderMeshIds, RenderPass, ResourceId};
 use resource_pool::{
-    DescriptorDesc, IndexBufferAllocation, ResourcePool, TextureAllocation, TextureDescriptorSets,
-    VertexBufferAllocation,
+    BufferAllocation, DescriptorDesc, GeometryBinding, IndexBufferAllocation, ResourcePool,
+    TextureAllocation, TextureDescriptorSets, VertexBufferAllocation,
 };
-use std::collections::HashSet;
+use shader_watcher::ShaderWatcher;
+use vertex_layout::StreamLayout;
+/// Debug name of the screen framebuffer
+const SCREEN_NAME: &str = "screen";
+use std::collections::{hash_map::DefaultHasher, HashSet};
+use std::hash::{Hash, Hasher};
 use std::{collections::HashMap, path::Path};
 
 #[derive(Error, Debug)]
@@ -37,23 +53,56 @@ pub enum RenderError {
     RenderingBoundFramebuffer { fb: BoundFramebuffer },
     #[error("Shader: {shader:} not found")]
     ShaderNotFound { shader: String },
+    #[error("Shader: {shader:} has no buffer named {buffer:}")]
+    BufferNotFound { shader: String, buffer: String },
+    #[error("Buffer {buffer:} of shader {shader:} is not bound")]
+    BufferNotBound { shader: String, buffer: String },
+    #[error(
+        "Buffer {buffer:} of shader {shader:} needs a {kind:?} buffer of at least {min_size:} bytes"
+    )]
+    BufferMismatch {
+        shader: String,
+        buffer: String,
+        kind: BufferKind,
+        min_size: u32,
+    },
 }
 unsafe impl Send for Backend {}
 pub struct Backend {
     #[allow(dead_code)]
     shaders: HashMap<String, ShaderDescription>,
-    window: winit::window::Window,
+    /// projects of shaders loaded with `watch_shader`, reloaded when changed
+    shader_watcher: ShaderWatcher,
+    compute_pipelines: HashMap<String, ComputePipeline>,
+    /// None when running headless
+    window: Option<winit::window::Window>,
     models: Arena<Model>,
     textures: Arena<RefCounter<TextureAllocation>>,
     to_free_textures: HashSet<MeshTexture>,
     framebuffer_arena: Arena<RefCounter<AttachableFramebuffer>>,
+    buffers: Arena<RefCounter<BufferAllocation>>,
+    to_free_buffers: HashSet<BufferID>,
+    /// buffers bound with `bind_shader_buffer`, by shader then buffer name
+    shader_buffers: HashMap<String, HashMap<String, BufferID>>,
     command_pool: CommandPool,
     resource_pool: ResourcePool,
     main_framebuffer: Framebuffer,
     renderpass: RenderPass,
     bound_framebuffer: BoundFramebuffer,
+    /// name of last shader bound, meshes are validated against it
+    bound_shader: String,
     screen_dimensions: Vector2<u32>,
+    max_frames_in_flight: usize,
+    /// samples per pixel of the screen framebuffer
+    screen_sample_count: vk::SampleCountFlags,
     main_shader: ShaderDescription,
+    debug_shader: ShaderDescription,
+    /// white texture bound to debug lines, built on first use
+    debug_texture: Option<TextureID>,
+    /// draws queued with `draw_mesh_sorted`
+    draw_list: DrawList<(Vec<u8>, MeshID)>,
+    /// stats of the sorted draws of the last finished frame
+    draw_stats: DrawStats,
     core: Core,
 }
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -68,17 +117,37 @@ pub struct IndexBufferID {
 pub struct TextureID {
     buffer_index: ArenaIndex,
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferID {
+    buffer_index: ArenaIndex,
+}
 /// Enum allowig both framebuffers and textures to be bound to mesh
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MeshTexture {
     RegularTexture(TextureID),
     Framebuffer(FramebufferID),
 }
+impl MeshTexture {
+    /// Same for meshes bound to the same descriptor sets, used to group sorted draws
+    fn sort_key(&self) -> u64 {
+        let (index, framebuffer) = match self {
+            MeshTexture::RegularTexture(id) => (id.buffer_index, 0),
+            MeshTexture::Framebuffer(id) => (id.buffer_index, 1),
+        };
+        (index.into_raw_parts().0 as u64) << 1 | framebuffer
+    }
+}
 /// Complete Mesh
 pub struct Model {
-    vertices: VertexBufferAllocation,
+    /// one buffer per vertex stream
+    vertices: Vec<VertexBufferAllocation>,
+    /// layout of each vertex buffer, pipelines are built for it
+    stream_layouts: Vec<StreamLayout>,
     indices: IndexBufferAllocation,
     texture: MeshTexture,
+    topology: Topology,
+    /// buffers bound with `bind_mesh_buffer`
+    buffers: HashMap<String, BufferID>,
 }
 #[derive(Clone, Copy, Debug)]
 pub struct MeshID {
@@ -103,11 +172,6 @@ impl Backend {
         create_info: CreateInfo,
         event_loop: &winit::event_loop::EventLoop<()>,
     ) -> Result<Self> {
-        let shaders = [("basic".to_string(), basic_shader())]
-            .iter()
-            .cloned()
-            .collect();
-        let main_shader = basic_shader();
         let window = winit::window::WindowBuilder::new()
             .with_title(create_info.name.clone())
             .with_inner_size(winit::dpi::LogicalSize::new(
@@ -115,35 +179,60 @@ impl Backend {
                 create_info.default_size.y,
             ))
             .build(event_loop)?;
-        let mut core = Core::new(&window, &create_info)?;
+        Self::with_window(create_info, Some(window))
+    }
+    /// Renders to offscreen images of `create_info.default_size` instead of a
+    /// window, frames are read with `read_screen`
+    pub fn new_headless(create_info: CreateInfo) -> Result<Self> {
+        Self::with_window(create_info, None)
+    }
+    fn with_window(create_info: CreateInfo, window: Option<winit::window::Window>) -> Result<Self> {
+        let shaders = [("basic".to_string(), basic_shader())]
+            .iter()
+            .cloned()
+            .collect();
+        let main_shader = basic_shader();
+        let debug_shader = debug_shader();
+        let mut core = Core::new(window.as_ref(), &create_info)?;
+        let pipeline_type = Self::screen_pipeline_type(&core);
         let mut resource_pool = ResourcePool::new(&core, &main_shader)?;
         let mut command_pool = CommandPool::new(&mut core);
+        let screen_sample_count = core.sample_count(create_info.sample_count);
         let texture_attachment = TextureAttachment::new(
             &mut core,
             &mut command_pool,
             &mut resource_pool,
             AttachmentType::Swapchain,
             create_info.default_size,
+            screen_sample_count,
         )?;
 
-        let main_framebuffer = Framebuffer::new(
+        let mut main_framebuffer = Framebuffer::new(
             &mut core,
             &main_shader,
+            &debug_shader,
             &resource_pool,
             texture_attachment,
             create_info.default_size,
-            PipelineType::Present,
+            pipeline_type,
         )?;
+        main_framebuffer.set_name(&core, SCREEN_NAME)?;
         let renderpass = RenderPass::new(
             &mut core,
             &command_pool,
             &main_framebuffer.framebuffer_target,
+            create_info.max_frames_in_flight,
         );
         let screen_dimensions = create_info.default_size;
 
         Ok(Self {
             window,
             main_shader,
+            debug_shader,
+            debug_texture: None,
+            draw_list: DrawList::default(),
+            draw_stats: DrawStats::default(),
+            shader_watcher: ShaderWatcher::new(shader_watcher::POLL_INTERVAL),
             core,
             resource_pool,
             command_pool,
@@ -151,36 +240,53 @@ impl Backend {
             renderpass,
             shaders,
             bound_framebuffer: BoundFramebuffer::ScreenFramebuffer,
+            bound_shader: "basic".to_string(),
             screen_dimensions,
+            max_frames_in_flight: create_info.max_frames_in_flight,
+            screen_sample_count,
             models: Arena::new(),
             framebuffer_arena: Arena::new(),
             textures: Arena::new(),
             to_free_textures: HashSet::new(),
+            buffers: Arena::new(),
+            to_free_buffers: HashSet::new(),
+            shader_buffers: HashMap::new(),
+            compute_pipelines: HashMap::new(),
         })
     }
-    pub fn build_mesh(
+    /// Builds mesh with one vertex buffer per vertex stream read by the bound shader
+    pub fn build_mesh(&mut self, mesh: MeshAsset, texture: MeshTexture) -> Result<MeshID> {
+        let vertex_input = self.shaders[&self.bound_shader].vertex_input.clone();
+        self.build_model(mesh, &vertex_input, texture)
+    }
+    fn build_model(
         &mut self,
-        verticies: Vec<u8>,
-        vertex_layout: VertexLayout,
-        indicies: Vec<u32>,
+        mesh: MeshAsset,
+        vertex_input: &ass_types::VertexInput,
         texture: MeshTexture,
     ) -> Result<MeshID> {
         self.incr_texture_refrences(&texture);
 
-        let vertices =
-            self.resource_pool
-                .allocate_vertex_buffer(&mut self.core, verticies, vertex_layout)?;
-        let indices = self.resource_pool.allocate_index_buffer(
-            &mut self.core,
-            &mut self.command_pool,
-            indicies,
-        )?;
+        let streams = mesh.vertex_streams(vertex_input);
+        let vertices = streams
+            .iter()
+            .map(|stream| {
+                self.resource_pool
+                    .allocate_vertex_buffer(&mut self.core, stream)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let indices = self
+            .resource_pool
+            .allocate_index_buffer(&mut self.core, &mesh.indices)?;
 
         Ok(MeshID {
             buffer_index: self.models.insert(Model {
                 vertices,
+                stream_layouts: streams.iter().map(StreamLayout::from).collect(),
                 indices,
                 texture,
+                topology: mesh.topology,
+                buffers: HashMap::new(),
             }),
         })
     }
@@ -219,6 +325,7 @@ impl Backend {
         };
     }
     pub fn bind_texture(&mut self, mesh_id: &mut MeshID, texture: MeshTexture) -> Result<()> {
+        self.flush_draws()?;
         let old_texture = self.models.get(mesh_id.buffer_index).unwrap().texture;
         self.decr_texture_refrences(&old_texture);
         self.models.get_mut(mesh_id.buffer_index).unwrap().texture = texture;
@@ -228,16 +335,43 @@ impl Backend {
     pub fn allocate_texture(&mut self, texture: &RgbaImage) -> Result<TextureID> {
         let texture = TextureID {
             buffer_index: self.textures.insert(RefCounter::new(
-                self.resource_pool.allocate_texture(
-                    &mut self.core,
-                    &mut self.command_pool,
-                    texture,
-                )?,
+                self.resource_pool
+                    .allocate_texture(&mut self.core, texture)?,
                 0,
             )),
         };
         Ok(texture)
     }
+    /// Names the buffers of `mesh_id` in graphics debuggers
+    pub fn set_mesh_name(&self, mesh_id: &MeshID, name: &str) -> Result<()> {
+        let model = self.models.get(mesh_id.buffer_index).unwrap();
+        for vertex_buffer in model.vertices.iter() {
+            vertex_buffer.set_name(&self.core, &self.resource_pool, name)?;
+        }
+        model
+            .indices
+            .set_name(&self.core, &self.resource_pool, name)
+    }
+    /// Names the image of `texture_id` in graphics debuggers
+    pub fn set_texture_name(&self, texture_id: &TextureID, name: &str) -> Result<()> {
+        self.textures
+            .get(texture_id.buffer_index)
+            .unwrap()
+            .get()
+            .set_name(&self.core, name)
+    }
+    /// Names the attachments of `framebuffer_id` in graphics debuggers
+    pub fn set_framebuffer_name(
+        &mut self,
+        framebuffer_id: &FramebufferID,
+        name: &str,
+    ) -> Result<()> {
+        self.framebuffer_arena
+            .get_mut(framebuffer_id.buffer_index)
+            .unwrap()
+            .get_mut()
+            .set_name(&self.core, name)
+    }
     /// Lazily frees textures once the texture is no longer in use
     pub fn free_texture(&mut self, tex: MeshTexture) -> Result<()> {
         self.to_free_textures.insert(tex);
@@ -289,9 +423,191 @@ impl Backend {
                 panic!("invalid state texture not in free list")
             }
         }
+        let freed_buffers = self
+            .to_free_buffers
+            .iter()
+            .filter(|id| {
+                let buffer = self.buffers.get(id.buffer_index).unwrap();
+                buffer.refrences() == 0
+                    && (0..buffer.get().num_copies()).all(|copy| {
+                        !self
+                            .renderpass
+                            .is_resource_used(&ResourceId::Buffer(id.buffer_index, copy))
+                    })
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        for id in freed_buffers {
+            self.to_free_buffers.remove(&id);
+            self.buffers
+                .remove(id.buffer_index)
+                .unwrap()
+                .drain()
+                .free(&mut self.core, &mut self.resource_pool)?;
+        }
+        Ok(())
+    }
+    /// Builds a buffer with one copy per frame that can be in flight plus the copy being
+    /// recorded, so updates rarely need a new copy
+    pub fn build_buffer(&mut self, kind: BufferKind, data: &[u8]) -> Result<BufferID> {
+        if data.is_empty() {
+            return Err(anyhow!("buffers can not be empty"));
+        }
+        let copies = self.core.num_images()? + 1;
+        let buffer = self
+            .resource_pool
+            .allocate_buffer(&mut self.core, kind, data, copies)?;
+        Ok(BufferID {
+            buffer_index: self.buffers.insert(RefCounter::new(buffer, 0)),
+        })
+    }
+    /// Writes to a copy of the buffer unused by renderpasses, adding a copy if every
+    /// copy is in use
+    pub fn update_buffer(&mut self, id: &BufferID, data: &[u8]) -> Result<()> {
+        self.flush_draws()?;
+        let buffer = self.buffers.get(id.buffer_index).unwrap().get();
+        if data.len() > buffer.size {
+            return Err(anyhow!(
+                "update of {} bytes does not fit in buffer of {} bytes",
+                data.len(),
+                buffer.size
+            ));
+        }
+        let copy = self.unused_buffer_copy(id)?;
+        self.buffers
+            .get_mut(id.buffer_index)
+            .unwrap()
+            .get_mut()
+            .update(copy, data);
+        Ok(())
+    }
+    /// Copy of the buffer unused by renderpasses, adds a copy if every copy is in use
+    fn unused_buffer_copy(&mut self, id: &BufferID) -> Result<usize> {
+        let buffer = self.buffers.get(id.buffer_index).unwrap().get();
+        let num_copies = buffer.num_copies();
+        let free_copy = (1..=num_copies)
+            .map(|offset| (buffer.current + offset) % num_copies)
+            .find(|copy| {
+                !self
+                    .renderpass
+                    .is_resource_used(&ResourceId::Buffer(id.buffer_index, *copy))
+            });
+        match free_copy {
+            Some(copy) => Ok(copy),
+            None => self.resource_pool.add_buffer_copy(
+                &mut self.core,
+                self.buffers.get_mut(id.buffer_index).unwrap().get_mut(),
+            ),
+        }
+    }
+    /// Latest contents of the buffer
+    pub fn read_buffer(&self, id: &BufferID) -> Result<Vec<u8>> {
+        Ok(self.buffers.get(id.buffer_index).unwrap().get().read())
+    }
+    /// Lazily frees buffer once it is unbound and no longer in use
+    pub fn free_buffer(&mut self, id: BufferID) -> Result<()> {
+        self.to_free_buffers.insert(id);
+        Ok(())
+    }
+    pub fn bind_shader_buffer(&mut self, shader: &str, name: &str, id: BufferID) -> Result<()> {
+        self.flush_draws()?;
+        let shader_desc = self.shaders.get(shader).ok_or_else(|| {
+            anyhow!(
+                "{}",
+                RenderError::ShaderNotFound {
+                    shader: shader.to_string()
+                }
+            )
+        })?;
+        if !shader_desc.buffers.iter().any(|buffer| buffer.name == name) {
+            return Err(anyhow!(
+                "{}",
+                RenderError::BufferNotFound {
+                    shader: shader.to_string(),
+                    buffer: name.to_string()
+                }
+            ));
+        }
+        self.buffers
+            .get_mut(id.buffer_index)
+            .unwrap()
+            .incr_refrence();
+        let old = self
+            .shader_buffers
+            .entry(shader.to_string())
+            .or_default()
+            .insert(name.to_string(), id);
+        if let Some(old) = old {
+            self.buffers
+                .get_mut(old.buffer_index)
+                .unwrap()
+                .decr_refrence();
+        }
         Ok(())
     }
-    pub fn build_framebuffer(&mut self, resolution: Vector2<u32>) -> Result<FramebufferID> {
+    pub fn bind_mesh_buffer(&mut self, mesh_id: &MeshID, name: &str, id: BufferID) -> Result<()> {
+        self.flush_draws()?;
+        self.buffers
+            .get_mut(id.buffer_index)
+            .unwrap()
+            .incr_refrence();
+        let old = self
+            .models
+            .get_mut(mesh_id.buffer_index)
+            .unwrap()
+            .buffers
+            .insert(name.to_string(), id);
+        if let Some(old) = old {
+            self.buffers
+                .get_mut(old.buffer_index)
+                .unwrap()
+                .decr_refrence();
+        }
+        Ok(())
+    }
+    /// Buffers read by the bound shader when drawing mesh, in descriptor set order
+    fn mesh_buffers(&self, mesh: &Model) -> Result<Vec<BufferID>> {
+        let shader = &self.shaders[&self.bound_shader];
+        shader
+            .buffers
+            .iter()
+            .map(|binding| {
+                let id = mesh
+                    .buffers
+                    .get(&binding.name)
+                    .or_else(|| {
+                        self.shader_buffers
+                            .get(&self.bound_shader)
+                            .and_then(|buffers| buffers.get(&binding.name))
+                    })
+                    .copied()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "{}",
+                            RenderError::BufferNotBound {
+                                shader: self.bound_shader.clone(),
+                                buffer: binding.name.clone()
+                            }
+                        )
+                    })?;
+                let buffer = self.buffers.get(id.buffer_index).unwrap().get();
+                if buffer.kind != binding.kind || buffer.size < binding.min_size as usize {
+                    return Err(anyhow!(
+                        "{}",
+                        RenderError::BufferMismatch {
+                            shader: self.bound_shader.clone(),
+                            buffer: binding.name.clone(),
+                            kind: binding.kind,
+                            min_size: binding.min_size
+                        }
+                    ));
+                }
+                Ok(id)
+            })
+            .collect()
+    }
+    pub fn build_framebuffer(&mut self, desc: FramebufferDesc) -> Result<FramebufferID> {
+        let sample_count = self.core.sample_count(desc.sample_count);
         let framebuffer = FramebufferID {
             buffer_index: self.framebuffer_arena.insert(RefCounter::new(
                 AttachableFramebuffer::new(
@@ -299,7 +615,9 @@ impl Backend {
                     &mut self.command_pool,
                     &mut self.resource_pool,
                     &self.main_shader,
-                    resolution,
+                    &self.debug_shader,
+                    desc.resolution,
+                    sample_count,
                 )?,
                 0,
             )),
@@ -310,6 +628,7 @@ impl Backend {
         Ok(framebuffer)
     }
     pub fn bind_framebuffer(&mut self, framebuffer_id: &BoundFramebuffer) -> Result<()> {
+        self.flush_draws()?;
         let framebuffer = match *framebuffer_id {
             BoundFramebuffer::ScreenFramebuffer => (&self.main_framebuffer),
             BoundFramebuffer::UserFramebuffer(id) => self
@@ -328,6 +647,8 @@ impl Backend {
         Ok(())
     }
     pub fn bind_shader(&mut self, framebuffer: &BoundFramebuffer, shader: &str) -> Result<()> {
+        self.flush_draws()?;
+        let shader_name = shader;
         let shader = if let Some(s) = self.shaders.get(shader) {
             s
         } else {
@@ -349,9 +670,18 @@ impl Backend {
                     .framebuffer
             }
         };
+        framebuffer.shader_name = Some(shader_name.to_string());
         framebuffer.rebuild_framebuffer(&mut self.core, &self.resource_pool, shader)?;
+        self.bound_shader = shader_name.to_string();
         Ok(())
     }
+    /// Checks that mesh can be read by the bound shader, pipelines take vertex formats, offsets
+    /// and strides from the mesh's layout
+    #[cfg(feature = "state_validation")]
+    pub fn validate_mesh(&self, mesh: &MeshAsset) -> std::result::Result<(), MeshError> {
+        let shader = &self.shaders[&self.bound_shader];
+        mesh.validate_vertex_input(&self.bound_shader, &shader.vertex_input)
+    }
     /// Frees mesh data, can be called at any time as freeing waits untill data is unused by
     /// renderpasses
     pub fn free_mesh(&mut self, mesh_id: &MeshID) -> Result<()> {
@@ -372,7 +702,71 @@ impl Backend {
     }
 
     pub fn draw_mesh(&mut self, push: Vec<u8>, mesh_id: &MeshID) -> Result<()> {
+        self.flush_draws()?;
+        self.draw_model(push, mesh_id, false)
+    }
+    /// Queues a draw to be recorded sorted with the other queued draws. The queue is recorded
+    /// before anything that depends on draw order, such as binding a framebuffer or shader,
+    /// drawing unsorted, updating buffers or finishing the frame.
+    pub fn draw_mesh_sorted(
+        &mut self,
+        push: Vec<u8>,
+        mesh_id: &MeshID,
+        queue: DrawQueue,
+        depth: f32,
+    ) -> Result<()> {
         let mesh = self.models.get(mesh_id.buffer_index).unwrap();
+        let mut pipeline = DefaultHasher::new();
+        (&mesh.stream_layouts, mesh.topology).hash(&mut pipeline);
+        let state = DrawState {
+            pipeline: pipeline.finish(),
+            texture: mesh.texture.sort_key(),
+        };
+        self.draw_list.push(state, queue, depth, (push, *mesh_id));
+        Ok(())
+    }
+    /// Records the queued sorted draws
+    fn flush_draws(&mut self) -> Result<()> {
+        if self.draw_list.is_empty() {
+            return Ok(());
+        }
+        for (push, mesh_id) in self.draw_list.sorted() {
+            self.draw_model(push, &mesh_id, false)?;
+        }
+        Ok(())
+    }
+    /// Stats of the sorted draws of the last finished frame
+    pub fn draw_stats(&self) -> DrawStats {
+        self.draw_stats
+    }
+    /// Draws a line mesh with the debug pipeline of the bound framebuffer. The mesh is only
+    /// used for one frame and is freed once the frame is done.
+    pub fn draw_debug(&mut self, mesh: MeshAsset, push: Vec<u8>) -> Result<()> {
+        self.flush_draws()?;
+        let texture = match self.debug_texture {
+            Some(texture) => texture,
+            None => {
+                let white = RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+                let texture = self.allocate_texture(&white)?;
+                self.debug_texture = Some(texture);
+                texture
+            }
+        };
+        let vertex_input = self.debug_shader.vertex_input.clone();
+        let mesh_id =
+            self.build_model(mesh, &vertex_input, MeshTexture::RegularTexture(texture))?;
+        let draw = self.draw_model(push, &mesh_id, true);
+        self.free_mesh(&mesh_id)?;
+        draw
+    }
+    fn draw_model(&mut self, push: Vec<u8>, mesh_id: &MeshID, debug: bool) -> Result<()> {
+        let mesh = self.models.get(mesh_id.buffer_index).unwrap();
+        // the debug shader reads no buffers
+        let buffer_ids = if debug {
+            vec![]
+        } else {
+            self.mesh_buffers(mesh)?
+        };
         let descriptor_set = match mesh.texture {
             MeshTexture::RegularTexture(texture) => self
                 .textures
@@ -398,6 +792,65 @@ impl Backend {
                 }
             }
         };
+        let vertex_buffers = mesh
+            .vertices
+            .iter()
+            .map(|vertices| {
+                (
+                    vertices.binding,
+                    self.resource_pool.geometry_binding(&vertices.range),
+                )
+            })
+            .collect::<Vec<_>>();
+        let index_buffer = self.resource_pool.geometry_binding(&mesh.indices.range);
+        let resource_ids = buffer_ids
+            .iter()
+            .map(|id| {
+                ResourceId::Buffer(
+                    id.buffer_index,
+                    self.buffers.get(id.buffer_index).unwrap().get().current,
+                )
+            })
+            .chain(
+                vertex_buffers
+                    .iter()
+                    .map(|(_binding, vertices)| vertices)
+                    .chain(std::iter::once(&index_buffer))
+                    .map(|geometry| ResourceId::GeometryBlock(geometry.block)),
+            )
+            .collect();
+        let descriptor_set_arr = [
+            descriptor_set.texture_descriptor_set,
+            descriptor_set.sampler_descriptor_set,
+        ]
+        .iter()
+        .copied()
+        .chain(buffer_ids.iter().map(|id| {
+            self.buffers
+                .get(id.buffer_index)
+                .unwrap()
+                .get()
+                .descriptor_set()
+        }))
+        .collect::<Vec<_>>();
+        let framebuffer = match self.bound_framebuffer {
+            BoundFramebuffer::ScreenFramebuffer => &mut self.main_framebuffer,
+            BoundFramebuffer::UserFramebuffer(fb) => {
+                &mut self
+                    .framebuffer_arena
+                    .get_mut(fb.buffer_index)
+                    .unwrap()
+                    .get_mut()
+                    .framebuffer
+            }
+        };
+        let pipeline = if debug {
+            &mut framebuffer.debug_pipeline
+        } else {
+            &mut framebuffer.pipeline
+        };
+        let pipelines =
+            pipeline.draw_pipelines(&mut self.core, &mesh.stream_layouts, mesh.topology)?;
         let render_mesh = RenderMesh {
             push,
             ids: RenderMeshIds {
@@ -411,26 +864,23 @@ impl Backend {
                     }
                 },
             },
-            vertex_buffer: &mesh.vertices,
-            index_buffer: &mesh.indices,
+            index_type: mesh.indices.index_type(),
+            num_indices: mesh.indices.num_indices() as u32,
+            pipelines,
+            buffer_ids: resource_ids,
+            vertex_buffers,
+            index_buffer,
+        };
+        let framebuffer = &*framebuffer;
+        let pipeline = if debug {
+            &framebuffer.debug_pipeline
+        } else {
+            &framebuffer.pipeline
         };
-        let descriptor_set_arr = [
-            descriptor_set.texture_descriptor_set,
-            descriptor_set.sampler_descriptor_set,
-        ];
         self.renderpass.draw_mesh(
             &mut self.core,
-            match self.bound_framebuffer {
-                BoundFramebuffer::ScreenFramebuffer => &self.main_framebuffer,
-                BoundFramebuffer::UserFramebuffer(fb) => {
-                    &self
-                        .framebuffer_arena
-                        .get(fb.buffer_index)
-                        .unwrap()
-                        .get()
-                        .framebuffer
-                }
-            },
+            framebuffer,
+            pipeline,
             &descriptor_set_arr,
             self.screen_dimensions,
             render_mesh,
@@ -438,6 +888,7 @@ impl Backend {
     }
     /// begins rendering of frame
     pub fn begin_render(&mut self) -> Result<()> {
+        self.reload_shaders()?;
         unsafe {
             self.renderpass.begin_frame(
                 &mut self.core,
@@ -456,11 +907,14 @@ impl Backend {
         }
     }
     pub fn finish_render(&mut self) -> Result<()> {
+        self.flush_draws()?;
+        self.draw_stats = self.draw_list.finish_frame();
         //the screen frmebuffer must be bound
         if self.bound_framebuffer != BoundFramebuffer::ScreenFramebuffer {
             self.bind_framebuffer(&BoundFramebuffer::ScreenFramebuffer)?;
         }
 
+        self.resource_pool.flush_uploads(&mut self.core)?;
         let free_data = self.renderpass.submit_draw(&mut self.core)?;
         for id in free_data.iter() {
             match id {
@@ -470,24 +924,40 @@ impl Backend {
                         self.decr_texture_refrences(&tex);
                     }
                     let model = self.models.remove(*id).unwrap();
+                    for buffer in model.buffers.values() {
+                        self.buffers
+                            .get_mut(buffer.buffer_index)
+                            .unwrap()
+                            .decr_refrence();
+                    }
 
                     model
                         .indices
                         .free(&mut self.core, &mut self.resource_pool)?;
-                    model
-                        .vertices
-                        .free(&mut self.core, &mut self.resource_pool)?;
+                    for vertices in model.vertices {
+                        vertices.free(&mut self.core, &mut self.resource_pool)?;
+                    }
                 }
+                ResourceId::GeometryBlock(block) => self
+                    .resource_pool
+                    .free_geometry_block(&mut self.core, *block)?,
                 ResourceId::UserTexture(_) => (),
                 ResourceId::Framebuffer(_) => (),
+                ResourceId::Buffer(_, _) => (),
             }
         }
+        if let Some(block) = self.resource_pool.compact_geometry(&mut self.core)? {
+            self.renderpass
+                .free_resource(ResourceId::GeometryBlock(block));
+        }
         let r = self.renderpass.swap_framebuffer(&mut self.core);
         if let Err(r) = r {
             if r == vk::Result::ERROR_OUT_OF_DATE_KHR {
-                let new_size = self.window.inner_size();
-                let new_size = Vector2::new(new_size.width, new_size.height);
-                self.resize_renderer(new_size)?;
+                if let Some(window) = self.window.as_ref() {
+                    let new_size = window.inner_size();
+                    let new_size = Vector2::new(new_size.width, new_size.height);
+                    self.resize_renderer(new_size)?;
+                }
                 Ok(())
             } else if r == vk::Result::SUBOPTIMAL_KHR {
                 Ok(())
@@ -502,35 +972,91 @@ impl Backend {
         if new_size == self.screen_dimensions {
             Ok(())
         } else {
-            self.renderpass.wait_idle(&mut self.core);
-            self.core.update_swapchain_resolution(new_size)?;
-            self.main_framebuffer
-                .free(&mut self.core, &mut self.resource_pool)?;
-            let texture_attachment = TextureAttachment::new(
-                &mut self.core,
-                &mut self.command_pool,
-                &mut self.resource_pool,
-                AttachmentType::Swapchain,
-                new_size,
-            )?;
+            self.rebuild_swapchain(new_size)
+        }
+    }
+    /// Switches present mode, the swapchain is rebuilt unless running headless
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()> {
+        if self.core.is_headless() {
+            return Ok(());
+        }
+        self.core.set_present_mode(present_mode)?;
+        self.rebuild_swapchain(self.screen_dimensions)
+    }
+    /// Rebuilds the swapchain along with the screen framebuffer and renderpass
+    fn rebuild_swapchain(&mut self, new_size: Vector2<u32>) -> Result<()> {
+        self.renderpass.wait_idle(&mut self.core);
+        self.main_framebuffer
+            .free(&mut self.core, &mut self.resource_pool)?;
+        self.core.update_swapchain_resolution(new_size)?;
+        let texture_attachment = TextureAttachment::new(
+            &mut self.core,
+            &mut self.command_pool,
+            &mut self.resource_pool,
+            AttachmentType::Swapchain,
+            new_size,
+            self.screen_sample_count,
+        )?;
 
-            self.main_framebuffer = Framebuffer::new(
-                &mut self.core,
-                &self.main_shader,
-                &self.resource_pool,
-                texture_attachment,
-                new_size,
-                PipelineType::Present,
-            )?;
-            self.renderpass.free(&mut self.core);
-            self.renderpass = RenderPass::new(
-                &mut self.core,
-                &self.command_pool,
-                &self.main_framebuffer.framebuffer_target,
-            );
-            self.screen_dimensions = new_size;
-            Ok(())
+        let pipeline_type = Self::screen_pipeline_type(&self.core);
+        self.main_framebuffer = Framebuffer::new(
+            &mut self.core,
+            &self.main_shader,
+            &self.debug_shader,
+            &self.resource_pool,
+            texture_attachment,
+            new_size,
+            pipeline_type,
+        )?;
+        self.main_framebuffer.set_name(&self.core, SCREEN_NAME)?;
+        self.renderpass.free(&mut self.core);
+        self.renderpass = RenderPass::new(
+            &mut self.core,
+            &self.command_pool,
+            &self.main_framebuffer.framebuffer_target,
+            self.max_frames_in_flight,
+        );
+        self.screen_dimensions = new_size;
+        Ok(())
+    }
+    /// Offscreen images are left in the general layout so they can be read back
+    fn screen_pipeline_type(core: &Core) -> PipelineType {
+        if core.is_headless() {
+            PipelineType::OffScreen
+        } else {
+            PipelineType::Present
+        }
+    }
+    /// Reads the last frame sent to the screen, only available when headless
+    pub fn read_screen(&mut self) -> Result<RgbaImage> {
+        if !self.core.is_headless() {
+            return Err(anyhow!("screen can only be read when running headless"));
         }
+        let image_index = self
+            .renderpass
+            .last_image_index()
+            .ok_or_else(|| anyhow!("no frame has been rendered"))?;
+        self.renderpass.wait_idle(&mut self.core);
+        let image = self
+            .main_framebuffer
+            .texture_attachment
+            .color_buffer
+            .present_images[image_index]
+            .0;
+        self.resource_pool.read_image(
+            &mut self.core,
+            &mut self.command_pool,
+            image,
+            self.screen_dimensions,
+        )
+    }
+    pub fn push_debug_group(&mut self, label: &str) -> Result<()> {
+        self.flush_draws()?;
+        self.renderpass.push_debug_group(&mut self.core, label)
+    }
+    pub fn pop_debug_group(&mut self) -> Result<()> {
+        self.flush_draws()?;
+        self.renderpass.pop_debug_group(&mut self.core)
     }
     pub fn get_screen_size(&self) -> Vector2<u32> {
         self.screen_dimensions
@@ -541,6 +1067,231 @@ impl Backend {
         self.shaders.insert(shader_name.to_string(), shader.into());
         Ok(())
     }
+    /// Compiles and loads the WGSL project in `project_dir`, it is recompiled when
+    /// files in the project change
+    pub fn watch_shader(&mut self, project_dir: &Path, shader_name: &str) -> Result<()> {
+        let shader = compile_shader_project(project_dir).with_context(|| {
+            format!(
+                "failed to compile shader {} at {}",
+                shader_name,
+                project_dir.display()
+            )
+        })?;
+        self.shaders.insert(shader_name.to_string(), shader);
+        self.shader_watcher.watch(shader_name, project_dir);
+        Ok(())
+    }
+    /// Recompiles changed watched shaders and rebuilds the pipelines of framebuffers
+    /// bound to them. Shaders that fail to compile keep their previous pipeline.
+    fn reload_shaders(&mut self) -> Result<()> {
+        let mut reloaded = HashSet::new();
+        for (name, project_dir) in self.shader_watcher.changed() {
+            match compile_shader_project(&project_dir) {
+                Ok(shader) => {
+                    println!("reloaded shader {}", name);
+                    self.shaders.insert(name.clone(), shader);
+                    reloaded.insert(name);
+                }
+                Err(e) => println!("failed to reload shader {}: {:?}", name, e),
+            }
+        }
+        if reloaded.is_empty() {
+            return Ok(());
+        }
+        self.renderpass.wait_idle(&mut self.core);
+        let framebuffers = std::iter::once(&mut self.main_framebuffer).chain(
+            self.framebuffer_arena
+                .iter_mut()
+                .map(|(_idx, framebuffer)| &mut framebuffer.get_mut().framebuffer),
+        );
+        for framebuffer in framebuffers {
+            if let Some(name) = framebuffer
+                .shader_name
+                .clone()
+                .filter(|name| reloaded.contains(name))
+            {
+                framebuffer.rebuild_framebuffer(
+                    &mut self.core,
+                    &self.resource_pool,
+                    &self.shaders[&name],
+                )?;
+            }
+        }
+        Ok(())
+    }
+    /// Loads a compute shader built by ass, replacing an earlier compute shader of the
+    /// same name
+    pub fn load_compute_shader(&mut self, shader_data: &str, shader_name: &str) -> Result<()> {
+        let shader = ass_vk::ComputeShader::from_json_str(shader_data)
+            .with_context(|| format!("failed to load compute shader {}", shader_name))?;
+        let pipeline = ComputePipeline::new(
+            &self.core,
+            &shader,
+            self.resource_pool.get_storage_descriptor_layout(),
+        )?;
+        pipeline.set_name(&self.core, shader_name)?;
+        if let Some(mut old) = self
+            .compute_pipelines
+            .insert(shader_name.to_string(), pipeline)
+        {
+            old.free(&mut self.core);
+        }
+        Ok(())
+    }
+    /// Buffers bound to the storage buffers of compute shader `shader` in descriptor set
+    /// order, along with whether the shader writes to them
+    fn compute_buffers(
+        &self,
+        shader: &str,
+        bindings: &[(&str, BufferID)],
+    ) -> Result<Vec<(BufferID, bool)>> {
+        let pipeline = self.compute_pipelines.get(shader).ok_or_else(|| {
+            anyhow!(
+                "{}",
+                RenderError::ShaderNotFound {
+                    shader: shader.to_string()
+                }
+            )
+        })?;
+        for (i, (name, id)) in bindings.iter().enumerate() {
+            if !pipeline.buffers.iter().any(|buffer| buffer.name == *name) {
+                return Err(anyhow!(
+                    "{}",
+                    RenderError::BufferNotFound {
+                        shader: shader.to_string(),
+                        buffer: name.to_string()
+                    }
+                ));
+            }
+            if bindings[..i].iter().any(|(_name, other)| other == id) {
+                return Err(anyhow!(
+                    "buffer bound to {} is bound to shader {} more than once",
+                    name,
+                    shader
+                ));
+            }
+        }
+        pipeline
+            .buffers
+            .iter()
+            .map(|binding| {
+                let id = bindings
+                    .iter()
+                    .find(|(name, _id)| *name == binding.name)
+                    .map(|(_name, id)| *id)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "{}",
+                            RenderError::BufferNotBound {
+                                shader: shader.to_string(),
+                                buffer: binding.name.clone()
+                            }
+                        )
+                    })?;
+                let buffer = self.buffers.get(id.buffer_index).unwrap().get();
+                if buffer.kind != BufferKind::Storage || buffer.size < binding.min_size as usize {
+                    return Err(anyhow!(
+                        "{}",
+                        RenderError::BufferMismatch {
+                            shader: shader.to_string(),
+                            buffer: binding.name.clone(),
+                            kind: BufferKind::Storage,
+                            min_size: binding.min_size
+                        }
+                    ));
+                }
+                Ok((id, binding.writable))
+            })
+            .collect()
+    }
+    /// Runs `groups` workgroups of compute shader `shader` and waits for them to finish.
+    /// Written buffers get their results in a copy unused by renderpasses which becomes
+    /// the latest data, so draws recorded before the dispatch keep reading the old data.
+    pub fn dispatch(
+        &mut self,
+        shader: &str,
+        groups: Vector3<u32>,
+        bindings: &[(&str, BufferID)],
+    ) -> Result<()> {
+        self.flush_draws()?;
+        let buffers = self.compute_buffers(shader, bindings)?;
+        let max_groups = unsafe {
+            self.core
+                .instance
+                .get_physical_device_properties(self.core.physical_device)
+        }
+        .limits
+        .max_compute_work_group_count;
+        if groups.iter().zip(max_groups.iter()).any(|(g, max)| g > max) {
+            return Err(anyhow!(
+                "dispatch of {:?} workgroups is larger than the limit of {:?}",
+                groups,
+                max_groups
+            ));
+        }
+        let mut descriptor_sets = vec![];
+        for (id, writable) in buffers {
+            if writable {
+                // the shader may write only part of the buffer so the copy starts
+                // with the latest data
+                let copy = self.unused_buffer_copy(&id)?;
+                self.buffers
+                    .get_mut(id.buffer_index)
+                    .unwrap()
+                    .get_mut()
+                    .update(copy, &[]);
+            }
+            descriptor_sets.push(
+                self.buffers
+                    .get(id.buffer_index)
+                    .unwrap()
+                    .get()
+                    .descriptor_set(),
+            );
+        }
+        let pipeline = &self.compute_pipelines[shader];
+        unsafe {
+            let command_buffer = self.command_pool.create_onetime_buffer(&mut self.core);
+            let device = &command_buffer.core.device;
+            device.cmd_bind_pipeline(
+                command_buffer.command_buffer[0],
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer.command_buffer[0],
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline_layout,
+                0,
+                &descriptor_sets,
+                &[],
+            );
+            device.cmd_dispatch(
+                command_buffer.command_buffer[0],
+                groups.x,
+                groups.y,
+                groups.z,
+            );
+            // makes the results visible to draws and later dispatches and to reads
+            // from the cpu
+            let barrier = vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::HOST_READ);
+            device.cmd_pipeline_barrier(
+                command_buffer.command_buffer[0],
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::VERTEX_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::COMPUTE_SHADER
+                    | vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[*barrier],
+                &[],
+                &[],
+            );
+        }
+        Ok(())
+    }
     /// Validates state, panics if state is invalid
     /// Warning: may be slow
     pub fn check_state(&mut self) {
@@ -570,12 +1321,16 @@ impl Backend {
 impl Drop for Backend {
     fn drop(&mut self) {
         self.renderpass.wait_idle(&mut self.core);
+        self.resource_pool
+            .wait_uploads(&mut self.core)
+            .expect("failed to wait for uploads");
         unsafe {
             for (_idx, model) in self.models.drain() {
-                model
-                    .vertices
-                    .free(&mut self.core, &mut self.resource_pool)
-                    .expect("failed to free vertex buffer");
+                for vertices in model.vertices {
+                    vertices
+                        .free(&mut self.core, &mut self.resource_pool)
+                        .expect("failed to free vertex buffer");
+                }
                 model
                     .indices
                     .free(&mut self.core, &mut self.resource_pool)
@@ -586,11 +1341,20 @@ impl Drop for Backend {
                     .free(&mut self.core, &mut self.resource_pool)
                     .expect("failed to free textures");
             }
+            for (_idx, buffer) in self.buffers.drain() {
+                buffer
+                    .drain()
+                    .free(&mut self.core, &mut self.resource_pool)
+                    .expect("failed to free buffer");
+            }
             for (_idx, mut fb) in self.framebuffer_arena.drain() {
                 fb.get_mut()
                     .free(&mut self.core, &mut self.resource_pool)
                     .expect("failed to free");
             }
+            for (_name, mut pipeline) in self.compute_pipelines.drain() {
+                pipeline.free(&mut self.core);
+            }
             self.renderpass.free(&mut self.core);
 
             self.main_framebuffer
@@ -28,6 +28,7 @@ impl TextureAttachment {
         resource_pool: &mut ResourcePool,
         attachment_type: AttachmentType,
         resolution: Vector2<u32>,
+        sample_count: vk::SampleCountFlags,
     ) -> Result<Self> {
         let color_buffer = ColorBuffer::new(
             core,
@@ -35,13 +36,19 @@ impl TextureAttachment {
             resource_pool,
             attachment_type,
             Some(resolution),
+            sample_count,
         )?;
-        let depth_buffer = DepthBuffer::new(core, command_pool, resource_pool, resolution)?;
+        let depth_buffer =
+            DepthBuffer::new(core, command_pool, resource_pool, resolution, sample_count)?;
         Ok(Self {
             color_buffer,
             depth_buffer,
         })
     }
+    pub fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        self.color_buffer.set_name(core, name)?;
+        self.depth_buffer.set_name(core, name)
+    }
     pub fn free(&mut self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
         self.depth_buffer.free(core, resource_pool)?;
         self.color_buffer.free(core, resource_pool)
@@ -51,13 +58,20 @@ pub struct Framebuffer {
     pub framebuffer_target: FrameBufferTarget,
     pub resolution: Vector2<u32>,
     pub pipeline: GraphicsPipeline,
+    /// draws debug lines, kept when the bound shader changes
+    pub debug_pipeline: GraphicsPipeline,
     pipeline_type: PipelineType,
     pub texture_attachment: TextureAttachment,
+    /// shader bound with `Backend::bind_shader`, None while using the built in shader
+    pub shader_name: Option<String>,
+    /// name shown in graphics debuggers, set with `set_name`
+    debug_name: Option<String>,
 }
 impl Framebuffer {
     pub fn new(
         core: &mut Core,
         shader: &ShaderDescription,
+        debug_shader: &ShaderDescription,
         resource_pool: &ResourcePool,
         texture_attachment: TextureAttachment,
         resolution: Vector2<u32>,
@@ -66,9 +80,19 @@ impl Framebuffer {
         let mut pipeline = GraphicsPipeline::new(
             core,
             shader,
-            &resource_pool.get_descriptor_set_layouts(),
+            &resource_pool.get_descriptor_set_layouts(shader),
+            resolution,
+            &texture_attachment.depth_buffer,
+            texture_attachment.color_buffer.sample_count,
+            pipeline_type,
+        );
+        let debug_pipeline = GraphicsPipeline::new(
+            core,
+            debug_shader,
+            &resource_pool.get_descriptor_set_layouts(debug_shader),
             resolution,
             &texture_attachment.depth_buffer,
+            texture_attachment.color_buffer.sample_count,
             pipeline_type,
         );
         let framebuffer_target =
@@ -77,10 +101,23 @@ impl Framebuffer {
             texture_attachment,
             resolution,
             pipeline,
+            debug_pipeline,
             framebuffer_target,
             pipeline_type,
+            shader_name: None,
+            debug_name: None,
         })
     }
+    /// Names the attachments and pipelines in graphics debuggers, the name is kept when
+    /// the framebuffer is rebuilt
+    pub fn set_name(&mut self, core: &Core, name: &str) -> Result<()> {
+        self.texture_attachment.set_name(core, name)?;
+        self.framebuffer_target.set_name(core, name)?;
+        self.debug_pipeline
+            .set_name(core, &format!("{} debug lines", name))?;
+        self.debug_name = Some(name.to_string());
+        Ok(())
+    }
     pub fn rebuild_framebuffer(
         &mut self,
         core: &mut Core,
@@ -92,9 +129,10 @@ impl Framebuffer {
         self.pipeline = GraphicsPipeline::new(
             core,
             shader,
-            &resource_pool.get_descriptor_set_layouts(),
+            &resource_pool.get_descriptor_set_layouts(shader),
             self.resolution,
             &self.texture_attachment.depth_buffer,
+            self.texture_attachment.color_buffer.sample_count,
             self.pipeline_type,
         );
         self.framebuffer_target = FrameBufferTarget::new(
@@ -103,12 +141,19 @@ impl Framebuffer {
             &self.texture_attachment,
             self.resolution,
         );
+        if let Some(name) = self.debug_name.as_ref() {
+            self.framebuffer_target.set_name(core, name)?;
+        }
+        if let Some(shader_name) = self.shader_name.as_ref() {
+            self.pipeline.set_name(core, shader_name)?;
+        }
         Ok(())
     }
     pub fn free(&mut self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
         self.framebuffer_target.free(core);
         self.texture_attachment.free(core, resource_pool)?;
         self.pipeline.free(core);
+        self.debug_pipeline.free(core);
         Ok(())
     }
 }
@@ -124,7 +169,9 @@ impl AttachableFramebuffer {
         command_pool: &mut CommandPool,
         resource_pool: &mut ResourcePool,
         shader: &ShaderDescription,
+        debug_shader: &ShaderDescription,
         resolution: Vector2<u32>,
+        sample_count: vk::SampleCountFlags,
     ) -> Result<Self> {
         let texture_attachment = TextureAttachment::new(
             core,
@@ -132,10 +179,12 @@ impl AttachableFramebuffer {
             resource_pool,
             AttachmentType::UserFramebuffer,
             resolution,
+            sample_count,
         )?;
         let framebuffer = Framebuffer::new(
             core,
             shader,
+            debug_shader,
             resource_pool,
             texture_attachment,
             resolution,
@@ -182,6 +231,10 @@ impl AttachableFramebuffer {
             descriptor_sets,
         })
     }
+    pub fn set_name(&mut self, core: &Core, name: &str) -> Result<()> {
+        self.framebuffer.set_name(core, name)?;
+        core.set_object_name(self.sampler, name)
+    }
     pub fn get_framebuffer(&self) -> &Framebuffer {
         &self.framebuffer
     }
@@ -8,6 +8,11 @@ use nalgebra::Vector2;
 pub struct ColorBuffer {
     pub present_images: Vec<(vk::Image, Option<Allocation>)>,
     pub present_image_views: Vec<vk::ImageView>,
+    /// multisampled images drawn to and resolved into the present image of the
+    /// same index, empty without multisampling
+    msaa_images: Vec<(vk::Image, Allocation)>,
+    pub msaa_image_views: Vec<vk::ImageView>,
+    pub sample_count: vk::SampleCountFlags,
     attachment_type: AttachmentType,
 }
 #[derive(Clone, Copy, PartialEq)]
@@ -22,85 +27,154 @@ impl ColorBuffer {
         self.present_images.len()
     }
     //builds new color buffer, dimensions are ignored if the attachment is for the swapchain
+    //unless running headless, where offscreen images stand in for the swapchain, or
+    //multisampling, where they size the multisampled images
     pub fn new(
         core: &mut Core,
         command_pool: &mut CommandPool,
         resource_pool: &mut ResourcePool,
         attachment_type: AttachmentType,
         dimensions: Option<Vector2<u32>>,
+        sample_count: vk::SampleCountFlags,
     ) -> Result<Self> {
-        let present_images: Vec<(vk::Image, Option<Allocation>)> = match attachment_type {
-            AttachmentType::Swapchain => {
-                unsafe { core.swapchain_loader.get_swapchain_images(core.swapchain)? }
-                    .iter()
-                    .map(|image| (*image, None))
-                    .collect()
-            }
-            AttachmentType::UserFramebuffer => {
-                let len =
-                    unsafe { core.swapchain_loader.get_swapchain_images(core.swapchain) }?.len();
-                (0..len)
-                    .map(|_| {
-                        let (image, suballoc) = resource_pool
-                            .new_image(
+        let present_images: Vec<(vk::Image, Option<Allocation>)> =
+            match (attachment_type, core.presentation.as_ref()) {
+                (AttachmentType::Swapchain, Some(presentation)) => unsafe {
+                    presentation
+                        .swapchain_loader
+                        .get_swapchain_images(presentation.swapchain)?
+                }
+                .iter()
+                .map(|image| (*image, None))
+                .collect(),
+                _ => {
+                    let len = core.num_images()?;
+                    (0..len)
+                        .map(|_| {
+                            let (image, suballoc) = resource_pool
+                                .new_image(
+                                    core,
+                                    core.surface_format.format,
+                                    vk::ImageUsageFlags::COLOR_ATTACHMENT
+                                        | vk::ImageUsageFlags::SAMPLED
+                                        | vk::ImageUsageFlags::TRANSFER_SRC,
+                                    dimensions.expect("needs dimensions"),
+                                )
+                                .expect("failed to allocate image");
+                            TextureAllocation::transition_image_layout(
                                 core,
-                                core.surface_format.format,
-                                vk::ImageUsageFlags::COLOR_ATTACHMENT
-                                    | vk::ImageUsageFlags::SAMPLED,
-                                dimensions.expect("needs dimensions"),
-                            )
-                            .expect("failed to allocate image");
-                        TextureAllocation::transition_image_layout(
-                            core,
-                            command_pool,
-                            &image,
-                            vk::ImageAspectFlags::COLOR,
-                            vk::ImageLayout::UNDEFINED,
-                            vk::ImageLayout::GENERAL,
-                        );
-                        (image, Some(suballoc))
-                    })
-                    .collect()
-            }
-        };
+                                command_pool,
+                                &image,
+                                vk::ImageAspectFlags::COLOR,
+                                vk::ImageLayout::UNDEFINED,
+                                vk::ImageLayout::GENERAL,
+                            );
+                            (image, Some(suballoc))
+                        })
+                        .collect()
+                }
+            };
         let present_image_views: Vec<vk::ImageView> = present_images
             .iter()
-            .map(|(image, _suballoc)| {
-                let create_image_view_info = vk::ImageViewCreateInfo::builder()
-                    .view_type(vk::ImageViewType::TYPE_2D)
-                    .format(core.surface_format.format)
-                    .components(vk::ComponentMapping {
-                        r: vk::ComponentSwizzle::R,
-                        g: vk::ComponentSwizzle::G,
-                        b: vk::ComponentSwizzle::B,
-                        a: vk::ComponentSwizzle::A,
-                    })
-                    .subresource_range(vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    })
-                    .image(*image);
-                unsafe { core.device.create_image_view(&create_image_view_info, None) }
-                    .expect("failed to create image")
-            })
+            .map(|(image, _suballoc)| Self::create_view(core, *image))
+            .collect();
+        let msaa_images = if sample_count == vk::SampleCountFlags::TYPE_1 {
+            vec![]
+        } else {
+            (0..present_images.len())
+                .map(|_| {
+                    let (image, allocation) = resource_pool.new_multisampled_image(
+                        core,
+                        core.surface_format.format,
+                        vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                        dimensions.expect("needs dimensions"),
+                        sample_count,
+                    )?;
+                    TextureAllocation::transition_image_layout(
+                        core,
+                        command_pool,
+                        &image,
+                        vk::ImageAspectFlags::COLOR,
+                        vk::ImageLayout::UNDEFINED,
+                        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    );
+                    Ok((image, allocation))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        let msaa_image_views = msaa_images
+            .iter()
+            .map(|(image, _allocation)| Self::create_view(core, *image))
             .collect();
         Ok(Self {
             present_images,
             present_image_views,
+            msaa_images,
+            msaa_image_views,
+            sample_count,
             attachment_type,
         })
     }
+    fn create_view(core: &Core, image: vk::Image) -> vk::ImageView {
+        let create_image_view_info = vk::ImageViewCreateInfo::builder()
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(core.surface_format.format)
+            .components(vk::ComponentMapping {
+                r: vk::ComponentSwizzle::R,
+                g: vk::ComponentSwizzle::G,
+                b: vk::ComponentSwizzle::B,
+                a: vk::ComponentSwizzle::A,
+            })
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image(image);
+        unsafe { core.device.create_image_view(&create_image_view_info, None) }
+            .expect("failed to create image")
+    }
+    pub fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        for (i, ((image, _), view)) in self
+            .present_images
+            .iter()
+            .zip(self.present_image_views.iter())
+            .enumerate()
+        {
+            let name = format!("{} color {}", name, i);
+            core.set_object_name(*image, &name)?;
+            core.set_object_name(*view, &name)?;
+        }
+        for (i, ((image, _), view)) in self
+            .msaa_images
+            .iter()
+            .zip(self.msaa_image_views.iter())
+            .enumerate()
+        {
+            let name = format!("{} msaa color {}", name, i);
+            core.set_object_name(*image, &name)?;
+            core.set_object_name(*view, &name)?;
+        }
+        Ok(())
+    }
     /// clears resources, warning once called object is in invalid state
     pub fn free(&mut self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
         unsafe {
             core.device.device_wait_idle().expect("failed to wait");
-            for view in self.present_image_views.iter() {
+            for view in self
+                .present_image_views
+                .iter()
+                .chain(self.msaa_image_views.iter())
+            {
                 core.device.destroy_image_view(*view, None);
             }
-            if self.attachment_type == AttachmentType::UserFramebuffer {
+            for (image, allocation) in self.msaa_images.drain(..) {
+                core.device.destroy_image(image, None);
+                resource_pool.free_allocation(allocation)?;
+            }
+            if self.attachment_type == AttachmentType::UserFramebuffer || core.is_headless() {
                 for (image, suballoc) in self.present_images.drain(..) {
                     if let Some(alloc) = suballoc {
                         core.device.destroy_image(image, None);
@@ -8,6 +8,7 @@ pub struct DepthBuffer {
     allocation: Option<Allocation>,
     pub view: vk::ImageView,
     depth_format: vk::Format,
+    sample_count: vk::SampleCountFlags,
 }
 impl DepthBuffer {
     pub fn new(
@@ -15,6 +16,7 @@ impl DepthBuffer {
         command_pool: &mut CommandPool,
         resource_pool: &mut ResourcePool,
         screen_dimensions: Vector2<u32>,
+        sample_count: vk::SampleCountFlags,
     ) -> Result<Self> {
         let depth_format = core.find_supported_format(
             &[
@@ -25,11 +27,12 @@ impl DepthBuffer {
             vk::ImageTiling::OPTIMAL,
             vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
         );
-        let (image, allocation) = resource_pool.new_image(
+        let (image, allocation) = resource_pool.new_multisampled_image(
             core,
             depth_format,
             vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
             screen_dimensions,
+            sample_count,
         )?;
         TextureAllocation::transition_image_layout(
             core,
@@ -59,8 +62,14 @@ impl DepthBuffer {
             allocation: Some(allocation),
             view,
             depth_format,
+            sample_count,
         })
     }
+    pub fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        let name = format!("{} depth", name);
+        core.set_object_name(self.image, &name)?;
+        core.set_object_name(self.view, &name)
+    }
     pub fn get_attachment(
         &self,
         load_op: vk::AttachmentLoadOp,
@@ -68,7 +77,7 @@ impl DepthBuffer {
         (
             *vk::AttachmentDescription::builder()
                 .format(self.depth_format)
-                .samples(vk::SampleCountFlags::TYPE_1)
+                .samples(self.sample_count)
                 .load_op(load_op)
                 .store_op(vk::AttachmentStoreOp::STORE)
                 .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -1,4 +1,5 @@
 use super::{Core, GraphicsPipeline, TextureAttachment};
+use anyhow::Result;
 use ash::{Device, vk};
 use nalgebra::Vector2;
 pub struct FrameBufferTarget {
@@ -15,8 +16,13 @@ impl FrameBufferTarget {
             .color_buffer
             .present_image_views
             .iter()
-            .map(|image_view| {
-                let attachments = [*image_view, attachment.depth_buffer.view];
+            .enumerate()
+            .map(|(index, image_view)| {
+                // the present image is the resolve target when multisampling
+                let attachments = match attachment.color_buffer.msaa_image_views.get(index) {
+                    Some(msaa_view) => vec![*msaa_view, attachment.depth_buffer.view, *image_view],
+                    None => vec![*image_view, attachment.depth_buffer.view],
+                };
                 let create_info = vk::FramebufferCreateInfo::builder()
                     .render_pass(pipeline.clear_pipeline.renderpass)
                     .attachments(&attachments)
@@ -33,6 +39,12 @@ impl FrameBufferTarget {
 
         Self { framebuffers }
     }
+    pub fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        for (i, framebuffer) in self.framebuffers.iter().enumerate() {
+            core.set_object_name(*framebuffer, &format!("{} {}", name, i))?;
+        }
+        Ok(())
+    }
     pub fn free(&mut self, core: &mut Core) {
         unsafe {
             for framebuffer in self.framebuffers.iter() {
@@ -1,22 +1,91 @@
-use super::{Core, DepthBuffer, DescriptorDesc};
+use super::{Core, DepthBuffer, DescriptorDesc, StreamLayout};
+use crate::Topology;
 
+use anyhow::{anyhow, Result};
 use ash::{util::*, vk, Device};
 use nalgebra::Vector2;
-use std::{ffi::CString, io::Cursor};
+use std::{collections::HashMap, ffi::CString, io::Cursor};
+mod cache;
+mod compute;
 mod shaders;
-pub use shaders::{basic_shader, PushConstantDesc, ShaderDescription, VertexBufferDesc};
+pub use cache::PipelineCache;
+pub use compute::ComputePipeline;
+pub use shaders::{
+    basic_shader, compile_shader_project, debug_shader, PushConstantDesc, ShaderDescription,
+};
+/// Topologies a pipeline is built for, in the order of `RenderPipeline::graphics_pipelines`
+const TOPOLOGIES: [Topology; 5] = [
+    Topology::TriangleList,
+    Topology::TriangleStrip,
+    Topology::LineList,
+    Topology::LineStrip,
+    Topology::PointList,
+];
+fn vk_topology(topology: Topology) -> vk::PrimitiveTopology {
+    match topology {
+        Topology::TriangleList => vk::PrimitiveTopology::TRIANGLE_LIST,
+        Topology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+        Topology::LineList => vk::PrimitiveTopology::LINE_LIST,
+        Topology::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+        Topology::PointList => vk::PrimitiveTopology::POINT_LIST,
+    }
+}
 pub struct RenderPipeline {
-    pub graphics_pipeline: vk::Pipeline,
+    /// pipelines for each vertex layout drawn so far, one per topology ordered as `TOPOLOGIES`
+    pub graphics_pipelines: HashMap<Vec<StreamLayout>, Vec<vk::Pipeline>>,
     pub renderpass: vk::RenderPass,
 }
+impl RenderPipeline {
+    fn new(renderpass: vk::RenderPass) -> Self {
+        Self {
+            graphics_pipelines: HashMap::new(),
+            renderpass,
+        }
+    }
+    /// Gets pipeline for a vertex layout built with `GraphicsPipeline::draw_pipelines`
+    pub fn graphics_pipeline(&self, streams: &[StreamLayout], topology: Topology) -> vk::Pipeline {
+        let index = TOPOLOGIES
+            .iter()
+            .position(|t| *t == topology)
+            .expect("pipeline not built for topology");
+        self.graphics_pipelines
+            .get(streams)
+            .expect("pipeline not built for vertex layout")[index]
+    }
+    fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        core.set_object_name(self.renderpass, name)?;
+        for pipelines in self.graphics_pipelines.values() {
+            Self::set_pipeline_names(core, pipelines, name)?;
+        }
+        Ok(())
+    }
+    fn set_pipeline_names(core: &Core, pipelines: &[vk::Pipeline], name: &str) -> Result<()> {
+        for (pipeline, topology) in pipelines.iter().zip(TOPOLOGIES.iter()) {
+            core.set_object_name(*pipeline, &format!("{} {:?}", name, topology))?;
+        }
+        Ok(())
+    }
+}
+/// Pipelines that draw a mesh, the renderpass picks the one matching its clear op
+#[derive(Clone, Copy, Debug)]
+pub struct DrawPipelines {
+    pub clear: vk::Pipeline,
+    pub load: vk::Pipeline,
+}
 pub struct GraphicsPipeline {
     fragment_shader: vk::ShaderModule,
     vertex_shader: vk::ShaderModule,
+    vertex_entrypoint: CString,
+    fragment_entrypoint: CString,
     pub pipeline_layout: vk::PipelineLayout,
     //clears render pipeline on draw
     pub clear_pipeline: RenderPipeline,
     // does not clear color bit on draw
     pub load_pipeline: RenderPipeline,
+    screen_dimensions: Vector2<u32>,
+    sample_count: vk::SampleCountFlags,
+    /// name given with `set_name`, pipelines built later are named after it
+    debug_name: Option<String>,
 }
 #[derive(Clone, Copy, Debug)]
 pub enum PipelineType {
@@ -24,12 +93,15 @@ pub enum PipelineType {
     OffScreen,
 }
 impl GraphicsPipeline {
+    /// Builds the shader modules, layout and renderpasses. Pipelines are built for each vertex
+    /// layout on first draw with `draw_pipelines`.
     pub fn new(
         core: &mut Core,
         shader_data: &ShaderDescription,
         descriptor_layouts: &[vk::DescriptorSetLayout],
         screen_dimensions: Vector2<u32>,
         depth_buffer: &DepthBuffer,
+        sample_count: vk::SampleCountFlags,
         pipeline_type: PipelineType,
     ) -> Self {
         println!("descriptor layouts: {:#?}", descriptor_layouts);
@@ -50,28 +122,6 @@ impl GraphicsPipeline {
                 .create_shader_module(&vert_shader_info, None)
                 .expect("failed to create shader")
         };
-        let vertex_shader_entry_name = CString::new(shader_data.vertex_entrypoint.clone()).unwrap();
-        let fragment_shader_entry_name =
-            CString::new(shader_data.fragment_entrypoint.clone()).unwrap();
-        let shader_stage_create_infos = [
-            vk::PipelineShaderStageCreateInfo {
-                module: vertex_shader,
-                p_name: vertex_shader_entry_name.as_ptr(),
-                stage: vk::ShaderStageFlags::VERTEX,
-                ..Default::default()
-            },
-            vk::PipelineShaderStageCreateInfo {
-                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
-                module: fragment_shader,
-                p_name: fragment_shader_entry_name.as_ptr(),
-                stage: vk::ShaderStageFlags::FRAGMENT,
-                ..Default::default()
-            },
-        ];
-        let binding_description = [shader_data.vertex_buffer_desc.binding_description];
-        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_binding_descriptions(&binding_description)
-            .vertex_attribute_descriptions(&shader_data.vertex_buffer_desc.attributes);
         let ranges = shader_data
             .push_constants
             .iter()
@@ -86,80 +136,129 @@ impl GraphicsPipeline {
                 .create_pipeline_layout(&layout_create_info, None)
                 .expect("failed to createlayout")
         };
-
-        let clear_pipeline = Self::build_render_pipeline(
+        let final_layout = match pipeline_type {
+            PipelineType::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+            PipelineType::OffScreen => vk::ImageLayout::GENERAL,
+        };
+        let clear_pipeline = RenderPipeline::new(Self::build_renderpass(
             core,
-            &shader_stage_create_infos,
-            *vertex_input_state_info,
-            pipeline_layout,
-            screen_dimensions.x,
-            screen_dimensions.y,
             vk::AttachmentLoadOp::CLEAR,
             depth_buffer,
+            sample_count,
             vk::ImageLayout::UNDEFINED,
-            match pipeline_type {
-                PipelineType::Present => vk::ImageLayout::PRESENT_SRC_KHR,
-                PipelineType::OffScreen => vk::ImageLayout::GENERAL,
-            },
-        );
-        let load_pipeline = Self::build_render_pipeline(
+            final_layout,
+        ));
+        let load_pipeline = RenderPipeline::new(Self::build_renderpass(
             core,
-            &shader_stage_create_infos,
-            *vertex_input_state_info,
-            pipeline_layout,
-            screen_dimensions.x,
-            screen_dimensions.y,
             vk::AttachmentLoadOp::LOAD,
             depth_buffer,
-            match pipeline_type {
-                PipelineType::Present => vk::ImageLayout::PRESENT_SRC_KHR,
-                PipelineType::OffScreen => vk::ImageLayout::GENERAL,
-            },
-            match pipeline_type {
-                PipelineType::Present => vk::ImageLayout::PRESENT_SRC_KHR,
-                PipelineType::OffScreen => vk::ImageLayout::GENERAL,
-            },
-        );
+            sample_count,
+            final_layout,
+            final_layout,
+        ));
         GraphicsPipeline {
             fragment_shader,
             vertex_shader,
+            vertex_entrypoint: CString::new(shader_data.vertex_entrypoint.clone()).unwrap(),
+            fragment_entrypoint: CString::new(shader_data.fragment_entrypoint.clone()).unwrap(),
             pipeline_layout,
             clear_pipeline,
             load_pipeline,
+            screen_dimensions,
+            sample_count,
+            debug_name: None,
         }
     }
-    fn build_render_pipeline(
+    /// Gets the pipelines drawing meshes with vertex buffers laid out as `streams`, building
+    /// them for every topology if the layout has not been drawn before
+    pub fn draw_pipelines(
+        &mut self,
         core: &mut Core,
-        shader_stage_create_infos: &[vk::PipelineShaderStageCreateInfo],
-        vertex_input_state_info: vk::PipelineVertexInputStateCreateInfo,
-        pipeline_layout: vk::PipelineLayout,
-        screen_width: u32,
-        screen_height: u32,
-        load_op: vk::AttachmentLoadOp,
-        depth_buffer: &DepthBuffer,
-        //initial layout is ignored if load_op is set to clear
-        initial_layout: vk::ImageLayout,
-        final_layout: vk::ImageLayout,
-    ) -> RenderPipeline {
-        println!("layout: {:#?}", pipeline_layout);
-        let renderpass =
-            Self::build_renderpass(core, load_op, depth_buffer, initial_layout, final_layout);
-        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-            .primitive_restart_enable(false);
+        streams: &[StreamLayout],
+        topology: Topology,
+    ) -> Result<DrawPipelines> {
+        if !self.clear_pipeline.graphics_pipelines.contains_key(streams) {
+            for clear in [true, false] {
+                let renderpass = if clear {
+                    self.clear_pipeline.renderpass
+                } else {
+                    self.load_pipeline.renderpass
+                };
+                let pipelines = self.build_graphics_pipelines(core, renderpass, streams)?;
+                if let Some(name) = self.debug_name.as_ref() {
+                    let name = format!("{} {}", name, if clear { "clear" } else { "load" });
+                    RenderPipeline::set_pipeline_names(core, &pipelines, &name)?;
+                }
+                let render_pipeline = if clear {
+                    &mut self.clear_pipeline
+                } else {
+                    &mut self.load_pipeline
+                };
+                render_pipeline
+                    .graphics_pipelines
+                    .insert(streams.to_vec(), pipelines);
+            }
+        }
+        Ok(DrawPipelines {
+            clear: self.clear_pipeline.graphics_pipeline(streams, topology),
+            load: self.load_pipeline.graphics_pipeline(streams, topology),
+        })
+    }
+    /// Builds one pipeline per topology, attribute formats, offsets and strides are taken
+    /// from `streams`
+    fn build_graphics_pipelines(
+        &self,
+        core: &mut Core,
+        renderpass: vk::RenderPass,
+        streams: &[StreamLayout],
+    ) -> Result<Vec<vk::Pipeline>> {
+        let shader_stage_create_infos = [
+            vk::PipelineShaderStageCreateInfo {
+                module: self.vertex_shader,
+                p_name: self.vertex_entrypoint.as_ptr(),
+                stage: vk::ShaderStageFlags::VERTEX,
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo {
+                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                module: self.fragment_shader,
+                p_name: self.fragment_entrypoint.as_ptr(),
+                stage: vk::ShaderStageFlags::FRAGMENT,
+                ..Default::default()
+            },
+        ];
+        let binding_descriptions = streams
+            .iter()
+            .map(|stream| stream.binding_description())
+            .collect::<Vec<_>>();
+        let attribute_descriptions = streams
+            .iter()
+            .flat_map(|stream| stream.attribute_descriptions())
+            .collect::<Vec<_>>();
+        let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&binding_descriptions)
+            .vertex_attribute_descriptions(&attribute_descriptions);
+        let input_assemblies = TOPOLOGIES
+            .iter()
+            .map(|topology| {
+                *vk::PipelineInputAssemblyStateCreateInfo::builder()
+                    .topology(vk_topology(*topology))
+                    .primitive_restart_enable(false)
+            })
+            .collect::<Vec<_>>();
         let viewports = [vk::Viewport {
             x: 0.0,
             y: 0.0,
-            width: screen_width as f32,
-            height: screen_height as f32,
+            width: self.screen_dimensions.x as f32,
+            height: self.screen_dimensions.y as f32,
             min_depth: 0.0,
             max_depth: 1.0,
         }];
         let scissors = [vk::Rect2D {
             offset: vk::Offset2D { x: 0, y: 0 },
             extent: vk::Extent2D {
-                width: screen_width,
-                height: screen_height,
+                width: self.screen_dimensions.x,
+                height: self.screen_dimensions.y,
             },
         }];
         let viewport_state_info = vk::PipelineViewportStateCreateInfo::builder()
@@ -173,7 +272,7 @@ impl GraphicsPipeline {
             .depth_bias_enable(false)
             .build();
         let multi_sample_state_info = vk::PipelineMultisampleStateCreateInfo {
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            rasterization_samples: self.sample_count,
             ..Default::default()
         };
         let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState::builder()
@@ -195,43 +294,55 @@ impl GraphicsPipeline {
             .depth_compare_op(vk::CompareOp::LESS)
             .depth_bounds_test_enable(false)
             .stencil_test_enable(false);
-        let graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
-            .stages(shader_stage_create_infos)
-            .vertex_input_state(&vertex_input_state_info)
-            .input_assembly_state(&input_assembly)
-            .viewport_state(&viewport_state_info)
-            .rasterization_state(&rasterization_info)
-            .multisample_state(&multi_sample_state_info)
-            .color_blend_state(&color_blend_state)
-            .depth_stencil_state(&depth_stencil)
-            .layout(pipeline_layout)
-            .render_pass(renderpass)
-            .build();
-        let graphics_pipeline = unsafe {
-            core.device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &[graphics_pipeline_info],
-                    None,
-                )
-                .expect("failed to create pipeline")[0]
-        };
-        RenderPipeline {
-            renderpass,
-            graphics_pipeline,
+        let graphics_pipeline_infos = input_assemblies
+            .iter()
+            .map(|input_assembly| {
+                vk::GraphicsPipelineCreateInfo::builder()
+                    .stages(&shader_stage_create_infos)
+                    .vertex_input_state(&vertex_input_state_info)
+                    .input_assembly_state(input_assembly)
+                    .viewport_state(&viewport_state_info)
+                    .rasterization_state(&rasterization_info)
+                    .multisample_state(&multi_sample_state_info)
+                    .color_blend_state(&color_blend_state)
+                    .depth_stencil_state(&depth_stencil)
+                    .layout(self.pipeline_layout)
+                    .render_pass(renderpass)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        unsafe {
+            core.device.create_graphics_pipelines(
+                core.pipeline_cache.cache,
+                &graphics_pipeline_infos,
+                None,
+            )
         }
+        .map_err(|(_pipelines, err)| anyhow!("failed to create pipeline: {}", err))
     }
+    /// When multisampling the multisampled color image is attachment 0 and stays in
+    /// the color attachment layout, the resolve target is attachment 2
     fn build_renderpass(
         core: &mut Core,
         load_op: vk::AttachmentLoadOp,
         depth_buffer: &DepthBuffer,
+        sample_count: vk::SampleCountFlags,
         initial_layout: vk::ImageLayout,
         final_layout: vk::ImageLayout,
     ) -> vk::RenderPass {
+        let multisampled = sample_count != vk::SampleCountFlags::TYPE_1;
         let (depth_attachment, depth_attachment_ref) = depth_buffer.get_attachment(load_op);
-        let color_attachment = [vk::AttachmentDescription::builder()
+        let (color_initial_layout, color_final_layout) = if multisampled {
+            (
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            )
+        } else {
+            (initial_layout, final_layout)
+        };
+        let color_attachment = vk::AttachmentDescription::builder()
             .format(core.surface_format.format)
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .samples(sample_count)
             .load_op(load_op)
             .store_op(vk::AttachmentStoreOp::STORE)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
@@ -239,19 +350,37 @@ impl GraphicsPipeline {
             .initial_layout(if load_op == vk::AttachmentLoadOp::CLEAR {
                 vk::ImageLayout::UNDEFINED
             } else {
-                initial_layout
+                color_initial_layout
             })
+            .final_layout(color_final_layout)
+            .build();
+        // every sample is resolved so the old contents are never needed
+        let resolve_attachment = vk::AttachmentDescription::builder()
+            .format(core.surface_format.format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
             .final_layout(final_layout)
-            .build()];
+            .build();
         let color_attachment_refs = [vk::AttachmentReference::builder()
             .attachment(0)
             .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
             .build()];
-        let subpasses = [vk::SubpassDescription::builder()
+        let resolve_attachment_refs = [vk::AttachmentReference::builder()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build()];
+        let mut subpass = vk::SubpassDescription::builder()
             .color_attachments(&color_attachment_refs)
             .depth_stencil_attachment(&depth_attachment_ref)
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .build()];
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+        if multisampled {
+            subpass = subpass.resolve_attachments(&resolve_attachment_refs);
+        }
+        let subpasses = [subpass.build()];
 
         let subpass_dependencies = [vk::SubpassDependency::builder()
             .src_subpass(vk::SUBPASS_EXTERNAL)
@@ -270,7 +399,11 @@ impl GraphicsPipeline {
                     | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
             )
             .build()];
-        let attachments = [color_attachment[0], depth_attachment];
+        let attachments = if multisampled {
+            vec![color_attachment, depth_attachment, resolve_attachment]
+        } else {
+            vec![color_attachment, depth_attachment]
+        };
         let render_pass_create_info = vk::RenderPassCreateInfo::builder()
             .attachments(&attachments)
             .subpasses(&subpasses)
@@ -281,11 +414,23 @@ impl GraphicsPipeline {
                 .expect("failed to create renderpass")
         }
     }
+    /// Names the shader modules, layout and pipelines after `name`, pipelines built later
+    /// are also named
+    pub fn set_name(&mut self, core: &Core, name: &str) -> Result<()> {
+        self.debug_name = Some(name.to_string());
+        core.set_object_name(self.vertex_shader, &format!("{} vertex", name))?;
+        core.set_object_name(self.fragment_shader, &format!("{} fragment", name))?;
+        core.set_object_name(self.pipeline_layout, name)?;
+        self.clear_pipeline
+            .set_name(core, &format!("{} clear", name))?;
+        self.load_pipeline.set_name(core, &format!("{} load", name))
+    }
     pub fn free(&mut self, core: &mut Core) {
         unsafe {
             let free_pipeline = |pipeline: &RenderPipeline| {
-                core.device
-                    .destroy_pipeline(pipeline.graphics_pipeline, None);
+                for graphics_pipeline in pipeline.graphics_pipelines.values().flatten() {
+                    core.device.destroy_pipeline(*graphics_pipeline, None);
+                }
                 core.device.destroy_render_pass(pipeline.renderpass, None);
             };
             free_pipeline(&self.clear_pipeline);
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use ash::{vk, Device, Instance};
+use std::path::{Path, PathBuf};
+/// Name of the cache file inside of `CreateInfo::pipeline_cache_dir`
+const CACHE_FILE: &str = "pipeline_cache.bin";
+/// Size of `VkPipelineCacheHeaderVersionOne`
+const HEADER_SIZE: usize = 32;
+/// Pipeline cache shared by every `GraphicsPipeline`, kept on disk between runs
+/// if a cache directory is given
+pub struct PipelineCache {
+    pub cache: vk::PipelineCache,
+    /// file the cache is loaded from and saved to
+    path: Option<PathBuf>,
+}
+impl PipelineCache {
+    /// Loads the cache in `cache_dir`, cache data written by a different driver
+    /// or device is discarded
+    pub fn new(
+        instance: &Instance,
+        device: &Device,
+        physical_device: vk::PhysicalDevice,
+        cache_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let path = cache_dir.map(|dir| dir.join(CACHE_FILE));
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let initial_data = path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok())
+            .filter(|data| {
+                let valid = is_compatible(data, &properties);
+                if !valid {
+                    println!("discarding pipeline cache from another device");
+                }
+                valid
+            })
+            .unwrap_or_default();
+        let create_info = vk::PipelineCacheCreateInfo::builder().initial_data(&initial_data);
+        let cache = unsafe { device.create_pipeline_cache(&create_info, None)? };
+        Ok(Self { cache, path })
+    }
+    /// Writes the cache to the cache directory, does nothing without one
+    pub fn save(&self, device: &Device) -> Result<()> {
+        if let Some(path) = self.path.as_ref() {
+            let data = unsafe { device.get_pipeline_cache_data(self.cache)? };
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, data)?;
+        }
+        Ok(())
+    }
+    pub fn free(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline_cache(self.cache, None);
+        }
+    }
+}
+/// Checks that the header of cache data matches the device, the driver
+/// rejects or ignores mismatched data but checking avoids relying on that
+fn is_compatible(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    let read_u32 = |offset: usize| {
+        u32::from_ne_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+    read_u32(0) as usize >= HEADER_SIZE
+        && read_u32(4) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+        && read_u32(8) == properties.vendor_id
+        && read_u32(12) == properties.device_id
+        && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn validates_header() {
+        let properties = vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 7,
+            pipeline_cache_uuid: [3; 16],
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        data.extend_from_slice(&(HEADER_SIZE as u32).to_ne_bytes());
+        data.extend_from_slice(
+            &(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_ne_bytes(),
+        );
+        data.extend_from_slice(&properties.vendor_id.to_ne_bytes());
+        data.extend_from_slice(&properties.device_id.to_ne_bytes());
+        data.extend_from_slice(&properties.pipeline_cache_uuid);
+        data.extend_from_slice(&[0xab; 8]);
+        assert!(is_compatible(&data, &properties));
+        assert!(!is_compatible(&data[..HEADER_SIZE - 1], &properties));
+
+        let mut other_device = data.clone();
+        other_device[12] += 1;
+        assert!(!is_compatible(&other_device, &properties));
+
+        let mut other_uuid = data.clone();
+        other_uuid[20] = 0;
+        assert!(!is_compatible(&other_uuid, &properties));
+    }
+}
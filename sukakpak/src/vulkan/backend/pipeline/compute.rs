@@ -0,0 +1,57 @@
+use super::super::Core;
+use anyhow::{anyhow, Result};
+use ash::vk;
+use std::ffi::CString;
+/// Compute shader ready to dispatch, storage buffer `i` is in descriptor set `i`
+pub struct ComputePipeline {
+    shader_module: vk::ShaderModule,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub pipeline: vk::Pipeline,
+    pub buffers: Vec<ass_types::StorageBinding>,
+}
+impl ComputePipeline {
+    pub fn new(
+        core: &Core,
+        shader: &ass_vk::ComputeShader,
+        storage_layout: vk::DescriptorSetLayout,
+    ) -> Result<Self> {
+        let shader_info = vk::ShaderModuleCreateInfo::builder().code(&shader.spirv_data);
+        let shader_module = unsafe { core.device.create_shader_module(&shader_info, None)? };
+        let set_layouts = vec![storage_layout; shader.buffers.len()];
+        let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+        let pipeline_layout = unsafe { core.device.create_pipeline_layout(&layout_info, None)? };
+        let entrypoint = CString::new(shader.entrypoint.clone())?;
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .module(shader_module)
+            .name(&entrypoint)
+            .stage(vk::ShaderStageFlags::COMPUTE);
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(*stage)
+            .layout(pipeline_layout);
+        let pipeline = unsafe {
+            core.device
+                .create_compute_pipelines(core.pipeline_cache.cache, &[*pipeline_info], None)
+        }
+        .map_err(|(_pipelines, e)| anyhow!("failed to create compute pipeline: {}", e))?[0];
+        Ok(Self {
+            shader_module,
+            pipeline_layout,
+            pipeline,
+            buffers: shader.buffers.clone(),
+        })
+    }
+    /// Names the shader module, layout and pipeline after `name`
+    pub fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        core.set_object_name(self.shader_module, name)?;
+        core.set_object_name(self.pipeline_layout, name)?;
+        core.set_object_name(self.pipeline, name)
+    }
+    pub fn free(&mut self, core: &mut Core) {
+        unsafe {
+            core.device.destroy_pipeline(self.pipeline, None);
+            core.device
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            core.device.destroy_shader_module(self.shader_module, None);
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use super::DescriptorDesc;
+use anyhow::Result;
 use ash::vk;
 
 use nalgebra::{Matrix4, Vector2, Vector3};
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
 #[derive(Clone, Copy, Debug)]
 pub struct PushConstantDesc {
     pub range: vk::PushConstantRange,
@@ -16,21 +17,20 @@ pub struct TextureDescriptorLayout {
 #[derive(Clone, Debug)]
 pub struct ShaderDescription {
     pub push_constants: Vec<PushConstantDesc>,
-    pub vertex_buffer_desc: VertexBufferDesc,
+    /// Reflected vertex input, used to split meshes into vertex streams and to
+    /// validate them. Attribute formats come from the mesh's layout.
+    pub vertex_input: ass_types::VertexInput,
     pub vertex_shader_data: Vec<u8>,
     pub fragment_shader_data: Vec<u8>,
     pub textures: HashMap<String, TextureDescriptorLayout>,
+    /// Uniform and storage buffers, buffer `i` is in descriptor set `i + 2`
+    pub buffers: Vec<ass_types::BufferBinding>,
     /// Name of vertex shader entrypoint, if v1 shader is "main"
     pub vertex_entrypoint: String,
     /// Name of fragment shader entrypoint, if v1 shader is "main"
     pub fragment_entrypoint: String,
 }
 
-#[derive(Clone, Debug)]
-pub struct VertexBufferDesc {
-    pub binding_description: vk::VertexInputBindingDescription,
-    pub attributes: Vec<vk::VertexInputAttributeDescription>,
-}
 /// a barebones shader that just does test corrections, todo: make it simple with no change to colors
 pub fn basic_shader() -> ShaderDescription {
     let shader =
@@ -39,6 +39,21 @@ pub fn basic_shader() -> ShaderDescription {
             .unwrap();
     shader.into()
 }
+/// Shader used by `ContextTrait::draw_debug`, takes a position and a color
+pub fn debug_shader() -> ShaderDescription {
+    let shader =
+        ass_vk::Shader::from_json_str(include_str!("../../../../shaders/v2/debug_lines.ass_spv"))
+            .ok()
+            .unwrap();
+    shader.into()
+}
+/// Compiles the WGSL shader project in `project_dir`
+pub fn compile_shader_project(project_dir: &Path) -> Result<ShaderDescription> {
+    let ir =
+        ass_lib::ShaderIR::compile_from_disk(project_dir, ass_lib::Options { verbose: false })?;
+    let shader = ass_vk::Shader::from_ir(ir, ass_vk::Options { verbose: false })?;
+    Ok(shader.into())
+}
 impl From<ass_vk::Shader> for ShaderDescription {
     fn from(shader: ass_vk::Shader) -> Self {
         let push_constants = vec![PushConstantDesc {
@@ -48,53 +63,9 @@ impl From<ass_vk::Shader> for ShaderDescription {
                 size: shader.push_constant.size(),
             },
         }];
-        let mut attributes = vec![];
-        let mut offset = 0;
-        for input in shader.vertex_input.fields.iter() {
-            attributes.push(vk::VertexInputAttributeDescription {
-                location: input.location,
-                binding: shader.vertex_input.binding,
-                format: match input.ty {
-                    ass_types::ShaderType::Mat4x4(_) => {
-                        panic!("matrix 4x4 not avalible as vertex input")
-                    }
-                    ass_types::ShaderType::Vec4(s) => match s {
-                        ass_types::Scalar::F32 => vk::Format::R32G32B32A32_SFLOAT,
-                        ass_types::Scalar::U32 => vk::Format::R32G32B32A32_UINT,
-                    },
-                    ass_types::ShaderType::Vec3(s) => match s {
-                        ass_types::Scalar::F32 => vk::Format::R32G32B32_SFLOAT,
-                        ass_types::Scalar::U32 => vk::Format::R32G32B32_UINT,
-                    },
-                    ass_types::ShaderType::Vec2(s) => match s {
-                        ass_types::Scalar::F32 => vk::Format::R32G32_SFLOAT,
-                        ass_types::Scalar::U32 => vk::Format::R32G32_UINT,
-                    },
-                    ass_types::ShaderType::Scalar(s) => match s {
-                        ass_types::Scalar::F32 => vk::Format::R32_SFLOAT,
-                        ass_types::Scalar::U32 => vk::Format::R32_UINT,
-                    },
-                    ass_types::ShaderType::Struct(_) => panic!("struct invalid as vertex input"),
-                },
-                offset,
-            });
-            offset += input.size();
-        }
         ShaderDescription {
             push_constants,
-            vertex_buffer_desc: VertexBufferDesc {
-                binding_description: vk::VertexInputBindingDescription {
-                    binding: shader.vertex_input.binding,
-                    stride: shader
-                        .vertex_input
-                        .fields
-                        .iter()
-                        .map(|f| f.size())
-                        .fold(0, |acc, x| acc + x),
-                    input_rate: vk::VertexInputRate::VERTEX,
-                },
-                attributes,
-            },
+            vertex_input: shader.vertex_input.clone(),
             vertex_shader_data: shader
                 .vertex_spirv_data
                 .iter()
@@ -105,6 +76,7 @@ impl From<ass_vk::Shader> for ShaderDescription {
                 .iter()
                 .flat_map(|u| u.to_ne_bytes())
                 .collect(),
+            buffers: shader.buffers,
             fragment_entrypoint: shader.fragment_entrypoint,
             vertex_entrypoint: shader.vertex_entrypoint,
             textures: shader
@@ -1,5 +1,6 @@
-use super::CommandPool;
-use anyhow::Result;
+use super::pipeline::PipelineCache;
+use crate::{PresentMode, SampleCount};
+use anyhow::{anyhow, Result};
 use ash::{
     extensions::ext::DebugUtils,
     extensions::khr::{Surface as AshSurface, Swapchain},
@@ -10,6 +11,8 @@ use nalgebra as na;
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 
 use std::ffi::{CStr, CString};
+mod adapter;
+pub use adapter::list_adapters;
 
 const DO_BACKTRACE: bool = true;
 const PANIC: bool = true;
@@ -84,6 +87,65 @@ fn map_instance_create_error(error: vk::Result, entry: &Entry, layer_names: &[CS
         format!("{:#?}", error).to_string()
     }
 }
+/// Number of offscreen images standing in for the swapchain when headless, unless
+/// `CreateInfo::swapchain_images` is set
+const HEADLESS_IMAGE_COUNT: usize = 2;
+/// Vulkan present modes tried for `mode` in order of preference, FIFO is always supported
+fn present_mode_preference(mode: PresentMode) -> &'static [vk::PresentModeKHR] {
+    match mode {
+        PresentMode::Vsync => &[vk::PresentModeKHR::FIFO],
+        PresentMode::AdaptiveVsync => &[vk::PresentModeKHR::FIFO_RELAXED, vk::PresentModeKHR::FIFO],
+        PresentMode::NoVsync => &[
+            vk::PresentModeKHR::MAILBOX,
+            vk::PresentModeKHR::IMMEDIATE,
+            vk::PresentModeKHR::FIFO,
+        ],
+    }
+}
+/// Picks the most preferred present mode for `mode` out of `supported`
+fn choose_present_mode(mode: PresentMode, supported: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+    present_mode_preference(mode)
+        .iter()
+        .copied()
+        .find(|preferred| supported.contains(preferred))
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+/// Highest count in `supported` that is at most `sample_count`, single sampling is
+/// always supported
+fn choose_sample_count(
+    sample_count: SampleCount,
+    supported: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    [
+        vk::SampleCountFlags::TYPE_8,
+        vk::SampleCountFlags::TYPE_4,
+        vk::SampleCountFlags::TYPE_2,
+    ]
+    .iter()
+    .copied()
+    .filter(|count| count.as_raw() <= sample_count.samples())
+    .find(|count| supported.contains(*count))
+    .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+/// Format of the offscreen screen images when headless, read back as `RgbaImage`
+const HEADLESS_FORMAT: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
+    format: vk::Format::R8G8B8A8_UNORM,
+    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+};
+/// Window surface and swapchain
+pub struct Presentation {
+    pub swapchain: vk::SwapchainKHR,
+    pub swapchain_loader: Swapchain,
+    //Swapchain Info
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+    swapchain_image_count: u32,
+    present_mode: vk::PresentModeKHR,
+    //end swapchain info
+    /// TODO: move into separate thread
+    surface: vk::SurfaceKHR,
+    /// TODO: move into separate thread
+    surface_loader: AshSurface,
+}
 pub struct Core {
     pub physical_device: vk::PhysicalDevice,
     pub device: ash::Device,
@@ -95,22 +157,22 @@ pub struct Core {
     debug_callback: vk::DebugUtilsMessengerEXT,
     pub present_queue: vk::Queue,
     pub queue_family_index: u32,
-    pub swapchain: vk::SwapchainKHR,
-    pub swapchain_loader: Swapchain,
     pub surface_format: vk::SurfaceFormatKHR,
-    //Swapchain Info
-    pre_transform: vk::SurfaceTransformFlagsKHR,
-    swapchain_image_count: u32,
-    present_mode: vk::PresentModeKHR,
-    //end swapchain info
-    /// TODO: move into separate thread
-    surface: vk::SurfaceKHR,
-    /// TODO: move into separate thread
-    surface_loader: AshSurface,
+    /// None when running headless
+    pub presentation: Option<Presentation>,
+    /// number of offscreen images used when headless
+    headless_image_count: usize,
+    /// shared by every pipeline, saved when the core is freed
+    pub pipeline_cache: PipelineCache,
     debug_utils_loader: DebugUtils,
 }
 impl Core {
-    pub fn new(window: &winit::window::Window, create_info: &super::CreateInfo) -> Result<Self> {
+    /// Creates core rendering to `window`, if `window` is None no surface extensions are loaded
+    /// and rendering is done to offscreen images
+    pub fn new(
+        window: Option<&winit::window::Window>,
+        create_info: &super::CreateInfo,
+    ) -> Result<Self> {
         let entry = if let Some(load_path) = create_info.vulkan_sdk_path.as_ref() {
             if load_path.exists() {
                 unsafe { Entry::load_from(load_path) }?
@@ -135,12 +197,12 @@ impl Core {
         }
         let layer_names_raw: Vec<*const i8> =
             layer_names.iter().map(|name| name.as_ptr()).collect();
-        let surface_extensions =
-            ash_window::enumerate_required_extensions(window.raw_display_handle()).unwrap();
-        let mut extension_names_raw: Vec<*const i8> = surface_extensions
-            .iter()
-            .map(|ext| *ext)
-            .collect::<Vec<_>>();
+        let mut extension_names_raw: Vec<*const i8> = match window {
+            Some(window) => ash_window::enumerate_required_extensions(window.raw_display_handle())
+                .unwrap()
+                .to_vec(),
+            None => vec![],
+        };
         let debug_utils_name = DebugUtils::name();
         extension_names_raw.push(debug_utils_name.as_ptr());
         let app_info = vk::ApplicationInfo::builder()
@@ -171,57 +233,89 @@ impl Core {
         let debug_utils_loader = DebugUtils::new(&entry, &instance);
         let debug_callback =
             unsafe { debug_utils_loader.create_debug_utils_messenger(&debug_info, None) }.unwrap();
-        let surface = unsafe {
-            ash_window::create_surface(
-                &entry,
-                &instance,
-                window.raw_display_handle(),
-                window.raw_window_handle(),
-                None,
-            )
-        }
-        .unwrap();
+        let surface = window.map(|window| {
+            unsafe {
+                ash_window::create_surface(
+                    &entry,
+                    &instance,
+                    window.raw_display_handle(),
+                    window.raw_window_handle(),
+                    None,
+                )
+            }
+            .unwrap()
+        });
         let pdevices =
             unsafe { instance.enumerate_physical_devices() }.expect("failed to get pdevices");
         let surface_loader = AshSurface::new(&entry, &instance);
 
-        let (physical_device, queue_family_index) = unsafe {
-            pdevices.iter().map(|pdevice| {
+        let preference =
+            adapter::preference_override().unwrap_or_else(|| create_info.device_preference.clone());
+        // devices able to render and present, along with their graphics queue family
+        let candidates = pdevices
+            .iter()
+            .enumerate()
+            .filter_map(|(device_index, pdevice)| unsafe {
+                if instance
+                    .get_physical_device_features(*pdevice)
+                    .sampler_anisotropy
+                    == vk::FALSE
+                {
+                    return None;
+                }
                 instance
                     .get_physical_device_queue_family_properties(*pdevice)
                     .iter()
                     .enumerate()
-                    .filter_map(|(index, info)| {
-                        let supports_graphic_and_surface =
-                            info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                                && surface_loader
-                                    .get_physical_device_surface_support(
-                                        *pdevice,
-                                        index as u32,
-                                        surface,
-                                    )
-                                    .unwrap()
-                                && instance
-                                    .get_physical_device_features(*pdevice)
-                                    .sampler_anisotropy
-                                    >= 1;
-                        if supports_graphic_and_surface {
-                            println!("supports?");
-                            Some((*pdevice, index))
-                        } else {
-                            None
-                        }
+                    .position(|(index, info)| {
+                        let supports_surface = match surface {
+                            Some(surface) => surface_loader
+                                .get_physical_device_surface_support(
+                                    *pdevice,
+                                    index as u32,
+                                    surface,
+                                )
+                                .unwrap(),
+                            None => true,
+                        };
+                        info.queue_flags.contains(vk::QueueFlags::GRAPHICS) && supports_surface
+                    })
+                    .map(|queue_family_index| {
+                        (
+                            adapter::adapter_info(&instance, *pdevice, device_index),
+                            *pdevice,
+                            queue_family_index,
+                        )
                     })
-                    .next()
             })
-        }
-        .flatten()
-        .next()
-        .expect("could not get physical device");
+            .collect::<Vec<_>>();
+        let adapters = candidates
+            .iter()
+            .map(|(info, _, _)| info.clone())
+            .collect::<Vec<_>>();
+        let chosen = adapter::choose_adapter(&preference, &adapters).ok_or_else(|| {
+            anyhow!(
+                "no gpu matching {:?}, usable gpus: {:?}",
+                preference,
+                adapters
+                    .iter()
+                    .map(|adapter| &adapter.name)
+                    .collect::<Vec<_>>()
+            )
+        })?;
+        let (adapter, physical_device, queue_family_index) = candidates[chosen].clone();
+        println!(
+            "rendering with {} ({:?})",
+            adapter.name, adapter.adapter_type
+        );
         let memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
         let queue_family_index = queue_family_index as u32;
-        let device_extension_names_raw = [Swapchain::name().as_ptr()];
+        let device_extension_names_raw: Vec<*const i8> = if surface.is_some() {
+            vec![Swapchain::name().as_ptr()]
+        } else {
+            vec![]
+        };
         let features = vk::PhysicalDeviceFeatures::builder()
             .shader_clip_distance(true)
             .sampler_anisotropy(true);
@@ -238,68 +332,34 @@ impl Core {
             .expect("failed to create device");
 
         let present_queue = unsafe { device.get_device_queue(queue_family_index, 0) };
-        let surface_format = unsafe {
-            surface_loader
-                .get_physical_device_surface_formats(physical_device, surface)
-                .unwrap()[0]
-        };
-
-        let surface_capabilities = unsafe {
-            surface_loader
-                .get_physical_device_surface_capabilities(physical_device, surface)
-                .unwrap()
-        };
-        let mut swapchain_image_count = surface_capabilities.min_image_count + 1;
-        if surface_capabilities.max_image_count > 0
-            && swapchain_image_count > surface_capabilities.max_image_count
-        {
-            swapchain_image_count = surface_capabilities.max_image_count;
-        }
-        let surface_resolution = match surface_capabilities.current_extent.width {
-            std::u32::MAX => vk::Extent2D {
-                width: create_info.default_size.x,
-                height: create_info.default_size.y,
-            },
-            _ => surface_capabilities.current_extent,
-        };
-
-        let pre_transform = if surface_capabilities
-            .supported_transforms
-            .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
-        {
-            vk::SurfaceTransformFlagsKHR::IDENTITY
-        } else {
-            surface_capabilities.current_transform
-        };
-        let present_modes = unsafe {
-            surface_loader
-                .get_physical_device_surface_present_modes(physical_device, surface)
-                .unwrap()
+        let (surface_format, presentation) = match surface {
+            Some(surface) => {
+                let surface_format = unsafe {
+                    surface_loader
+                        .get_physical_device_surface_formats(physical_device, surface)
+                        .unwrap()[0]
+                };
+                let presentation = Presentation::new(
+                    &instance,
+                    &device,
+                    physical_device,
+                    surface,
+                    surface_loader,
+                    surface_format,
+                    create_info,
+                );
+                (surface_format, Some(presentation))
+            }
+            None => (HEADLESS_FORMAT, None),
         };
-        let present_mode = present_modes
-            .iter()
-            .cloned()
-            .find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
-            .unwrap_or(vk::PresentModeKHR::FIFO);
-        let swapchain_loader = Swapchain::new(&instance, &device);
-        let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
-            .surface(surface)
-            .min_image_count(swapchain_image_count)
-            .image_color_space(surface_format.color_space)
-            .image_format(surface_format.format)
-            .image_extent(surface_resolution)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .pre_transform(pre_transform)
-            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-            .present_mode(present_mode)
-            .clipped(true)
-            .image_array_layers(1);
-        let swapchain =
-            unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None) }.unwrap();
+        let pipeline_cache = PipelineCache::new(
+            &instance,
+            &device,
+            physical_device,
+            create_info.pipeline_cache_dir.as_deref(),
+        )?;
         Ok(Self {
             physical_device,
-            swapchain_loader,
             device,
             entry,
             instance,
@@ -307,38 +367,125 @@ impl Core {
             debug_callback,
             surface_format,
             present_queue,
-            swapchain,
-            surface_loader,
-            surface,
+            presentation,
+            headless_image_count: create_info
+                .swapchain_images
+                .map(|count| count.max(1) as usize)
+                .unwrap_or(HEADLESS_IMAGE_COUNT),
+            pipeline_cache,
             debug_utils_loader,
             queue_family_index,
-            pre_transform,
-            swapchain_image_count,
-            present_mode,
         })
     }
+    pub fn is_headless(&self) -> bool {
+        self.presentation.is_none()
+    }
+    /// Number of images in the swapchain, or of the offscreen images when headless
+    pub fn num_images(&self) -> Result<usize> {
+        match self.presentation.as_ref() {
+            Some(presentation) => Ok(unsafe {
+                presentation
+                    .swapchain_loader
+                    .get_swapchain_images(presentation.swapchain)?
+            }
+            .len()),
+            None => Ok(self.headless_image_count),
+        }
+    }
+    /// Sample count to use for `sample_count`, limited to what color and depth
+    /// attachments support
+    pub fn sample_count(&self, sample_count: SampleCount) -> vk::SampleCountFlags {
+        let limits = unsafe {
+            self.instance
+                .get_physical_device_properties(self.physical_device)
+        }
+        .limits;
+        choose_sample_count(
+            sample_count,
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts,
+        )
+    }
+    /// Names `object` in debuggers and captures such as RenderDoc
+    pub fn set_object_name<T: vk::Handle>(&self, object: T, name: &str) -> Result<()> {
+        let name = CString::new(name)?;
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(T::TYPE)
+            .object_handle(object.as_raw())
+            .object_name(&name);
+        unsafe {
+            self.debug_utils_loader
+                .set_debug_utils_object_name(self.device.handle(), &name_info)?
+        };
+        Ok(())
+    }
+    /// Opens a labeled region in `command_buffer`, closed by `end_label`
+    pub fn begin_label(&self, command_buffer: vk::CommandBuffer, label: &str) -> Result<()> {
+        let label = CString::new(label)?;
+        let label_info = vk::DebugUtilsLabelEXT::builder().label_name(&label);
+        unsafe {
+            self.debug_utils_loader
+                .cmd_begin_debug_utils_label(command_buffer, &label_info)
+        };
+        Ok(())
+    }
+    pub fn end_label(&self, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            self.debug_utils_loader
+                .cmd_end_debug_utils_label(command_buffer)
+        };
+    }
+    /// Sets the present mode used by the next `update_swapchain_resolution`, does nothing
+    /// when headless
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<()> {
+        if let Some(presentation) = self.presentation.as_mut() {
+            let supported = unsafe {
+                presentation
+                    .surface_loader
+                    .get_physical_device_surface_present_modes(
+                        self.physical_device,
+                        presentation.surface,
+                    )?
+            };
+            presentation.present_mode = choose_present_mode(present_mode, &supported);
+        }
+        Ok(())
+    }
+    /// Recreates the swapchain, does nothing when headless. Views of the old swapchain's
+    /// images must already be destroyed.
     pub fn update_swapchain_resolution(&mut self, new_size: na::Vector2<u32>) -> Result<()> {
+        let surface_format = self.surface_format;
+        let presentation = match self.presentation.as_mut() {
+            Some(presentation) => presentation,
+            None => return Ok(()),
+        };
         let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
-            .surface(self.surface)
-            .min_image_count(self.swapchain_image_count)
-            .image_color_space(self.surface_format.color_space)
-            .image_format(self.surface_format.format)
+            .surface(presentation.surface)
+            .min_image_count(presentation.swapchain_image_count)
+            .image_color_space(surface_format.color_space)
+            .image_format(surface_format.format)
             .image_extent(vk::Extent2D {
                 width: new_size.x,
                 height: new_size.y,
             })
             .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .pre_transform(self.pre_transform)
+            .pre_transform(presentation.pre_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-            .present_mode(self.present_mode)
+            .present_mode(presentation.present_mode)
             .clipped(true)
-            .image_array_layers(1);
-        self.swapchain = unsafe {
-            self.swapchain_loader
+            .image_array_layers(1)
+            .old_swapchain(presentation.swapchain);
+        let swapchain = unsafe {
+            presentation
+                .swapchain_loader
                 .create_swapchain(&swapchain_create_info, None)
+        }?;
+        let old_swapchain = std::mem::replace(&mut presentation.swapchain, swapchain);
+        unsafe {
+            presentation
+                .swapchain_loader
+                .destroy_swapchain(old_swapchain, None);
         }
-        .unwrap();
 
         Ok(())
     }
@@ -363,36 +510,162 @@ impl Core {
         }
         panic!("format not found")
     }
-    pub fn copy_buffer(
-        &mut self,
-        command_pool: &mut CommandPool,
-        src: &vk::Buffer,
-        dst: &vk::Buffer,
-        buffer_size: u64,
-    ) {
-        unsafe {
-            let copy_command = command_pool.create_onetime_buffer(self);
-            let copy_region = [*vk::BufferCopy::builder()
-                .src_offset(0)
-                .dst_offset(0)
-                .size(buffer_size)];
-            copy_command.core.device.cmd_copy_buffer(
-                copy_command.command_buffer[0],
-                *src,
-                *dst,
-                &copy_region,
-            );
-        }
-    }
     /// frees resources. Must only be called once
     pub unsafe fn free(&mut self) {
         self.device.device_wait_idle().expect("failed to wait idle");
-        self.swapchain_loader
-            .destroy_swapchain(self.swapchain, None);
-        self.surface_loader.destroy_surface(self.surface, None);
+        if let Err(e) = self.pipeline_cache.save(&self.device) {
+            println!("failed to save pipeline cache: {}", e);
+        }
+        self.pipeline_cache.free(&self.device);
+        if let Some(presentation) = self.presentation.as_ref() {
+            presentation
+                .swapchain_loader
+                .destroy_swapchain(presentation.swapchain, None);
+            presentation
+                .surface_loader
+                .destroy_surface(presentation.surface, None);
+        }
         self.device.destroy_device(None);
         self.debug_utils_loader
             .destroy_debug_utils_messenger(self.debug_callback, None);
         self.instance.destroy_instance(None);
     }
 }
+impl Presentation {
+    fn new(
+        instance: &Instance,
+        device: &Device,
+        physical_device: vk::PhysicalDevice,
+        surface: vk::SurfaceKHR,
+        surface_loader: AshSurface,
+        surface_format: vk::SurfaceFormatKHR,
+        create_info: &super::CreateInfo,
+    ) -> Self {
+        let surface_capabilities = unsafe {
+            surface_loader
+                .get_physical_device_surface_capabilities(physical_device, surface)
+                .unwrap()
+        };
+        let mut swapchain_image_count = create_info
+            .swapchain_images
+            .unwrap_or(surface_capabilities.min_image_count + 1)
+            .max(surface_capabilities.min_image_count);
+        if surface_capabilities.max_image_count > 0
+            && swapchain_image_count > surface_capabilities.max_image_count
+        {
+            swapchain_image_count = surface_capabilities.max_image_count;
+        }
+        let surface_resolution = match surface_capabilities.current_extent.width {
+            std::u32::MAX => vk::Extent2D {
+                width: create_info.default_size.x,
+                height: create_info.default_size.y,
+            },
+            _ => surface_capabilities.current_extent,
+        };
+
+        let pre_transform = if surface_capabilities
+            .supported_transforms
+            .contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+        {
+            vk::SurfaceTransformFlagsKHR::IDENTITY
+        } else {
+            surface_capabilities.current_transform
+        };
+        let present_modes = unsafe {
+            surface_loader
+                .get_physical_device_surface_present_modes(physical_device, surface)
+                .unwrap()
+        };
+        let present_mode = choose_present_mode(create_info.present_mode, &present_modes);
+        let swapchain_loader = Swapchain::new(instance, device);
+        let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(surface)
+            .min_image_count(swapchain_image_count)
+            .image_color_space(surface_format.color_space)
+            .image_format(surface_format.format)
+            .image_extent(surface_resolution)
+            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(pre_transform)
+            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .present_mode(present_mode)
+            .clipped(true)
+            .image_array_layers(1);
+        let swapchain =
+            unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None) }.unwrap();
+        Self {
+            swapchain,
+            swapchain_loader,
+            pre_transform,
+            swapchain_image_count,
+            present_mode,
+            surface,
+            surface_loader,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn present_mode_falls_back() {
+        let fifo_only = [vk::PresentModeKHR::FIFO];
+        for mode in [
+            PresentMode::Vsync,
+            PresentMode::AdaptiveVsync,
+            PresentMode::NoVsync,
+        ] {
+            assert_eq!(
+                choose_present_mode(mode, &fifo_only),
+                vk::PresentModeKHR::FIFO
+            );
+        }
+        let all = [
+            vk::PresentModeKHR::IMMEDIATE,
+            vk::PresentModeKHR::MAILBOX,
+            vk::PresentModeKHR::FIFO,
+            vk::PresentModeKHR::FIFO_RELAXED,
+        ];
+        assert_eq!(
+            choose_present_mode(PresentMode::Vsync, &all),
+            vk::PresentModeKHR::FIFO
+        );
+        assert_eq!(
+            choose_present_mode(PresentMode::AdaptiveVsync, &all),
+            vk::PresentModeKHR::FIFO_RELAXED
+        );
+        assert_eq!(
+            choose_present_mode(PresentMode::NoVsync, &all),
+            vk::PresentModeKHR::MAILBOX
+        );
+        assert_eq!(
+            choose_present_mode(
+                PresentMode::NoVsync,
+                &[vk::PresentModeKHR::FIFO, vk::PresentModeKHR::IMMEDIATE]
+            ),
+            vk::PresentModeKHR::IMMEDIATE
+        );
+    }
+    #[test]
+    fn sample_count_falls_back() {
+        let supported = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_2
+            | vk::SampleCountFlags::TYPE_4;
+        assert_eq!(
+            choose_sample_count(SampleCount::X8, supported),
+            vk::SampleCountFlags::TYPE_4
+        );
+        assert_eq!(
+            choose_sample_count(SampleCount::X2, supported),
+            vk::SampleCountFlags::TYPE_2
+        );
+        assert_eq!(
+            choose_sample_count(SampleCount::X1, supported),
+            vk::SampleCountFlags::TYPE_1
+        );
+        assert_eq!(
+            choose_sample_count(SampleCount::X4, vk::SampleCountFlags::TYPE_1),
+            vk::SampleCountFlags::TYPE_1
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use super::choose_sample_count;
+use crate::{AdapterInfo, AdapterType, DevicePreference, SampleCount, DEVICE_ENV_VAR};
+use anyhow::Result;
+use ash::{vk, Entry, Instance};
+use std::ffi::CStr;
+/// Order types are tried in when the preferred type is not found
+const TYPE_FALLBACK: [AdapterType; 5] = [
+    AdapterType::Discrete,
+    AdapterType::Integrated,
+    AdapterType::Virtual,
+    AdapterType::Software,
+    AdapterType::Other,
+];
+fn adapter_type(device_type: vk::PhysicalDeviceType) -> AdapterType {
+    match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => AdapterType::Discrete,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => AdapterType::Integrated,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => AdapterType::Virtual,
+        vk::PhysicalDeviceType::CPU => AdapterType::Software,
+        _ => AdapterType::Other,
+    }
+}
+/// Describes `physical_device`, `index` is its position in `enumerate_physical_devices`
+pub fn adapter_info(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    index: usize,
+) -> AdapterInfo {
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    let limits = properties.limits;
+    let max_sample_count = match choose_sample_count(
+        SampleCount::X8,
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts,
+    ) {
+        vk::SampleCountFlags::TYPE_8 => SampleCount::X8,
+        vk::SampleCountFlags::TYPE_4 => SampleCount::X4,
+        vk::SampleCountFlags::TYPE_2 => SampleCount::X2,
+        _ => SampleCount::X1,
+    };
+    AdapterInfo {
+        index,
+        name: unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned(),
+        adapter_type: adapter_type(properties.device_type),
+        max_texture_size: limits.max_image_dimension2_d,
+        max_push_constant_size: limits.max_push_constants_size,
+        max_uniform_buffer_size: limits.max_uniform_buffer_range,
+        max_storage_buffer_size: limits.max_storage_buffer_range,
+        max_sample_count,
+    }
+}
+/// Lists every GPU Vulkan can use, including ones unable to present to a window
+pub fn list_adapters() -> Result<Vec<AdapterInfo>> {
+    let entry = unsafe { Entry::load() }?;
+    let app_info = vk::ApplicationInfo::builder().api_version(vk::make_api_version(0, 1, 0, 0));
+    let create_info = vk::InstanceCreateInfo::builder().application_info(&app_info);
+    let instance = unsafe { entry.create_instance(&create_info, None) }?;
+    let adapters = unsafe { instance.enumerate_physical_devices() }.map(|devices| {
+        devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| adapter_info(&instance, *device, index))
+            .collect()
+    });
+    unsafe { instance.destroy_instance(None) };
+    Ok(adapters?)
+}
+/// Preference set with `DEVICE_ENV_VAR`, if any
+pub fn preference_override() -> Option<DevicePreference> {
+    std::env::var(DEVICE_ENV_VAR)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| value.parse().unwrap())
+}
+/// Position in `adapters` of the adapter to use, None if nothing matches
+pub fn choose_adapter(preference: &DevicePreference, adapters: &[AdapterInfo]) -> Option<usize> {
+    let by_type = |preferred: AdapterType| {
+        (0..adapters.len()).min_by_key(|i| {
+            let adapter_type = adapters[*i].adapter_type;
+            let rank = if adapter_type == preferred {
+                0
+            } else {
+                1 + TYPE_FALLBACK
+                    .iter()
+                    .position(|t| *t == adapter_type)
+                    .unwrap()
+            };
+            (rank, *i)
+        })
+    };
+    match preference {
+        DevicePreference::Discrete => by_type(AdapterType::Discrete),
+        DevicePreference::Integrated => by_type(AdapterType::Integrated),
+        DevicePreference::Software => by_type(AdapterType::Software),
+        DevicePreference::Name(name) => {
+            let name = name.to_lowercase();
+            adapters
+                .iter()
+                .position(|adapter| adapter.name.to_lowercase().contains(&name))
+        }
+        DevicePreference::Index(index) => {
+            adapters.iter().position(|adapter| adapter.index == *index)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn adapter(index: usize, name: &str, adapter_type: AdapterType) -> AdapterInfo {
+        AdapterInfo {
+            index,
+            name: name.to_string(),
+            adapter_type,
+            max_texture_size: 4096,
+            max_push_constant_size: 128,
+            max_uniform_buffer_size: 65536,
+            max_storage_buffer_size: 1 << 27,
+            max_sample_count: SampleCount::X4,
+        }
+    }
+    #[test]
+    fn chooses_preferred_adapter() {
+        let adapters = [
+            adapter(0, "Intel(R) UHD Graphics", AdapterType::Integrated),
+            adapter(2, "llvmpipe (LLVM 15.0.7, 256 bits)", AdapterType::Software),
+            adapter(
+                3,
+                "NVIDIA GeForce RTX 3060 Laptop GPU",
+                AdapterType::Discrete,
+            ),
+        ];
+        let choose = |preference: &str| choose_adapter(&preference.parse().unwrap(), &adapters);
+        assert_eq!(choose("discrete"), Some(2));
+        assert_eq!(choose("Integrated"), Some(0));
+        assert_eq!(choose("software"), Some(1));
+        assert_eq!(choose("geforce"), Some(2));
+        assert_eq!(choose("3"), Some(2));
+        assert_eq!(choose("1"), None);
+        assert_eq!(choose("radeon"), None);
+        // falls back to the best other type
+        assert_eq!(
+            choose_adapter(&DevicePreference::Discrete, &adapters[..2]),
+            Some(0)
+        );
+        assert_eq!(
+            choose_adapter(
+                &DevicePreference::Software,
+                &[adapters[0].clone(), adapters[2].clone()]
+            ),
+            Some(1)
+        );
+        assert_eq!(choose_adapter(&DevicePreference::Discrete, &[]), None);
+    }
+}
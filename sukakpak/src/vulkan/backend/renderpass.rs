@@ -1,15 +1,16 @@
 use super::{
-    CommandPool, Core, FrameBufferTarget, Framebuffer, IndexBufferAllocation,
-    VertexBufferAllocation,
+    CommandPool, Core, DrawPipelines, FrameBufferTarget, Framebuffer, GeometryBinding,
+    GraphicsPipeline,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use ash::{vk, Device};
 use generational_arena::Index as ArenaIndex;
 use nalgebra::Vector2;
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 mod semaphore_buffer;
 use free_list::FreeList;
 use semaphore_buffer::SemaphoreBuffer;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ClearOp {
     ClearColor,
     DoNotClear,
@@ -20,6 +21,10 @@ pub enum ResourceId {
     Mesh(ArenaIndex),
     UserTexture(ArenaIndex),
     Framebuffer(ArenaIndex),
+    /// copy of a uniform or storage buffer
+    Buffer(ArenaIndex, usize),
+    /// block of the geometry heap
+    GeometryBlock(ArenaIndex),
 }
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum TextureId {
@@ -44,12 +49,18 @@ impl RenderMeshIds {
         ]
     }
 }
-pub struct RenderMesh<'a> {
+pub struct RenderMesh {
     pub ids: RenderMeshIds,
-    //pub uniform_data: HashMap<String, &'a [u8]>,
     pub push: Vec<u8>,
-    pub vertex_buffer: &'a VertexBufferAllocation,
-    pub index_buffer: &'a IndexBufferAllocation,
+    /// binding and location of each vertex stream
+    pub vertex_buffers: Vec<(u32, GeometryBinding)>,
+    pub index_buffer: GeometryBinding,
+    pub index_type: vk::IndexType,
+    pub num_indices: u32,
+    /// pipelines for the mesh's vertex layout and topology
+    pub pipelines: DrawPipelines,
+    /// buffer copies and geometry blocks read by the draw
+    pub buffer_ids: Vec<ResourceId>,
 }
 
 #[derive(Clone, Copy)]
@@ -72,6 +83,10 @@ impl RenderpassGarbageCollector {
             self.mesh_freelist.push(item, renderpass_id);
         }
     }
+    /// Marks a single resource as used in given renderpass
+    pub fn push_resource(&mut self, id: ResourceId, renderpass_id: u32) {
+        self.mesh_freelist.push(id, renderpass_id);
+    }
     /// returns true if resource is currently in use
     pub fn is_resource_used(&self, res: &ResourceId) -> bool {
         self.mesh_freelist.is_used(res)
@@ -103,12 +118,24 @@ pub struct RenderPass {
     garbage_collector: RenderpassGarbageCollector,
     image_index: Option<(ImageIndex, RenderpassId)>,
     highest_renderpass_id: RenderpassId,
+    /// clear op of the current renderpass and the pipeline bound in it
+    bound_pipeline: (ClearOp, vk::Pipeline),
+    /// layout and descriptor sets bound by the last draw
+    bound_descriptor_sets: (vk::PipelineLayout, Vec<vk::DescriptorSet>),
+    /// image of the last frame sent to the screen
+    last_image_index: Option<ImageIndex>,
+    /// images of submitted frames that may still be running, oldest first
+    in_flight: VecDeque<ImageIndex>,
+    max_frames_in_flight: usize,
+    /// debug groups opened in the current frame and not yet closed
+    open_debug_groups: usize,
 }
 impl RenderPass {
     pub fn new(
         core: &mut Core,
         command_pool: &CommandPool,
         framebuffer_target: &FrameBufferTarget,
+        max_frames_in_flight: usize,
     ) -> Self {
         let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::builder()
             .command_buffer_count(framebuffer_target.framebuffers.len() as u32)
@@ -157,44 +184,98 @@ impl RenderPass {
             garbage_collector: Default::default(),
             highest_renderpass_id: 0,
             image_index: None,
+            bound_pipeline: (ClearOp::ClearColor, vk::Pipeline::null()),
+            bound_descriptor_sets: (vk::PipelineLayout::null(), vec![]),
+            last_image_index: None,
+            in_flight: VecDeque::new(),
+            max_frames_in_flight: max_frames_in_flight.max(1),
+            open_debug_groups: 0,
+        }
+    }
+    /// Opens a labeled group of commands in the current frame, groups still open when
+    /// the frame is submitted are closed
+    pub fn push_debug_group(&mut self, core: &mut Core, label: &str) -> Result<()> {
+        let (image_index, _renderpass_id) = self
+            .image_index
+            .ok_or_else(|| anyhow!("debug groups can only be pushed while rendering a frame"))?;
+        core.begin_label(self.buffers[image_index as usize].command_buffer, label)?;
+        self.open_debug_groups += 1;
+        Ok(())
+    }
+    /// Closes the most recently opened debug group
+    pub fn pop_debug_group(&mut self, core: &mut Core) -> Result<()> {
+        match self.image_index {
+            Some((image_index, _renderpass_id)) if self.open_debug_groups > 0 => {
+                core.end_label(self.buffers[image_index as usize].command_buffer);
+                self.open_debug_groups -= 1;
+                Ok(())
+            }
+            _ => Err(anyhow!("no debug group to pop")),
         }
     }
+    /// Draws mesh to `framebuffer` with `pipeline`, which must be one of the framebuffer's
+    /// pipelines
     pub fn draw_mesh(
         &mut self,
         core: &mut Core,
         framebuffer: &Framebuffer,
+        pipeline: &GraphicsPipeline,
         descriptor_sets: &[vk::DescriptorSet],
         screen_dimensions: Vector2<u32>,
         mesh: RenderMesh,
     ) -> Result<()> {
         if let Some((image_index, renderpass_id)) = self.image_index {
             self.garbage_collector.push(mesh.ids, renderpass_id);
+            for id in mesh.buffer_ids {
+                self.garbage_collector.push_resource(id, renderpass_id);
+            }
             unsafe {
-                core.device.cmd_bind_vertex_buffers(
-                    self.buffers[image_index as usize].command_buffer,
-                    0,
-                    &[mesh.vertex_buffer.buffer],
-                    &[0],
-                );
+                let (clear_op, bound_pipeline) = self.bound_pipeline;
+                let vk_pipeline = match clear_op {
+                    ClearOp::ClearColor => mesh.pipelines.clear,
+                    ClearOp::DoNotClear => mesh.pipelines.load,
+                };
+                if vk_pipeline != bound_pipeline {
+                    core.device.cmd_bind_pipeline(
+                        self.buffers[image_index as usize].command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        vk_pipeline,
+                    );
+                    self.bound_pipeline = (clear_op, vk_pipeline);
+                }
+                for (binding, vertex_buffer) in mesh.vertex_buffers.iter() {
+                    core.device.cmd_bind_vertex_buffers(
+                        self.buffers[image_index as usize].command_buffer,
+                        *binding,
+                        &[vertex_buffer.buffer],
+                        &[vertex_buffer.offset],
+                    );
+                }
                 core.device.cmd_bind_index_buffer(
                     self.buffers[image_index as usize].command_buffer,
                     mesh.index_buffer.buffer,
-                    0,
-                    vk::IndexType::UINT32,
-                );
-
-                core.device.cmd_bind_descriptor_sets(
-                    self.buffers[image_index as usize].command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    framebuffer.pipeline.pipeline_layout,
-                    0,
-                    descriptor_sets,
-                    &[],
+                    mesh.index_buffer.offset,
+                    mesh.index_type,
                 );
+                // draws sorted by texture share descriptor sets
+                if self.bound_descriptor_sets.0 != pipeline.pipeline_layout
+                    || self.bound_descriptor_sets.1 != descriptor_sets
+                {
+                    core.device.cmd_bind_descriptor_sets(
+                        self.buffers[image_index as usize].command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline.pipeline_layout,
+                        0,
+                        descriptor_sets,
+                        &[],
+                    );
+                    self.bound_descriptor_sets =
+                        (pipeline.pipeline_layout, descriptor_sets.to_vec());
+                }
                 if !mesh.push.is_empty() {
                     core.device.cmd_push_constants(
                         self.buffers[image_index as usize].command_buffer,
-                        framebuffer.pipeline.pipeline_layout,
+                        pipeline.pipeline_layout,
                         vk::ShaderStageFlags::VERTEX,
                         0,
                         &mesh.push,
@@ -203,7 +284,7 @@ impl RenderPass {
 
                 core.device.cmd_draw_indexed(
                     self.buffers[image_index as usize].command_buffer,
-                    mesh.index_buffer.num_indices() as u32,
+                    mesh.num_indices,
                     1,
                     0,
                     0,
@@ -214,7 +295,14 @@ impl RenderPass {
         } else {
             self.acquire_next_image(core)?;
             self.begin_renderpass(core, framebuffer, ClearOp::DoNotClear)?;
-            self.draw_mesh(core, framebuffer, descriptor_sets, screen_dimensions, mesh)?;
+            self.draw_mesh(
+                core,
+                framebuffer,
+                pipeline,
+                descriptor_sets,
+                screen_dimensions,
+                mesh,
+            )?;
             Ok(())
         }
     }
@@ -272,14 +360,9 @@ impl RenderPass {
                 &renderpass_info,
                 vk::SubpassContents::INLINE,
             );
-            core.device.cmd_bind_pipeline(
-                self.buffers[image_index as usize].command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                match clear_op {
-                    ClearOp::ClearColor => framebuffer.pipeline.clear_pipeline.graphics_pipeline,
-                    ClearOp::DoNotClear => framebuffer.pipeline.load_pipeline.graphics_pipeline,
-                },
-            );
+            // pipelines depend on the vertex layout so they are bound by the first draw
+            self.bound_pipeline = (clear_op, vk::Pipeline::null());
+            self.bound_descriptor_sets = (vk::PipelineLayout::null(), vec![]);
             self.buffers[image_index as usize].renderpass_id = rendeprass_id;
             Ok(())
         }
@@ -299,6 +382,10 @@ impl RenderPass {
             unsafe {
                 core.device
                     .cmd_end_render_pass(self.buffers[image_index as usize].command_buffer);
+                for _ in 0..self.open_debug_groups {
+                    core.end_label(self.buffers[image_index as usize].command_buffer);
+                }
+                self.open_debug_groups = 0;
                 core.device
                     .end_command_buffer(self.buffers[image_index as usize].command_buffer)?;
 
@@ -326,6 +413,7 @@ impl RenderPass {
                     self.fences[image_index as usize].1,
                 )?;
             }
+            self.in_flight.push_back(image_index);
             if let Some(free_id) = self.fences[image_index as usize].0 {
                 let meshes = self.garbage_collector.finish_renderpass(free_id);
                 self.fences[image_index as usize].0 = Some(renderpass_id);
@@ -343,6 +431,11 @@ impl RenderPass {
     pub fn free_mesh(&mut self, mesh: RenderMeshIds) {
         self.garbage_collector.try_free(mesh)
     }
+    /// Marks a resource for freeing, it is returned by `submit_draw` once no inprogress
+    /// renderpass uses it
+    pub fn free_resource(&mut self, resource: ResourceId) {
+        self.garbage_collector.mesh_freelist.try_free(resource)
+    }
     /// checks if resource is used. if it is returns true
     pub fn is_resource_used(&self, resource: &ResourceId) -> bool {
         self.garbage_collector.is_resource_used(resource)
@@ -350,40 +443,84 @@ impl RenderPass {
     pub fn swap_framebuffer(&mut self, core: &mut Core) -> std::result::Result<(), vk::Result> {
         if let Some((image_index, _rendeprass_id)) = self.image_index {
             let indices = [image_index];
-            let swapchain = [core.swapchain];
             let wait_semaphore = [self.buffers[image_index as usize]
                 .semaphore_buffer
                 .last_semaphore()];
-            let present_info = vk::PresentInfoKHR::builder()
-                .wait_semaphores(&wait_semaphore)
-                .swapchains(&swapchain)
-                .image_indices(&indices);
-            unsafe {
-                core.swapchain_loader
-                    .queue_present(core.present_queue, &present_info)?;
+            if let Some(presentation) = core.presentation.as_ref() {
+                let swapchain = [presentation.swapchain];
+                let present_info = vk::PresentInfoKHR::builder()
+                    .wait_semaphores(&wait_semaphore)
+                    .swapchains(&swapchain)
+                    .image_indices(&indices);
+                unsafe {
+                    presentation
+                        .swapchain_loader
+                        .queue_present(core.present_queue, &present_info)?;
+                }
+            } else {
+                // stands in for presenting so the last semaphore is unsignaled
+                let wait_stages = [vk::PipelineStageFlags::BOTTOM_OF_PIPE];
+                let submit_info = *vk::SubmitInfo::builder()
+                    .wait_semaphores(&wait_semaphore)
+                    .wait_dst_stage_mask(&wait_stages);
+                unsafe {
+                    core.device.queue_submit(
+                        core.present_queue,
+                        &[submit_info],
+                        vk::Fence::null(),
+                    )?;
+                }
             }
             self.buffers[image_index as usize].semaphore_buffer.reset();
             self.image_index = None;
+            self.last_image_index = Some(image_index);
             Ok(())
         } else {
             self.acquire_next_image(core)?;
             self.swap_framebuffer(core)
         }
     }
-    /// aquires new image index and populates self.image_index
+    /// aquires new image index and populates self.image_index. Waits until fewer than
+    /// `max_frames_in_flight` frames are running and the image's last frame is done.
     pub fn acquire_next_image(&mut self, core: &mut Core) -> std::result::Result<(), vk::Result> {
+        while self.in_flight.len() >= self.max_frames_in_flight {
+            let oldest = self.in_flight.pop_front().unwrap();
+            unsafe {
+                core.device
+                    .wait_for_fences(&[self.fences[oldest as usize].1], true, u64::MAX)?;
+            }
+        }
         let semaphore_create_info = vk::SemaphoreCreateInfo::builder().build();
         let image_available_semaphore =
             unsafe { core.device.create_semaphore(&semaphore_create_info, None) }
                 .expect("failed to build semaphore");
-        let (image_index, _) = unsafe {
-            core.swapchain_loader.acquire_next_image(
-                core.swapchain,
-                u64::MAX,
-                image_available_semaphore,
-                vk::Fence::null(),
-            )
-        }?;
+        let image_index = if let Some(presentation) = core.presentation.as_ref() {
+            unsafe {
+                presentation.swapchain_loader.acquire_next_image(
+                    presentation.swapchain,
+                    u64::MAX,
+                    image_available_semaphore,
+                    vk::Fence::null(),
+                )
+            }?
+            .0
+        } else {
+            // offscreen images are used in turn and nothing signals the semaphore on acquire
+            let signal_semaphores = [image_available_semaphore];
+            let submit_info = *vk::SubmitInfo::builder().signal_semaphores(&signal_semaphores);
+            unsafe {
+                core.device
+                    .queue_submit(core.present_queue, &[submit_info], vk::Fence::null())?;
+            }
+            self.last_image_index
+                .map(|index| (index + 1) % self.buffers.len() as u32)
+                .unwrap_or(0)
+        };
+        // the command buffer of the image is reused for this frame
+        unsafe {
+            core.device
+                .wait_for_fences(&[self.fences[image_index as usize].1], true, u64::MAX)?;
+        }
         self.highest_renderpass_id += 1;
         // freeing semaphore
         unsafe {
@@ -400,6 +537,10 @@ impl RenderPass {
         self.image_index = Some((image_index, self.highest_renderpass_id));
         Ok(())
     }
+    /// Image of the last frame sent to the screen, None if no frame is done
+    pub fn last_image_index(&self) -> Option<usize> {
+        self.last_image_index.map(|index| index as usize)
+    }
     pub fn get_image_index(&mut self, core: &mut Core) -> Result<usize> {
         if let Some((idx, _renderpass_id)) = self.image_index {
             Ok(idx as usize)
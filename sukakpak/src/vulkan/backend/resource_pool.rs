@@ -1,5 +1,6 @@
-use super::{CommandPool, Core, ShaderDescription, VertexLayout};
-use anyhow::Result;
+use super::{CommandPool, Core, ShaderDescription};
+use crate::{BufferKind, IndexWidth, VertexStream};
+use anyhow::{anyhow, Result};
 use ash::{vk, Device, Instance};
 use gpu_allocator::{
     vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator, AllocatorCreateDesc},
@@ -8,14 +9,25 @@ use gpu_allocator::{
 use image::RgbaImage;
 use nalgebra::Vector2;
 mod descriptor_pool;
+mod geometry_heap;
+mod staging;
 use descriptor_pool::DescriptorPool;
 pub use descriptor_pool::{DescriptorDesc, DescriptorName};
-use std::mem::{size_of, ManuallyDrop};
+use geometry_heap::GeometryHeap;
+pub use geometry_heap::{GeometryBinding, GeometryRange};
+use staging::UploadQueue;
+use std::mem::ManuallyDrop;
 pub struct ResourcePool {
     allocator: ManuallyDrop<Allocator>,
+    uploads: UploadQueue,
+    geometry: GeometryHeap,
     texture_descriptor_pool: DescriptorPool,
     sampler_descriptor_pool: DescriptorPool,
+    uniform_descriptor_pool: DescriptorPool,
+    storage_descriptor_pool: DescriptorPool,
 }
+/// Name of the only descriptor in the uniform and storage buffer pools
+const BUFFER_DESCRIPTOR: &str = "buffer";
 impl ResourcePool {
     pub fn new(core: &Core, shader: &ShaderDescription) -> Result<Self> {
         let texture_layouts = shader
@@ -42,18 +54,20 @@ impl ResourcePool {
                 )
             })
             .collect();
+        let mut allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: core.instance.clone(),
+            device: core.device.clone(),
+            physical_device: core.physical_device,
+            buffer_device_address: false,
+            debug_settings: Default::default(),
+            allocation_sizes: AllocationSizes::default(),
+        })
+        .expect("failed to create allocator");
+        let uploads = UploadQueue::new(core, &mut allocator)?;
         Ok(Self {
-            allocator: ManuallyDrop::new(
-                Allocator::new(&AllocatorCreateDesc {
-                    instance: core.instance.clone(),
-                    device: core.device.clone(),
-                    physical_device: core.physical_device,
-                    buffer_device_address: false,
-                    debug_settings: Default::default(),
-                    allocation_sizes: AllocationSizes::default(),
-                })
-                .expect("failed to create allocator"),
-            ),
+            allocator: ManuallyDrop::new(allocator),
+            uploads,
+            geometry: GeometryHeap::default(),
             texture_descriptor_pool: DescriptorPool::new(
                 core,
                 vk::DescriptorType::SAMPLED_IMAGE,
@@ -64,107 +78,141 @@ impl ResourcePool {
                 vk::DescriptorType::SAMPLER,
                 &sampler_layouts,
             )?,
+            uniform_descriptor_pool: Self::buffer_descriptor_pool(
+                core,
+                vk::DescriptorType::UNIFORM_BUFFER,
+            )?,
+            storage_descriptor_pool: Self::buffer_descriptor_pool(
+                core,
+                vk::DescriptorType::STORAGE_BUFFER,
+            )?,
         })
     }
-    pub fn allocate_vertex_buffer(
+    /// Buffers are alone in their descriptor set at binding 0
+    fn buffer_descriptor_pool(
+        core: &Core,
+        descriptor_type: vk::DescriptorType,
+    ) -> Result<DescriptorPool> {
+        let layouts = [(
+            BUFFER_DESCRIPTOR.to_string(),
+            DescriptorDesc {
+                layout_binding: *vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_count(1)
+                    .descriptor_type(descriptor_type)
+                    .stage_flags(
+                        vk::ShaderStageFlags::FRAGMENT
+                            | vk::ShaderStageFlags::VERTEX
+                            | vk::ShaderStageFlags::COMPUTE,
+                    ),
+            },
+        )]
+        .iter()
+        .cloned()
+        .collect();
+        DescriptorPool::new(core, descriptor_type, &layouts)
+    }
+    fn buffer_pool(&mut self, kind: BufferKind) -> &mut DescriptorPool {
+        match kind {
+            BufferKind::Uniform => &mut self.uniform_descriptor_pool,
+            BufferKind::Storage => &mut self.storage_descriptor_pool,
+        }
+    }
+    /// Allocates a uniform or storage buffer of `data.len()` bytes with `copies` copies, each
+    /// with its own descriptor set. Every copy starts out holding `data`.
+    pub fn allocate_buffer(
         &mut self,
         core: &mut Core,
-        mesh: Vec<u8>,
-        layout: VertexLayout,
-    ) -> Result<VertexBufferAllocation> {
-        let (binding_description, input_description) = (
-            *vk::VertexInputBindingDescription::builder()
-                .binding(0)
-                .input_rate(vk::VertexInputRate::VERTEX)
-                .stride(layout.components.iter().map(|c| c.size()).sum::<usize>() as u32),
-            layout
-                .components
-                .iter()
-                .enumerate()
-                .map(|(i, comp)| {
-                    *vk::VertexInputAttributeDescription::builder()
-                        .binding(0)
-                        .location(i as u32)
-                        .format(comp.into())
-                })
-                .collect(),
-        );
-        let buffer_create_info = vk::BufferCreateInfo::builder()
-            .size(mesh.len() as u64)
-            .usage(vk::BufferUsageFlags::VERTEX_BUFFER)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE);
-        let buffer = unsafe { core.device.create_buffer(&buffer_create_info, None)? };
-        let requirements = unsafe { core.device.get_buffer_memory_requirements(buffer) };
-        let allocation = self.allocator.allocate(&AllocationCreateDesc {
-            name: "vertex buffer",
-            requirements,
-            location: MemoryLocation::CpuToGpu,
-            linear: true,
-            allocation_scheme: AllocationScheme::DedicatedBuffer(buffer),
-        })?;
+        kind: BufferKind,
+        data: &[u8],
+        copies: usize,
+    ) -> Result<BufferAllocation> {
+        let mut allocation = BufferAllocation {
+            kind,
+            size: data.len(),
+            copies: vec![],
+            current: 0,
+        };
+        for _ in 0..copies {
+            let copy = self.add_buffer_copy(core, &mut allocation)?;
+            allocation.write(copy, data);
+        }
+        Ok(allocation)
+    }
+    /// Adds a copy to the buffer and returns its index, the contents of the copy are undefined
+    pub fn add_buffer_copy(
+        &mut self,
+        core: &mut Core,
+        allocation: &mut BufferAllocation,
+    ) -> Result<usize> {
+        let (usage, descriptor_type) = match allocation.kind {
+            BufferKind::Uniform => (
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::DescriptorType::UNIFORM_BUFFER,
+            ),
+            BufferKind::Storage => (
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::DescriptorType::STORAGE_BUFFER,
+            ),
+        };
+        let (buffer, buffer_allocation) = self.create_buffer(
+            core,
+            allocation.size as u64,
+            usage,
+            vk::SharingMode::EXCLUSIVE,
+            MemoryLocation::CpuToGpu,
+        )?;
+        let descriptor_set = unsafe {
+            self.buffer_pool(allocation.kind)
+                .allocate_descriptor_set(core, BUFFER_DESCRIPTOR)
+        }?[0];
+        let buffer_info = [*vk::DescriptorBufferInfo::builder()
+            .buffer(buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE)];
+        let descriptor_write = [*vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(descriptor_type)
+            .buffer_info(&buffer_info)];
         unsafe {
-            core.device
-                .bind_buffer_memory(buffer, allocation.memory(), allocation.offset())?;
-            let mesh_len = mesh.len();
-            std::ptr::copy_nonoverlapping(
-                mesh.as_ptr() as *const std::ffi::c_void,
-                allocation
-                    .mapped_ptr()
-                    .expect("failed to map mesh ptr")
-                    .as_ptr(),
-                mesh_len,
-            );
+            core.device.update_descriptor_sets(&descriptor_write, &[]);
         }
-        Ok(VertexBufferAllocation {
-            allocation: Some(allocation),
+        allocation.copies.push(BufferCopy {
             buffer,
-            binding_description,
-            input_description,
+            allocation: Some(buffer_allocation),
+            descriptor_set,
+        });
+        Ok(allocation.copies.len() - 1)
+    }
+    /// Allocates the vertex buffer for one stream of a mesh, bound to the stream's binding
+    pub fn allocate_vertex_buffer(
+        &mut self,
+        core: &mut Core,
+        stream: &VertexStream,
+    ) -> Result<VertexBufferAllocation> {
+        Ok(VertexBufferAllocation {
+            range: self.allocate_geometry(core, &stream.vertices)?,
+            binding: stream.binding,
         })
     }
     pub fn free_allocation(&mut self, allocation: Allocation) -> Result<()> {
         self.allocator.free(allocation)?;
         Ok(())
     }
+    /// Allocates an index buffer, indices are stored as `u16` if they all fit
     pub fn allocate_index_buffer(
         &mut self,
         core: &mut Core,
-        command_pool: &mut CommandPool,
-        indicies: Vec<u32>,
+        indicies: &[u32],
     ) -> Result<IndexBufferAllocation> {
-        let buffer_size = indicies.len() * size_of::<u32>();
-        let (staging_buffer, staging_memory) = self.create_buffer(
-            core,
-            buffer_size as u64,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::SharingMode::EXCLUSIVE,
-            MemoryLocation::CpuToGpu,
-        )?;
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                indicies.as_ptr() as *const std::ffi::c_void,
-                staging_memory
-                    .mapped_ptr()
-                    .expect("failed to map memory")
-                    .as_ptr(),
-                buffer_size,
-            )
-        }
-        let (buffer, allocation) = self.create_buffer(
-            core,
-            buffer_size as u64,
-            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
-            vk::SharingMode::EXCLUSIVE,
-            MemoryLocation::GpuOnly,
-        )?;
-        core.copy_buffer(command_pool, &staging_buffer, &buffer, buffer_size as u64);
-        unsafe { core.device.destroy_buffer(staging_buffer, None) }
-        self.allocator.free(staging_memory)?;
-
+        let index_width = IndexWidth::for_indices(indicies);
+        let index_bytes = index_width.index_bytes(indicies);
         Ok(IndexBufferAllocation {
-            buffer,
-            allocation: Some(allocation),
-            buffer_size,
+            range: self.allocate_geometry(core, &index_bytes)?,
+            buffer_size: index_bytes.len(),
+            index_width,
         })
     }
     pub fn create_buffer(
@@ -201,6 +249,23 @@ impl ResourcePool {
         format: vk::Format,
         usage: vk::ImageUsageFlags,
         dimensions: Vector2<u32>,
+    ) -> Result<(vk::Image, Allocation)> {
+        self.new_multisampled_image(
+            core,
+            format,
+            usage,
+            dimensions,
+            vk::SampleCountFlags::TYPE_1,
+        )
+    }
+    /// Builds an image with `samples` samples per pixel
+    pub fn new_multisampled_image(
+        &mut self,
+        core: &mut Core,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        dimensions: Vector2<u32>,
+        samples: vk::SampleCountFlags,
     ) -> Result<(vk::Image, Allocation)> {
         let image_create_info = vk::ImageCreateInfo::builder()
             .image_type(vk::ImageType::TYPE_2D)
@@ -216,7 +281,7 @@ impl ResourcePool {
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .usage(usage)
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(vk::SampleCountFlags::TYPE_1);
+            .samples(samples);
         let image = unsafe { core.device.create_image(&image_create_info, None) }?;
         let requirements = unsafe { core.device.get_image_memory_requirements(image) };
         let allocation = self.allocator.allocate(&AllocationCreateDesc {
@@ -232,60 +297,25 @@ impl ResourcePool {
         };
         Ok((image, allocation))
     }
+    /// Allocates a sampled texture, the image is uploaded with the next upload flush
     pub fn allocate_texture(
         &mut self,
         core: &mut Core,
-        command_pool: &mut CommandPool,
         image_data: &RgbaImage,
     ) -> Result<TextureAllocation> {
-        let image_len = image_data.as_raw().len();
-        let (buffer, transfer_allocation) = self.create_buffer(
-            core,
-            image_len as u64,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::SharingMode::EXCLUSIVE,
-            MemoryLocation::CpuToGpu,
-        )?;
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                image_data.as_raw().as_ptr() as *const std::ffi::c_void,
-                transfer_allocation
-                    .mapped_ptr()
-                    .expect("failed to map texture pointer")
-                    .as_ptr(),
-                image_len,
-            );
-        }
         let (image, image_allocation) = self.new_image(
             core,
             vk::Format::R8G8B8A8_SRGB,
             vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
             Vector2::new(image_data.width(), image_data.height()),
         )?;
-        TextureAllocation::transition_image_layout(
-            core,
-            command_pool,
-            &image,
-            vk::ImageAspectFlags::COLOR,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        );
-        TextureAllocation::copy_buffer_image(
+        self.upload_image(
             core,
-            command_pool,
+            image_data.as_raw(),
             image,
-            buffer,
             image_data.width(),
             image_data.height(),
-        );
-        TextureAllocation::transition_image_layout(
-            core,
-            command_pool,
-            &image,
-            vk::ImageAspectFlags::COLOR,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-        );
+        )?;
         let view_info = vk::ImageViewCreateInfo::builder()
             .image(image)
             .view_type(vk::ImageViewType::TYPE_2D)
@@ -331,15 +361,48 @@ impl ResourcePool {
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
         )?;
         Ok(TextureAllocation {
-            buffer,
             descriptor_sets,
             image,
             image_allocation,
             image_view,
             sampler,
-            transfer_allocation,
         })
     }
+    /// Reads back an rgba8 color image in the general layout
+    pub fn read_image(
+        &mut self,
+        core: &mut Core,
+        command_pool: &mut CommandPool,
+        image: vk::Image,
+        dimensions: Vector2<u32>,
+    ) -> Result<RgbaImage> {
+        let image_len = dimensions.x as usize * dimensions.y as usize * 4;
+        let (buffer, allocation) = self.create_buffer(
+            core,
+            image_len as u64,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::SharingMode::EXCLUSIVE,
+            MemoryLocation::GpuToCpu,
+        )?;
+        TextureAllocation::copy_image_buffer(
+            core,
+            command_pool,
+            image,
+            buffer,
+            dimensions.x,
+            dimensions.y,
+        );
+        let data = allocation
+            .mapped_slice()
+            .ok_or_else(|| anyhow!("failed to map readback buffer"))?[..image_len]
+            .to_vec();
+        unsafe {
+            core.device.destroy_buffer(buffer, None);
+        }
+        self.free_allocation(allocation)?;
+        RgbaImage::from_raw(dimensions.x, dimensions.y, data)
+            .ok_or_else(|| anyhow!("readback buffer is too small"))
+    }
     pub fn get_texture_descriptor(
         &mut self,
         core: &mut Core,
@@ -397,8 +460,12 @@ impl ResourcePool {
             sampler_descriptor_set,
         })
     }
-    /// Allocates descriptor sets. pool provided by descriptor pool
-    pub fn get_descriptor_set_layouts(&self) -> Vec<vk::DescriptorSetLayout> {
+    /// Layouts of the descriptor sets used by `shader`, the texture and sampler sets followed
+    /// by one set per buffer
+    pub fn get_descriptor_set_layouts(
+        &self,
+        shader: &ShaderDescription,
+    ) -> Vec<vk::DescriptorSetLayout> {
         self.texture_descriptor_pool
             .get_descriptor_layouts()
             .iter()
@@ -406,11 +473,29 @@ impl ResourcePool {
             .map(|(l1, l2)| [l1, l2])
             .flatten()
             .copied()
+            .chain(shader.buffers.iter().map(|buffer| {
+                match buffer.kind {
+                    BufferKind::Uniform => &self.uniform_descriptor_pool,
+                    BufferKind::Storage => &self.storage_descriptor_pool,
+                }
+                .get_descriptor_layout(BUFFER_DESCRIPTOR)
+                .expect("buffer layout not built")
+            }))
             .collect()
     }
+    /// Layout of a storage buffer's descriptor set, compute shaders have one set per buffer
+    pub fn get_storage_descriptor_layout(&self) -> vk::DescriptorSetLayout {
+        self.storage_descriptor_pool
+            .get_descriptor_layout(BUFFER_DESCRIPTOR)
+            .expect("buffer layout not built")
+    }
     pub fn free(&mut self, core: &mut Core) -> Result<()> {
+        self.free_geometry_heap(core)?;
+        self.free_uploads(core)?;
         self.texture_descriptor_pool.free(core)?;
         self.sampler_descriptor_pool.free(core)?;
+        self.uniform_descriptor_pool.free(core)?;
+        self.storage_descriptor_pool.free(core)?;
         unsafe {
             ManuallyDrop::drop(&mut self.allocator);
         }
@@ -423,37 +508,117 @@ pub struct TextureDescriptorSets {
     pub sampler_descriptor_set: vk::DescriptorSet,
 }
 pub struct IndexBufferAllocation {
-    pub buffer: vk::Buffer,
-    pub allocation: Option<Allocation>,
+    pub range: GeometryRange,
     pub buffer_size: usize,
+    pub index_width: IndexWidth,
 }
 impl IndexBufferAllocation {
     pub fn num_indices(&self) -> usize {
-        self.buffer_size / size_of::<u32>()
+        self.buffer_size / self.index_width.size()
     }
-    pub fn free(mut self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
-        resource_pool
-            .allocator
-            .free(self.allocation.take().expect("index buffer already freed"))?;
-        unsafe {
-            core.device.destroy_buffer(self.buffer, None);
+    pub fn index_type(&self) -> vk::IndexType {
+        match self.index_width {
+            IndexWidth::U16 => vk::IndexType::UINT16,
+            IndexWidth::U32 => vk::IndexType::UINT32,
         }
-        Ok(())
+    }
+    pub fn set_name(&self, core: &Core, resource_pool: &ResourcePool, name: &str) -> Result<()> {
+        resource_pool.set_geometry_name(core, &self.range, &format!("{} indices", name))
+    }
+    pub fn free(self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
+        resource_pool.free_geometry(core, self.range)
     }
 }
 pub struct VertexBufferAllocation {
-    allocation: Option<Allocation>,
-    pub buffer: vk::Buffer,
-    pub binding_description: vk::VertexInputBindingDescription,
-    pub input_description: Vec<vk::VertexInputAttributeDescription>,
+    pub range: GeometryRange,
+    pub binding: u32,
 }
 impl VertexBufferAllocation {
-    pub fn free(mut self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
-        resource_pool
-            .allocator
-            .free(self.allocation.take().expect("vertex buffer already freed"))?;
+    pub fn set_name(&self, core: &Core, resource_pool: &ResourcePool, name: &str) -> Result<()> {
+        resource_pool.set_geometry_name(
+            core,
+            &self.range,
+            &format!("{} vertices {}", name, self.binding),
+        )
+    }
+    pub fn free(self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
+        resource_pool.free_geometry(core, self.range)
+    }
+}
+struct BufferCopy {
+    buffer: vk::Buffer,
+    allocation: Option<Allocation>,
+    descriptor_set: vk::DescriptorSet,
+}
+/// Uniform or storage buffer. Updates are written to a copy that no frame in flight reads.
+pub struct BufferAllocation {
+    pub kind: BufferKind,
+    /// size in bytes
+    pub size: usize,
+    copies: Vec<BufferCopy>,
+    /// copy holding the latest data
+    pub current: usize,
+}
+impl BufferAllocation {
+    pub fn num_copies(&self) -> usize {
+        self.copies.len()
+    }
+    /// Descriptor set of the copy holding the latest data
+    pub fn descriptor_set(&self) -> vk::DescriptorSet {
+        self.copies[self.current].descriptor_set
+    }
+    fn mapped_ptr(&self, copy: usize) -> *mut u8 {
+        self.copies[copy]
+            .allocation
+            .as_ref()
+            .unwrap()
+            .mapped_ptr()
+            .expect("failed to map buffer")
+            .as_ptr() as *mut u8
+    }
+    /// Contents of the copy holding the latest data
+    pub fn read(&self) -> Vec<u8> {
+        let mut data = vec![0; self.size];
         unsafe {
-            core.device.destroy_buffer(self.buffer, None);
+            std::ptr::copy_nonoverlapping(
+                self.mapped_ptr(self.current),
+                data.as_mut_ptr(),
+                self.size,
+            );
+        }
+        data
+    }
+    /// Writes `data` to the start of `copy`, `data` must fit in the buffer
+    pub fn write(&mut self, copy: usize, data: &[u8]) {
+        assert!(data.len() <= self.size);
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.mapped_ptr(copy), data.len());
+        }
+    }
+    /// Makes `copy` the current copy, it holds the contents of the previous current copy with
+    /// the start overwritten by `data`
+    pub fn update(&mut self, copy: usize, data: &[u8]) {
+        if copy != self.current {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    self.mapped_ptr(self.current),
+                    self.mapped_ptr(copy),
+                    self.size,
+                );
+            }
+        }
+        self.write(copy, data);
+        self.current = copy;
+    }
+    pub fn free(mut self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
+        for copy in self.copies.iter_mut() {
+            unsafe {
+                resource_pool
+                    .buffer_pool(self.kind)
+                    .free_descriptor_set(core, copy.descriptor_set)?;
+                core.device.destroy_buffer(copy.buffer, None);
+            }
+            resource_pool.free_allocation(copy.allocation.take().unwrap())?;
         }
         Ok(())
     }
@@ -461,21 +626,43 @@ impl VertexBufferAllocation {
 pub struct TextureAllocation {
     sampler: vk::Sampler,
     image_view: vk::ImageView,
-    transfer_allocation: Allocation,
-    buffer: vk::Buffer,
     image: vk::Image,
     image_allocation: Allocation,
     pub descriptor_sets: TextureDescriptorSets,
 }
 impl TextureAllocation {
-    fn copy_buffer_image(
+    pub fn set_name(&self, core: &Core, name: &str) -> Result<()> {
+        core.set_object_name(self.image, name)?;
+        core.set_object_name(self.image_view, name)?;
+        core.set_object_name(self.sampler, name)
+    }
+    /// Copies a color image in the general layout, written by a renderpass,
+    /// into `buffer`
+    pub fn copy_image_buffer(
         core: &mut Core,
-        command_queue: &mut CommandPool,
+        command_pool: &mut CommandPool,
         image: vk::Image,
         buffer: vk::Buffer,
         width: u32,
         height: u32,
     ) {
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(vk::ImageLayout::GENERAL)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(
+                vk::ImageSubresourceRange::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .base_mip_level(0)
+                    .level_count(1)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            );
         let region = vk::BufferImageCopy::builder()
             .buffer_offset(0)
             .buffer_row_length(0)
@@ -495,12 +682,21 @@ impl TextureAllocation {
             })
             .build();
         unsafe {
-            let command_buffer = command_queue.create_onetime_buffer(core);
-            command_buffer.core.device.cmd_copy_buffer_to_image(
+            let command_buffer = command_pool.create_onetime_buffer(core);
+            command_buffer.core.device.cmd_pipeline_barrier(
+                command_buffer.command_buffer[0],
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier.build()],
+            );
+            command_buffer.core.device.cmd_copy_image_to_buffer(
                 command_buffer.command_buffer[0],
-                buffer,
                 image,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::GENERAL,
+                buffer,
                 &[region],
             );
         }
@@ -555,6 +751,16 @@ impl TextureAllocation {
                 vk::PipelineStageFlags::TOP_OF_PIPE,
                 vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
             )
+        } else if old_layout == vk::ImageLayout::UNDEFINED
+            && new_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        {
+            barrier.src_access_mask = vk::AccessFlags::empty();
+            barrier.dst_access_mask =
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
+            (
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )
         } else if old_layout == vk::ImageLayout::UNDEFINED && new_layout == vk::ImageLayout::GENERAL
         {
             barrier.src_access_mask = vk::AccessFlags::empty();
@@ -580,6 +786,7 @@ impl TextureAllocation {
         }
     }
     pub fn free(self, core: &mut Core, resource_pool: &mut ResourcePool) -> Result<()> {
+        resource_pool.wait_uploads(core)?;
         unsafe {
             core.device.destroy_sampler(self.sampler, None);
             core.device.destroy_image_view(self.image_view, None);
@@ -594,8 +801,6 @@ impl TextureAllocation {
                 &[self.descriptor_sets.sampler_descriptor_set],
             )?;
             core.device.destroy_image(self.image, None);
-            resource_pool.free_allocation(self.transfer_allocation)?;
-            core.device.destroy_buffer(self.buffer, None);
         }
         Ok(())
     }
@@ -91,6 +91,20 @@ impl DescriptorPool {
             ))
         }
     }
+    /// Returns a set allocated by `allocate_descriptor_set` to the pool
+    pub unsafe fn free_descriptor_set(
+        &mut self,
+        core: &mut Core,
+        descriptor_set: vk::DescriptorSet,
+    ) -> Result<()> {
+        core.device
+            .free_descriptor_sets(self.descriptor_pool, &[descriptor_set])?;
+        Ok(())
+    }
+    /// Layout of a single descriptor
+    pub fn get_descriptor_layout(&self, name: &str) -> Option<vk::DescriptorSetLayout> {
+        self.descriptors.get(name).map(|(layout, _desc)| *layout)
+    }
     pub fn get_descriptor_desc(&self, name: &str) -> Option<DescriptorDesc> {
         if let Some((_layout, desc)) = self.descriptors.get(name) {
             Some(*desc)
@@ -0,0 +1,357 @@
+use super::{Core, ResourcePool};
+use anyhow::Result;
+use ash::vk;
+use generational_arena::{Arena, Index as ArenaIndex};
+use gpu_allocator::{vulkan::Allocation, MemoryLocation};
+/// Size of the shared buffers meshes are sub-allocated from
+const BLOCK_SIZE: u64 = 8 * 1024 * 1024;
+/// Offset and size alignment of ranges, satisfies index and vertex attribute alignment
+const GEOMETRY_ALIGNMENT: u64 = 16;
+/// Blocks are only compacted once at least this fraction of them is free
+const MIN_COMPACT_FREE: f32 = 0.25;
+/// Blocks are only compacted when more of their free space than this is outside the largest
+/// free range
+const MAX_FRAGMENTATION: f32 = 0.5;
+/// Hands out ranges of a fixed size region, freed ranges merge with their free neighbours
+#[derive(Debug)]
+pub struct RangeAllocator {
+    capacity: u64,
+    /// free ranges as (offset, size), sorted by offset and never touching
+    free: Vec<(u64, u64)>,
+}
+impl RangeAllocator {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            free: vec![(0, capacity)],
+        }
+    }
+    /// Returns the offset of the first free range that fits `size` bytes, None if none fits
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let (index, offset) = self
+            .free
+            .iter()
+            .enumerate()
+            .find_map(|(index, &(start, len))| {
+                let offset = start.div_ceil(alignment) * alignment;
+                Some((index, offset)).filter(|_| offset + size <= start + len)
+            })?;
+        let (start, len) = self.free.remove(index);
+        let end = start + len;
+        let before = Some((start, offset - start)).filter(|(_, len)| *len > 0);
+        let after = Some((offset + size, end - offset - size)).filter(|(_, len)| *len > 0);
+        self.free
+            .splice(index..index, before.into_iter().chain(after));
+        Some(offset)
+    }
+    /// Returns the range of `size` bytes at `offset` to the free list
+    pub fn free(&mut self, offset: u64, size: u64) {
+        let index = self.free.partition_point(|&(start, _)| start < offset);
+        let mut range = (offset, size);
+        if index < self.free.len() && offset + size == self.free[index].0 {
+            range.1 += self.free.remove(index).1;
+        }
+        match index.checked_sub(1).map(|prev| &mut self.free[prev]) {
+            Some(prev) if prev.0 + prev.1 == offset => prev.1 += range.1,
+            _ => self.free.insert(index, range),
+        }
+    }
+    pub fn free_bytes(&self) -> u64 {
+        self.free.iter().map(|(_, len)| len).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.free_bytes() == self.capacity
+    }
+    /// Fraction of the free space outside the largest free range, 0 when the free space is
+    /// contiguous
+    pub fn fragmentation(&self) -> f32 {
+        let free_bytes = self.free_bytes();
+        if free_bytes == 0 {
+            return 0.0;
+        }
+        let largest = self.free.iter().map(|(_, len)| *len).max().unwrap();
+        1.0 - largest as f32 / free_bytes as f32
+    }
+    /// Whether enough space is free and it is split up enough that moving the live ranges
+    /// elsewhere is worth it
+    pub fn needs_compaction(&self) -> bool {
+        self.free_bytes() as f32 >= self.capacity as f32 * MIN_COMPACT_FREE
+            && self.fragmentation() > MAX_FRAGMENTATION
+    }
+}
+/// Buffer shared by the vertex and index data of many meshes
+struct Block {
+    buffer: vk::Buffer,
+    allocation: Option<Allocation>,
+    ranges: RangeAllocator,
+    /// holds a single range too large for a shared block
+    dedicated: bool,
+    /// compacted, hands out no new ranges and is freed once no frame reads it
+    retired: bool,
+}
+struct Range {
+    block: ArenaIndex,
+    offset: u64,
+    size: u64,
+}
+/// Vertex or index data of a mesh stored in the geometry heap. Compaction may move the data,
+/// so the buffer and offset are looked up when drawing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GeometryRange {
+    index: ArenaIndex,
+}
+/// Where a geometry range currently lives
+#[derive(Clone, Copy, Debug)]
+pub struct GeometryBinding {
+    pub block: ArenaIndex,
+    pub buffer: vk::Buffer,
+    pub offset: u64,
+}
+/// Sub-allocates the vertex and index buffers of meshes from large shared buffers
+#[derive(Default)]
+pub struct GeometryHeap {
+    blocks: Arena<Block>,
+    ranges: Arena<Range>,
+}
+impl ResourcePool {
+    /// Copies `data` into the geometry heap, the copy runs once uploads are flushed
+    pub fn allocate_geometry(&mut self, core: &mut Core, data: &[u8]) -> Result<GeometryRange> {
+        let size = (data.len() as u64).max(1).div_ceil(GEOMETRY_ALIGNMENT) * GEOMETRY_ALIGNMENT;
+        let (block, offset) = self.place_geometry(core, size, None)?;
+        let buffer = self.geometry.blocks[block].buffer;
+        self.upload_buffer(core, data, buffer, offset)?;
+        Ok(GeometryRange {
+            index: self.geometry.ranges.insert(Range {
+                block,
+                offset,
+                size,
+            }),
+        })
+    }
+    /// Finds room for `size` bytes outside of `exclude`, adding a block if no block has room
+    fn place_geometry(
+        &mut self,
+        core: &mut Core,
+        size: u64,
+        exclude: Option<ArenaIndex>,
+    ) -> Result<(ArenaIndex, u64)> {
+        let found = self
+            .geometry
+            .blocks
+            .iter_mut()
+            .filter(|(index, block)| !block.dedicated && !block.retired && Some(*index) != exclude)
+            .find_map(|(index, block)| {
+                block
+                    .ranges
+                    .allocate(size, GEOMETRY_ALIGNMENT)
+                    .map(|offset| (index, offset))
+            });
+        if let Some(found) = found {
+            return Ok(found);
+        }
+        let dedicated = size > BLOCK_SIZE;
+        let capacity = if dedicated { size } else { BLOCK_SIZE };
+        let (buffer, allocation) = self.create_buffer(
+            core,
+            capacity,
+            vk::BufferUsageFlags::TRANSFER_SRC
+                | vk::BufferUsageFlags::TRANSFER_DST
+                | vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::INDEX_BUFFER,
+            vk::SharingMode::EXCLUSIVE,
+            MemoryLocation::GpuOnly,
+        )?;
+        let mut ranges = RangeAllocator::new(capacity);
+        let offset = ranges.allocate(size, GEOMETRY_ALIGNMENT).unwrap();
+        let block = self.geometry.blocks.insert(Block {
+            buffer,
+            allocation: Some(allocation),
+            ranges,
+            dedicated,
+            retired: false,
+        });
+        if !dedicated {
+            core.set_object_name(
+                buffer,
+                &format!("geometry heap {}", block.into_raw_parts().0),
+            )?;
+        }
+        Ok((block, offset))
+    }
+    pub fn geometry_binding(&self, range: &GeometryRange) -> GeometryBinding {
+        let range = &self.geometry.ranges[range.index];
+        GeometryBinding {
+            block: range.block,
+            buffer: self.geometry.blocks[range.block].buffer,
+            offset: range.offset,
+        }
+    }
+    /// Names the buffer of `range` if it is the only range in its block
+    pub fn set_geometry_name(&self, core: &Core, range: &GeometryRange, name: &str) -> Result<()> {
+        let block = &self.geometry.blocks[self.geometry.ranges[range.index].block];
+        if block.dedicated {
+            core.set_object_name(block.buffer, name)?;
+        }
+        Ok(())
+    }
+    /// Returns `range` to its block. Must only be called once no frame in flight reads the
+    /// range. Empty blocks are freed unless they are the last shared block.
+    pub fn free_geometry(&mut self, core: &mut Core, range: GeometryRange) -> Result<()> {
+        let range = self
+            .geometry
+            .ranges
+            .remove(range.index)
+            .expect("geometry range already freed");
+        let block = &mut self.geometry.blocks[range.block];
+        block.ranges.free(range.offset, range.size);
+        let shared_blocks = self
+            .geometry
+            .blocks
+            .iter()
+            .filter(|(_, block)| !block.dedicated && !block.retired)
+            .count();
+        let block = &self.geometry.blocks[range.block];
+        if block.ranges.is_empty() && (block.dedicated || shared_blocks > 1) {
+            self.free_geometry_block(core, range.block)?;
+        }
+        Ok(())
+    }
+    /// Moves the ranges of the first fragmented block to other blocks and returns the emptied
+    /// block. The block is retired and must be freed with `free_geometry_block` once no frame
+    /// in flight reads it.
+    pub fn compact_geometry(&mut self, core: &mut Core) -> Result<Option<ArenaIndex>> {
+        let old_block = match self.geometry.blocks.iter().find(|(_, block)| {
+            !block.dedicated && !block.retired && block.ranges.needs_compaction()
+        }) {
+            Some((index, _)) => index,
+            None => return Ok(None),
+        };
+        self.geometry.blocks[old_block].retired = true;
+        let src = self.geometry.blocks[old_block].buffer;
+        let moved = self
+            .geometry
+            .ranges
+            .iter()
+            .filter(|(_, range)| range.block == old_block)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let command_buffer = self.upload_command_buffer(core)?;
+        // earlier uploads into the block must land before it is copied from
+        let memory_barrier = [*vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE)];
+        unsafe {
+            core.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &memory_barrier,
+                &[],
+                &[],
+            );
+        }
+        for index in moved {
+            let (src_offset, size) = {
+                let range = &self.geometry.ranges[index];
+                (range.offset, range.size)
+            };
+            let (block, offset) = self.place_geometry(core, size, Some(old_block))?;
+            let region = [*vk::BufferCopy::builder()
+                .src_offset(src_offset)
+                .dst_offset(offset)
+                .size(size)];
+            unsafe {
+                core.device.cmd_copy_buffer(
+                    command_buffer,
+                    src,
+                    self.geometry.blocks[block].buffer,
+                    &region,
+                );
+            }
+            let range = &mut self.geometry.ranges[index];
+            range.block = block;
+            range.offset = offset;
+        }
+        Ok(Some(old_block))
+    }
+    /// Frees a block, waiting for uploads that may still touch it
+    pub fn free_geometry_block(&mut self, core: &mut Core, block: ArenaIndex) -> Result<()> {
+        self.wait_uploads(core)?;
+        let mut block = self
+            .geometry
+            .blocks
+            .remove(block)
+            .expect("geometry block already freed");
+        unsafe {
+            core.device.destroy_buffer(block.buffer, None);
+        }
+        self.free_allocation(block.allocation.take().unwrap())
+    }
+    /// Frees every block, ranges still in use become invalid
+    pub(super) fn free_geometry_heap(&mut self, core: &mut Core) -> Result<()> {
+        let blocks = self
+            .geometry
+            .blocks
+            .iter()
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        for block in blocks {
+            self.free_geometry_block(core, block)?;
+        }
+        self.geometry.ranges.clear();
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn ranges_merge_when_freed() {
+        let mut ranges = RangeAllocator::new(100);
+        assert_eq!(ranges.allocate(30, 1), Some(0));
+        assert_eq!(ranges.allocate(30, 1), Some(30));
+        assert_eq!(ranges.allocate(30, 1), Some(60));
+        assert_eq!(ranges.allocate(30, 1), None);
+        ranges.free(0, 30);
+        ranges.free(60, 30);
+        // 30 free at the start and 40 at the end
+        assert_eq!(ranges.allocate(35, 1), Some(60));
+        ranges.free(60, 35);
+        ranges.free(30, 30);
+        assert!(ranges.is_empty());
+        assert_eq!(ranges.allocate(100, 1), Some(0));
+    }
+    #[test]
+    fn ranges_are_aligned() {
+        let mut ranges = RangeAllocator::new(64);
+        assert_eq!(ranges.allocate(3, 16), Some(0));
+        assert_eq!(ranges.allocate(3, 16), Some(16));
+        // the padding after each range stays free
+        assert_eq!(ranges.allocate(13, 1), Some(3));
+        assert_eq!(ranges.free_bytes(), 64 - 19);
+        ranges.free(16, 3);
+        ranges.free(3, 13);
+        ranges.free(0, 3);
+        assert!(ranges.is_empty());
+    }
+    #[test]
+    fn fragmented_ranges_need_compaction() {
+        let mut ranges = RangeAllocator::new(100);
+        for i in 0..10 {
+            assert_eq!(ranges.allocate(10, 1), Some(i * 10));
+        }
+        assert_eq!(ranges.fragmentation(), 0.0);
+        assert!(!ranges.needs_compaction());
+        // frees every other range, leaving five holes of 10 bytes
+        for i in (0..10).step_by(2) {
+            ranges.free(i * 10, 10);
+        }
+        assert!((ranges.fragmentation() - 0.8).abs() < 1e-6);
+        assert!(ranges.needs_compaction());
+        // contiguous free space does not need compacting
+        let mut ranges = RangeAllocator::new(100);
+        assert_eq!(ranges.allocate(50, 1), Some(0));
+        assert!(!ranges.needs_compaction());
+    }
+}
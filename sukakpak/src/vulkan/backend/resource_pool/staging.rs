@@ -0,0 +1,430 @@
+use super::{Core, ResourcePool};
+use anyhow::Result;
+use ash::vk;
+use gpu_allocator::{
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator},
+    MemoryLocation,
+};
+use std::collections::VecDeque;
+/// Size of the persistent staging buffer
+const STAGING_SIZE: u64 = 32 * 1024 * 1024;
+/// Uploads larger than this get a dedicated staging buffer so they do not flush the ring
+const MAX_RING_UPLOAD: u64 = STAGING_SIZE / 4;
+/// Offset alignment of staged data, satisfies texel and copy offset alignment
+const STAGING_ALIGNMENT: u64 = 16;
+/// Hands out ranges of a ring buffer. Ranges are released in the order they were handed out.
+#[derive(Debug)]
+pub struct RingAllocator {
+    capacity: u64,
+    /// end of the newest range
+    head: u64,
+    /// live ranges as (start, end), oldest first
+    live: VecDeque<(u64, u64)>,
+}
+impl RingAllocator {
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            capacity,
+            head: 0,
+            live: VecDeque::new(),
+        }
+    }
+    /// Returns the offset of a free range of `size` bytes, None if there is no room
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let offset = match self.live.front() {
+            None => Some(0).filter(|_| size <= self.capacity),
+            Some(&(tail, _)) => {
+                let aligned_head = self.head.div_ceil(alignment) * alignment;
+                if self.head > tail {
+                    // free space is after the head and before the tail
+                    if aligned_head + size <= self.capacity {
+                        Some(aligned_head)
+                    } else if size <= tail {
+                        Some(0)
+                    } else {
+                        None
+                    }
+                } else if aligned_head + size <= tail {
+                    Some(aligned_head)
+                } else {
+                    None
+                }
+            }
+        }?;
+        self.head = offset + size;
+        self.live.push_back((offset, self.head));
+        Some(offset)
+    }
+    /// Releases the oldest `count` ranges
+    pub fn release(&mut self, count: usize) {
+        for _ in 0..count {
+            self.live
+                .pop_front()
+                .expect("released more ranges than allocated");
+        }
+    }
+}
+/// Copies recorded since the last flush, submitted together
+struct UploadBatch {
+    command_buffer: vk::CommandBuffer,
+    /// ring ranges read by the batch
+    ranges: usize,
+    /// staging buffers of uploads too large for the ring
+    dedicated: Vec<(vk::Buffer, Allocation)>,
+}
+/// Batches uploads to gpu only memory into one submission per frame, staging data
+/// through a persistently mapped ring buffer
+pub struct UploadQueue {
+    command_pool: vk::CommandPool,
+    staging_buffer: vk::Buffer,
+    staging_allocation: Option<Allocation>,
+    ring: RingAllocator,
+    batch: Option<UploadBatch>,
+    submitted: VecDeque<(vk::Fence, UploadBatch)>,
+}
+impl UploadQueue {
+    pub fn new(core: &Core, allocator: &mut Allocator) -> Result<Self> {
+        let command_pool_create_info = vk::CommandPoolCreateInfo::builder()
+            .queue_family_index(core.queue_family_index)
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT);
+        let command_pool = unsafe {
+            core.device
+                .create_command_pool(&command_pool_create_info, None)?
+        };
+        let buffer_create_info = vk::BufferCreateInfo::builder()
+            .size(STAGING_SIZE)
+            .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let staging_buffer = unsafe { core.device.create_buffer(&buffer_create_info, None)? };
+        let requirements = unsafe { core.device.get_buffer_memory_requirements(staging_buffer) };
+        let staging_allocation = allocator.allocate(&AllocationCreateDesc {
+            name: "staging ring",
+            requirements,
+            location: MemoryLocation::CpuToGpu,
+            linear: true,
+            allocation_scheme: AllocationScheme::DedicatedBuffer(staging_buffer),
+        })?;
+        unsafe {
+            core.device.bind_buffer_memory(
+                staging_buffer,
+                staging_allocation.memory(),
+                staging_allocation.offset(),
+            )?;
+        }
+        Ok(Self {
+            command_pool,
+            staging_buffer,
+            staging_allocation: Some(staging_allocation),
+            ring: RingAllocator::new(STAGING_SIZE),
+            batch: None,
+            submitted: VecDeque::new(),
+        })
+    }
+}
+impl ResourcePool {
+    /// Records a copy of `data` to `dst_offset` in `dst`, the copy runs once uploads are flushed
+    pub fn upload_buffer(
+        &mut self,
+        core: &mut Core,
+        data: &[u8],
+        dst: vk::Buffer,
+        dst_offset: u64,
+    ) -> Result<()> {
+        let (src, src_offset) = self.stage(core, data)?;
+        let region = [*vk::BufferCopy::builder()
+            .src_offset(src_offset)
+            .dst_offset(dst_offset)
+            .size(data.len() as u64)];
+        let command_buffer = self.upload_command_buffer(core)?;
+        unsafe {
+            core.device
+                .cmd_copy_buffer(command_buffer, src, dst, &region);
+        }
+        Ok(())
+    }
+    /// Records a copy of rgba8 `data` into `image`, which is left in the shader read only
+    /// layout once uploads are flushed
+    pub fn upload_image(
+        &mut self,
+        core: &mut Core,
+        data: &[u8],
+        image: vk::Image,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        let (src, src_offset) = self.stage(core, data)?;
+        let subresource_range = *vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+        let to_transfer = *vk::ImageMemoryBarrier::builder()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range);
+        let to_shader = *vk::ImageMemoryBarrier::builder()
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range);
+        let region = *vk::BufferImageCopy::builder()
+            .buffer_offset(src_offset)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(
+                *vk::ImageSubresourceLayers::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(0)
+                    .base_array_layer(0)
+                    .layer_count(1),
+            )
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            });
+        let command_buffer = self.upload_command_buffer(core)?;
+        unsafe {
+            core.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            core.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                src,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            );
+            core.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        }
+        Ok(())
+    }
+    /// Submits every recorded upload in one batch. Must be called before submitting work
+    /// that reads the uploaded resources.
+    pub fn flush_uploads(&mut self, core: &mut Core) -> Result<()> {
+        self.reclaim_uploads(core)?;
+        let batch = match self.uploads.batch.take() {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+        // makes buffer copies visible to vertex input and shaders of later submissions
+        let memory_barrier = [*vk::MemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                    | vk::AccessFlags::INDEX_READ
+                    | vk::AccessFlags::SHADER_READ,
+            )];
+        let command_buffers = [batch.command_buffer];
+        let submit_info = [*vk::SubmitInfo::builder().command_buffers(&command_buffers)];
+        let fence = unsafe {
+            core.device.cmd_pipeline_barrier(
+                batch.command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::VERTEX_INPUT
+                    | vk::PipelineStageFlags::VERTEX_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &memory_barrier,
+                &[],
+                &[],
+            );
+            core.device.end_command_buffer(batch.command_buffer)?;
+            let fence = core
+                .device
+                .create_fence(&vk::FenceCreateInfo::builder(), None)?;
+            core.device
+                .queue_submit(core.present_queue, &submit_info, fence)?;
+            fence
+        };
+        self.uploads.submitted.push_back((fence, batch));
+        Ok(())
+    }
+    /// Flushes uploads and waits for every upload to finish
+    pub fn wait_uploads(&mut self, core: &mut Core) -> Result<()> {
+        self.flush_uploads(core)?;
+        while let Some((fence, _batch)) = self.uploads.submitted.front() {
+            unsafe {
+                core.device.wait_for_fences(&[*fence], true, u64::MAX)?;
+            }
+            self.reclaim_uploads(core)?;
+        }
+        Ok(())
+    }
+    /// Frees batches the gpu is done with
+    fn reclaim_uploads(&mut self, core: &mut Core) -> Result<()> {
+        while let Some((fence, _batch)) = self.uploads.submitted.front() {
+            if !unsafe { core.device.get_fence_status(*fence) }? {
+                break;
+            }
+            let (fence, batch) = self.uploads.submitted.pop_front().unwrap();
+            unsafe {
+                core.device.destroy_fence(fence, None);
+                core.device
+                    .free_command_buffers(self.uploads.command_pool, &[batch.command_buffer]);
+            }
+            self.uploads.ring.release(batch.ranges);
+            for (buffer, allocation) in batch.dedicated {
+                unsafe {
+                    core.device.destroy_buffer(buffer, None);
+                }
+                self.free_allocation(allocation)?;
+            }
+        }
+        Ok(())
+    }
+    /// Command buffer of the current batch, begun on first use
+    pub(super) fn upload_command_buffer(&mut self, core: &mut Core) -> Result<vk::CommandBuffer> {
+        if let Some(batch) = self.uploads.batch.as_ref() {
+            return Ok(batch.command_buffer);
+        }
+        let allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_pool(self.uploads.command_pool)
+            .command_buffer_count(1);
+        let command_buffer = unsafe { core.device.allocate_command_buffers(&allocate_info)? }[0];
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            core.device
+                .begin_command_buffer(command_buffer, &begin_info)?;
+        }
+        self.uploads.batch = Some(UploadBatch {
+            command_buffer,
+            ranges: 0,
+            dedicated: vec![],
+        });
+        Ok(command_buffer)
+    }
+    /// Copies `data` to staging memory and returns the buffer and offset holding it. When
+    /// the ring is full pending uploads are flushed and waited on.
+    fn stage(&mut self, core: &mut Core, data: &[u8]) -> Result<(vk::Buffer, u64)> {
+        let size = data.len() as u64;
+        let mut offset = None;
+        if size <= MAX_RING_UPLOAD {
+            self.reclaim_uploads(core)?;
+            offset = self.uploads.ring.allocate(size, STAGING_ALIGNMENT);
+            if offset.is_none() {
+                self.wait_uploads(core)?;
+                offset = self.uploads.ring.allocate(size, STAGING_ALIGNMENT);
+            }
+        }
+        let (buffer, offset) = match offset {
+            Some(offset) => {
+                let ptr = self
+                    .uploads
+                    .staging_allocation
+                    .as_ref()
+                    .unwrap()
+                    .mapped_ptr()
+                    .expect("failed to map staging buffer")
+                    .as_ptr() as *mut u8;
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        data.as_ptr(),
+                        ptr.add(offset as usize),
+                        data.len(),
+                    );
+                }
+                self.upload_command_buffer(core)?;
+                self.uploads.batch.as_mut().unwrap().ranges += 1;
+                (self.uploads.staging_buffer, offset)
+            }
+            None => {
+                let (buffer, allocation) = self.create_buffer(
+                    core,
+                    size,
+                    vk::BufferUsageFlags::TRANSFER_SRC,
+                    vk::SharingMode::EXCLUSIVE,
+                    MemoryLocation::CpuToGpu,
+                )?;
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        data.as_ptr(),
+                        allocation
+                            .mapped_ptr()
+                            .expect("failed to map staging buffer")
+                            .as_ptr() as *mut u8,
+                        data.len(),
+                    );
+                }
+                self.upload_command_buffer(core)?;
+                self.uploads
+                    .batch
+                    .as_mut()
+                    .unwrap()
+                    .dedicated
+                    .push((buffer, allocation));
+                (buffer, 0)
+            }
+        };
+        Ok((buffer, offset))
+    }
+    /// Waits for uploads and frees the upload queue
+    pub(super) fn free_uploads(&mut self, core: &mut Core) -> Result<()> {
+        self.wait_uploads(core)?;
+        unsafe {
+            core.device
+                .destroy_command_pool(self.uploads.command_pool, None);
+            core.device
+                .destroy_buffer(self.uploads.staging_buffer, None);
+        }
+        let allocation = self.uploads.staging_allocation.take().unwrap();
+        self.free_allocation(allocation)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn ring_wraps_and_releases_in_order() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(ring.allocate(40, 1), Some(0));
+        assert_eq!(ring.allocate(40, 1), Some(40));
+        // does not fit after the head and the start is in use
+        assert_eq!(ring.allocate(30, 1), None);
+        ring.release(1);
+        // wraps to the start freed by the release
+        assert_eq!(ring.allocate(30, 1), Some(0));
+        // the free space between head and tail is 10 bytes
+        assert_eq!(ring.allocate(11, 1), None);
+        assert_eq!(ring.allocate(10, 1), Some(30));
+        ring.release(3);
+        // empty rings start over at 0
+        assert_eq!(ring.allocate(100, 1), Some(0));
+        assert_eq!(ring.allocate(1, 1), None);
+    }
+    #[test]
+    fn ring_aligns_offsets() {
+        let mut ring = RingAllocator::new(64);
+        assert_eq!(ring.allocate(3, 16), Some(0));
+        assert_eq!(ring.allocate(3, 16), Some(16));
+        assert_eq!(ring.allocate(40, 16), None);
+        assert_eq!(ring.allocate(32, 16), Some(32));
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+/// How often watched project directories are checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+struct WatchedShader {
+    name: String,
+    project_dir: PathBuf,
+    /// newest modification time seen in the project directory
+    modified: Option<SystemTime>,
+}
+/// Polls shader project directories for modified files
+pub struct ShaderWatcher {
+    watched: Vec<WatchedShader>,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+}
+impl ShaderWatcher {
+    pub fn new(poll_interval: Duration) -> Self {
+        Self {
+            watched: vec![],
+            poll_interval,
+            last_poll: None,
+        }
+    }
+    /// Watches `project_dir` for shader `name`, replacing an earlier watch of `name`
+    pub fn watch(&mut self, name: &str, project_dir: &Path) {
+        self.watched.retain(|shader| shader.name != name);
+        self.watched.push(WatchedShader {
+            name: name.to_string(),
+            project_dir: project_dir.to_path_buf(),
+            modified: latest_modification(project_dir),
+        });
+    }
+    /// Returns the name and project directory of shaders modified since the last poll,
+    /// returns nothing if polled sooner than the poll interval
+    pub fn changed(&mut self) -> Vec<(String, PathBuf)> {
+        if self
+            .last_poll
+            .map(|last_poll| last_poll.elapsed() < self.poll_interval)
+            .unwrap_or(false)
+        {
+            return vec![];
+        }
+        self.last_poll = Some(Instant::now());
+        self.watched
+            .iter_mut()
+            .filter_map(|shader| {
+                let modified = latest_modification(&shader.project_dir);
+                if modified > shader.modified {
+                    shader.modified = modified;
+                    Some((shader.name.clone(), shader.project_dir.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+/// Newest modification time of the files in `dir` and its subdirectories
+fn latest_modification(dir: &Path) -> Option<SystemTime> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() {
+                latest_modification(&entry.path())
+            } else {
+                metadata.modified().ok()
+            }
+        })
+        .max()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    #[test]
+    fn detects_modified_files() {
+        let dir = std::env::temp_dir().join(format!("sukakpak_watch_{}", std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let shader_path = dir.join("src").join("shader.wgsl");
+        fs::write(&shader_path, "old").unwrap();
+        let mut watcher = ShaderWatcher::new(Duration::ZERO);
+        watcher.watch("world", &dir);
+        assert!(watcher.changed().is_empty());
+
+        let later = SystemTime::now() + Duration::from_secs(10);
+        File::options()
+            .write(true)
+            .open(&shader_path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(watcher.changed(), vec![("world".to_string(), dir.clone())]);
+        assert!(watcher.changed().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
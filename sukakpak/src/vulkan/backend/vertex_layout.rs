@@ -1,4 +1,5 @@
 use super::VertexComponent;
+use crate::{VertexLayout, VertexStream};
 use ash::vk;
 
 impl From<&VertexComponent> for vk::Format {
@@ -8,6 +9,56 @@ impl From<&VertexComponent> for vk::Format {
             VertexComponent::Vec2F32 => vk::Format::R32G32_SFLOAT,
             VertexComponent::Vec3F32 => vk::Format::R32G32B32_SFLOAT,
             VertexComponent::Vec4F32 => vk::Format::R32G32B32A32_SFLOAT,
+            VertexComponent::Vec4U8Norm => vk::Format::R8G8B8A8_UNORM,
+            VertexComponent::Vec2U16 => vk::Format::R16G16_UINT,
+            VertexComponent::Vec4U16 => vk::Format::R16G16B16A16_UINT,
+            VertexComponent::Vec1U32 => vk::Format::R32_UINT,
+            VertexComponent::Vec1I32 => vk::Format::R32_SINT,
+            VertexComponent::Vec1F16 => vk::Format::R16_SFLOAT,
+            VertexComponent::Vec2F16 => vk::Format::R16G16_SFLOAT,
+            VertexComponent::Vec4F16 => vk::Format::R16G16B16A16_SFLOAT,
+        }
+    }
+}
+/// Layout of the vertex buffer bound to one binding, pipelines are built for the
+/// layouts of the meshes drawn with them
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StreamLayout {
+    pub binding: u32,
+    /// Shader location of each component in `vertex_layout`
+    pub locations: Vec<u32>,
+    pub vertex_layout: VertexLayout,
+}
+impl StreamLayout {
+    pub fn binding_description(&self) -> vk::VertexInputBindingDescription {
+        *vk::VertexInputBindingDescription::builder()
+            .binding(self.binding)
+            .input_rate(vk::VertexInputRate::VERTEX)
+            .stride(self.vertex_layout.vertex_size() as u32)
+    }
+    /// Components are packed in order
+    pub fn attribute_descriptions(&self) -> Vec<vk::VertexInputAttributeDescription> {
+        self.vertex_layout
+            .components
+            .iter()
+            .zip(self.locations.iter())
+            .enumerate()
+            .map(|(index, (component, location))| {
+                *vk::VertexInputAttributeDescription::builder()
+                    .binding(self.binding)
+                    .location(*location)
+                    .format(component.into())
+                    .offset(self.vertex_layout.component_offset(index) as u32)
+            })
+            .collect()
+    }
+}
+impl From<&VertexStream> for StreamLayout {
+    fn from(stream: &VertexStream) -> Self {
+        Self {
+            binding: stream.binding,
+            locations: stream.locations.clone(),
+            vertex_layout: stream.vertex_layout.clone(),
         }
     }
 }
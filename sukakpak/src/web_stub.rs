@@ -1,10 +1,11 @@
 use super::{
-    BackendTrait, ContextTrait, ControlFlow, CreateInfo, EventLoopTrait, GenericBindable,
-    GenericDrawableTexture, MeshAsset, Timer, WindowEvent,
+    AdapterInfo, BackendTrait, Bounds, BufferKind, ComputeReference, ContextTrait, ControlFlow,
+    CreateInfo, DebugDraw, DrawQueue, DrawStats, EventLoopTrait, FramebufferDesc, GenericBindable,
+    GenericDrawableTexture, MeshAsset, PresentMode, Timer, WindowEvent,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use image::RgbaImage;
-use nalgebra::Vector2;
+use nalgebra::{Matrix4, Vector2, Vector3};
 use std::{
     path::Path,
     sync::{Arc, Mutex},
@@ -32,6 +33,10 @@ impl BackendTrait for Backend {
         Self {}
     }
 }
+/// The stub has no gpus
+pub fn list_adapters() -> Result<Vec<AdapterInfo>> {
+    Ok(vec![])
+}
 pub struct Context {
     quit: Arc<Mutex<bool>>,
 }
@@ -59,16 +64,27 @@ impl Timer for TimerContainer {
     }
 }
 #[derive(Debug)]
-pub struct Mesh {}
+pub struct Mesh {
+    bounds: Option<Bounds>,
+}
+impl Mesh {
+    /// Bounds of the mesh in model space
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
+}
 #[derive(Debug)]
 pub struct Framebuffer {}
 #[derive(Debug)]
 pub struct Texture {}
+#[derive(Debug)]
+pub struct Buffer {}
 impl ContextTrait for Context {
     type Backend = Backend;
     type Mesh = Mesh;
     type Framebuffer = Framebuffer;
     type Texture = Texture;
+    type Buffer = Buffer;
     type Timer = TimerContainer;
     fn new(_: Self::Backend) -> Self {
         Self {
@@ -83,10 +99,15 @@ impl ContextTrait for Context {
     }
     fn build_mesh(
         &mut self,
-        _: MeshAsset,
+        mesh: MeshAsset,
         _: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
     ) -> Result<Self::Mesh> {
-        Ok(Mesh {})
+        mesh.validate_lengths()?;
+        #[cfg(feature = "state_validation")]
+        mesh.validate()?;
+        Ok(Mesh {
+            bounds: mesh.bounds(),
+        })
     }
     fn bind_texture(
         &mut self,
@@ -95,28 +116,91 @@ impl ContextTrait for Context {
     ) -> Result<()> {
         Ok(())
     }
+    fn build_mesh_named(
+        &mut self,
+        mesh: MeshAsset,
+        texture: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
+        _: &str,
+    ) -> Result<Self::Mesh> {
+        self.build_mesh(mesh, texture)
+    }
     fn build_texture(&mut self, _: &RgbaImage) -> Result<Self::Texture> {
         Ok(Texture {})
     }
+    fn build_texture_named(&mut self, _: &RgbaImage, _: &str) -> Result<Self::Texture> {
+        Ok(Texture {})
+    }
     fn draw_mesh(&mut self, _: Vec<u8>, _: &Self::Mesh) -> Result<()> {
         Ok(())
     }
+    fn draw_mesh_sorted(&mut self, _: Vec<u8>, _: &Self::Mesh, _: DrawQueue, _: f32) -> Result<()> {
+        Ok(())
+    }
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats::default()
+    }
+    fn draw_debug(&mut self, _: &DebugDraw, _: Matrix4<f32>) -> Result<()> {
+        Ok(())
+    }
 
-    fn build_framebuffer(&mut self, _: Vector2<u32>) -> Result<Self::Framebuffer> {
+    fn build_framebuffer(&mut self, _: FramebufferDesc) -> Result<Self::Framebuffer> {
         Ok(Framebuffer {})
     }
+    fn build_framebuffer_named(
+        &mut self,
+        _: FramebufferDesc,
+        _: &str,
+    ) -> Result<Self::Framebuffer> {
+        Ok(Framebuffer {})
+    }
+    fn build_buffer(&mut self, _: BufferKind, _: &[u8]) -> Result<Self::Buffer> {
+        Ok(Buffer {})
+    }
+    fn update_buffer(&mut self, _: &Self::Buffer, _: &[u8]) -> Result<()> {
+        Ok(())
+    }
+    fn bind_shader_buffer(&mut self, _: &str, _: &str, _: &Self::Buffer) -> Result<()> {
+        Ok(())
+    }
+    fn bind_mesh_buffer(&mut self, _: &mut Self::Mesh, _: &str, _: &Self::Buffer) -> Result<()> {
+        Ok(())
+    }
     fn bind_shader(&mut self, _: GenericBindable<Self::Framebuffer>, _: &str) -> Result<()> {
         Ok(())
     }
     fn bind_framebuffer(&mut self, _: GenericBindable<Self::Framebuffer>) -> Result<()> {
         Ok(())
     }
+    fn push_debug_group(&mut self, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn pop_debug_group(&mut self) -> Result<()> {
+        Ok(())
+    }
     fn get_screen_size(&self) -> Vector2<u32> {
         Vector2::new(100, 100)
     }
+    fn set_present_mode(&mut self, _: PresentMode) -> Result<()> {
+        Ok(())
+    }
     fn load_shader<P: AsRef<Path>>(&mut self, _: P, _: &str) -> Result<()> {
         Ok(())
     }
+    fn watch_shader(&mut self, _: &Path, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn load_compute_shader(&mut self, _: &str, _: &str) -> Result<()> {
+        Ok(())
+    }
+    fn set_compute_reference(&mut self, _: &str, _: ComputeReference) -> Result<()> {
+        Ok(())
+    }
+    fn dispatch(&mut self, _: &str, _: Vector3<u32>, _: &[(&str, &Self::Buffer)]) -> Result<()> {
+        Ok(())
+    }
+    fn read_buffer(&mut self, _: &Self::Buffer) -> Result<Vec<u8>> {
+        bail!("the web stub does not keep buffer data")
+    }
     fn quit(&mut self) {
         *self.quit.lock().expect("failed to get lock") = true
     }
@@ -2,18 +2,19 @@ mod backend;
 mod event_loop;
 
 use super::{
-    BackendTrait, ContextTrait, ControlFlow, CreateInfo, EventLoopTrait, GenericBindable,
-    GenericDrawableTexture, MeshAsset, Timer, VertexComponent, WindowEvent,
+    AdapterInfo, BackendTrait, BufferKind, ComputeReference, ContextTrait, ControlFlow, CreateInfo,
+    DebugDraw, DrawQueue, DrawStats, EventLoopTrait, FramebufferDesc, GenericBindable,
+    GenericDrawableTexture, MeshAsset, PresentMode, Timer, VertexComponent, WindowEvent,
 };
 use anyhow::{bail, Result};
 use ass_wgl::Shader;
 use backend::Backend;
-pub use backend::{Framebuffer, MeshIndex, TextureIndex};
+pub use backend::{BufferIndex, Framebuffer, MeshIndex, TextureIndex};
 pub use event_loop::EventLoop;
 use generational_arena::{Arena, Index as ArenaIndex};
 use image::RgbaImage;
 use log::{info, Level};
-use nalgebra::Vector2;
+use nalgebra::{Matrix4, Vector2, Vector3};
 use std::{cell::RefCell, collections::HashMap, mem::size_of, path::Path, rc::Rc, time::Duration};
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{
@@ -51,6 +52,10 @@ impl Timer for TimerContainer {
         Duration::from_micros((ms * 1000.0) as u64)
     }
 }
+/// WebGL does not expose the gpus of the machine
+pub fn list_adapters() -> Result<Vec<AdapterInfo>> {
+    Ok(vec![])
+}
 /// For now only supporting uniforms with a 4x4 matrix
 pub struct Context {
     backend: Rc<RefCell<Backend>>,
@@ -63,6 +68,7 @@ impl ContextTrait for Context {
     type Mesh = MeshIndex;
     type Framebuffer = Framebuffer;
     type Texture = TextureIndex;
+    type Buffer = BufferIndex;
     type Timer = TimerContainer;
     fn new(backend: Self::Backend) -> Self {
         let backend = Backend::new(backend);
@@ -91,15 +97,86 @@ impl ContextTrait for Context {
     ) -> Result<()> {
         self.backend.borrow_mut().bind_texture(mesh, texture)
     }
+    /// WebGL has no object names, `name` is ignored
+    fn build_mesh_named(
+        &mut self,
+        mesh: MeshAsset,
+        texture: GenericDrawableTexture<Self::Texture, Self::Framebuffer>,
+        _name: &str,
+    ) -> Result<Self::Mesh> {
+        self.build_mesh(mesh, texture)
+    }
     fn build_texture(&mut self, image: &RgbaImage) -> Result<Self::Texture> {
         self.backend.borrow_mut().build_texture(image)
     }
+    fn build_texture_named(&mut self, image: &RgbaImage, _name: &str) -> Result<Self::Texture> {
+        self.build_texture(image)
+    }
     fn draw_mesh(&mut self, push_data: Vec<u8>, mesh_index: &Self::Mesh) -> Result<()> {
         self.backend.borrow_mut().draw_mesh(push_data, mesh_index)
     }
+    /// WebGL has no draw list, draws immediately
+    fn draw_mesh_sorted(
+        &mut self,
+        push_data: Vec<u8>,
+        mesh_index: &Self::Mesh,
+        _queue: DrawQueue,
+        _depth: f32,
+    ) -> Result<()> {
+        self.draw_mesh(push_data, mesh_index)
+    }
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats::default()
+    }
+    fn draw_debug(&mut self, debug: &DebugDraw, view_projection: Matrix4<f32>) -> Result<()> {
+        if debug.is_empty() {
+            return Ok(());
+        }
+        let push = view_projection
+            .as_slice()
+            .iter()
+            .flat_map(|f| f.to_ne_bytes())
+            .collect();
+        self.backend
+            .borrow_mut()
+            .draw_debug(debug.line_mesh(&view_projection), push)
+    }
 
-    fn build_framebuffer(&mut self, dimensions: Vector2<u32>) -> Result<Self::Framebuffer> {
-        self.backend.borrow_mut().build_framebuffer(dimensions)
+    fn build_framebuffer(&mut self, desc: FramebufferDesc) -> Result<Self::Framebuffer> {
+        self.backend.borrow_mut().build_framebuffer(desc)
+    }
+    fn build_framebuffer_named(
+        &mut self,
+        desc: FramebufferDesc,
+        _name: &str,
+    ) -> Result<Self::Framebuffer> {
+        self.build_framebuffer(desc)
+    }
+    fn build_buffer(&mut self, kind: BufferKind, data: &[u8]) -> Result<Self::Buffer> {
+        self.backend.borrow_mut().build_buffer(kind, data)
+    }
+    fn update_buffer(&mut self, buffer: &Self::Buffer, data: &[u8]) -> Result<()> {
+        self.backend.borrow_mut().update_buffer(buffer, data)
+    }
+    fn bind_shader_buffer(
+        &mut self,
+        shader: &str,
+        name: &str,
+        buffer: &Self::Buffer,
+    ) -> Result<()> {
+        self.backend
+            .borrow_mut()
+            .bind_shader_buffer(shader, name, buffer)
+    }
+    fn bind_mesh_buffer(
+        &mut self,
+        mesh: &mut Self::Mesh,
+        name: &str,
+        buffer: &Self::Buffer,
+    ) -> Result<()> {
+        self.backend
+            .borrow_mut()
+            .bind_mesh_buffer(mesh, name, buffer)
     }
     fn bind_shader(
         &mut self,
@@ -113,12 +190,46 @@ impl ContextTrait for Context {
     fn bind_framebuffer(&mut self, framebuffer: GenericBindable<Self::Framebuffer>) -> Result<()> {
         self.backend.borrow_mut().bind_framebuffer(framebuffer)
     }
+    /// WebGL has no command labels
+    fn push_debug_group(&mut self, _label: &str) -> Result<()> {
+        Ok(())
+    }
+    fn pop_debug_group(&mut self) -> Result<()> {
+        Ok(())
+    }
     fn get_screen_size(&self) -> Vector2<u32> {
         self.backend.borrow_mut().get_screen_size()
     }
+    /// The browser presents with vsync, other modes are ignored
+    fn set_present_mode(&mut self, _present_mode: PresentMode) -> Result<()> {
+        Ok(())
+    }
     fn load_shader(&mut self, shader_text: &str, name: &str) -> Result<()> {
         self.backend.borrow_mut().load_shader(shader_text, name)
     }
+    fn watch_shader(&mut self, _: &Path, name: &str) -> Result<()> {
+        bail!("cannot watch shader {}, WebGL has no filesystem", name)
+    }
+    fn load_compute_shader(&mut self, _: &str, name: &str) -> Result<()> {
+        bail!(
+            "cannot load compute shader {}, WebGL has no compute shaders",
+            name
+        )
+    }
+    fn set_compute_reference(&mut self, _: &str, _: ComputeReference) -> Result<()> {
+        Ok(())
+    }
+    fn dispatch(
+        &mut self,
+        shader: &str,
+        _: Vector3<u32>,
+        _: &[(&str, &Self::Buffer)],
+    ) -> Result<()> {
+        bail!("cannot dispatch {}, WebGL has no compute shaders", shader)
+    }
+    fn read_buffer(&mut self, buffer: &Self::Buffer) -> Result<Vec<u8>> {
+        self.backend.borrow_mut().read_buffer(buffer)
+    }
     fn quit(&mut self) {
         self.backend.borrow_mut().quit()
     }
@@ -1,7 +1,9 @@
+mod buffer;
 mod mesh;
 mod shader;
 mod texture;
 
+use buffer::Buffer;
 use mesh::Mesh;
 use shader::ShaderModule;
 use texture::Texture;
@@ -16,7 +18,17 @@ use web_sys::{HtmlCanvasElement, WebGl2RenderingContext};
 
 use std::{collections::HashMap, mem::size_of};
 
-use super::super::{GenericBindable, GenericDrawableTexture, MeshAsset, VertexComponent};
+use super::super::{
+    Bounds, BufferKind, FramebufferDesc, GenericBindable, GenericDrawableTexture, IndexWidth,
+    MeshAsset, Topology, VertexComponent, VertexStream, DEBUG_SHADER,
+};
+/// Type passed to `draw_elements` for indices of `width`
+fn gl_index_type(width: IndexWidth) -> u32 {
+    match width {
+        IndexWidth::U16 => WebGl2RenderingContext::UNSIGNED_SHORT,
+        IndexWidth::U32 => WebGl2RenderingContext::UNSIGNED_INT,
+    }
+}
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DrawableTexture {
     Texture(TextureIndex),
@@ -25,6 +37,13 @@ pub enum DrawableTexture {
 #[derive(Debug)]
 pub struct MeshIndex {
     index: ArenaIndex,
+    bounds: Option<Bounds>,
+}
+impl MeshIndex {
+    /// Bounds of the mesh in model space
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.bounds
+    }
 }
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Framebuffer {}
@@ -32,13 +51,24 @@ pub struct Framebuffer {}
 pub struct TextureIndex {
     index: ArenaIndex,
 }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferIndex {
+    index: ArenaIndex,
+}
 pub struct Backend {
     quit: bool,
     context: WebGl2RenderingContext,
     shaders: HashMap<String, ShaderModule>,
     mesh_arena: Arena<Mesh>,
     texture_arena: Arena<Texture>,
+    buffer_arena: Arena<Buffer>,
+    /// buffers bound with `bind_shader_buffer`, by shader then buffer name
+    shader_buffers: HashMap<String, HashMap<String, BufferIndex>>,
     bound_shader: String,
+    /// white texture bound to debug lines, built on first use
+    debug_texture: Option<TextureIndex>,
+    /// reused every frame by `draw_debug`
+    debug_mesh: Option<MeshIndex>,
 }
 impl Backend {
     pub fn new(backend: super::CreateBackend) -> Self {
@@ -68,6 +98,10 @@ impl Backend {
             .bind_shader(&mut context)
             .expect("failed to bind default shader");
         shaders.insert("basic".to_string(), basic_shader);
+        shaders.insert(
+            DEBUG_SHADER.to_string(),
+            ShaderModule::debug_shader(&mut context).expect("failed to build debug shader"),
+        );
 
         let bound_shader = "basic".to_string();
         let mesh_arena = Arena::new();
@@ -79,6 +113,10 @@ impl Backend {
             bound_shader,
             mesh_arena,
             texture_arena,
+            buffer_arena: Arena::new(),
+            shader_buffers: HashMap::new(),
+            debug_texture: None,
+            debug_mesh: None,
         }
     }
     /// runs steps necessary for start of render
@@ -92,43 +130,54 @@ impl Backend {
         mesh: MeshAsset,
         texture: GenericDrawableTexture<TextureIndex, Framebuffer>,
     ) -> Result<MeshIndex> {
-        let buffer = self.context.create_buffer();
-        if buffer.is_none() {
-            bail!("failed to create buffer");
+        mesh.validate_lengths()?;
+        #[cfg(feature = "state_validation")]
+        {
+            mesh.validate()?;
+            mesh.validate_vertex_input(
+                &self.bound_shader,
+                &self.shaders[&self.bound_shader].shader.vertex_input,
+            )?;
         }
-        let buffer = buffer.unwrap();
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
-        self.context.buffer_data_with_u8_array(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            &mesh.vertices,
-            WebGl2RenderingContext::STATIC_DRAW,
-        );
+        let bounds = mesh.bounds();
         let vao = self.context.create_vertex_array();
         if vao.is_none() {
             bail!("failed to create vertex array object")
         }
         let vao = vao.unwrap();
         self.context.bind_vertex_array(Some(&vao));
-        let mut offset: usize = 0;
-        let stride: usize = mesh.vertex_layout.components.iter().map(|v| v.size()).sum();
-        for (location, vertex) in mesh.vertex_layout.components.iter().enumerate() {
-            self.context.enable_vertex_attrib_array(location as u32);
-            let normalized = false;
-            self.context.vertex_attrib_pointer_with_i32(
-                location as u32,
-                vertex.num_components() as i32,
-                match vertex {
-                    VertexComponent::Vec1F32 => WebGl2RenderingContext::FLOAT,
-                    VertexComponent::Vec2F32 => WebGl2RenderingContext::FLOAT,
-                    VertexComponent::Vec3F32 => WebGl2RenderingContext::FLOAT,
-                    VertexComponent::Vec4F32 => WebGl2RenderingContext::FLOAT,
-                },
-                normalized,
-                stride as i32,
-                offset as i32,
+        let index_buffer = match self.context.create_buffer() {
+            Some(buffer) => buffer,
+            None => bail!("failed to create index buffer"),
+        };
+        // element array binding is stored in the vertex array object
+        self.context.bind_buffer(
+            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            Some(&index_buffer),
+        );
+        let index_width = mesh.index_width();
+        self.context.buffer_data_with_u8_array(
+            WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+            &index_width.index_bytes(&mesh.indices),
+            WebGl2RenderingContext::STATIC_DRAW,
+        );
+        // every vertex stream of the bound shader gets its own buffer
+        let streams = mesh.vertex_streams(&self.shaders[&self.bound_shader].shader.vertex_input);
+        let mut buffers = Vec::with_capacity(streams.len());
+        for stream in streams.iter() {
+            let buffer = match self.context.create_buffer() {
+                Some(buffer) => buffer,
+                None => bail!("failed to create buffer"),
+            };
+            self.context
+                .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+            self.context.buffer_data_with_u8_array(
+                WebGl2RenderingContext::ARRAY_BUFFER,
+                &stream.vertices,
+                WebGl2RenderingContext::STATIC_DRAW,
             );
-            offset += vertex.size();
+            self.vertex_attrib_pointers(stream);
+            buffers.push(buffer);
         }
         self.context
             .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
@@ -138,15 +187,73 @@ impl Backend {
             GenericDrawableTexture::Framebuffer(_) => todo!("framebuffer"),
         };
 
-        let num_vertices = mesh.num_vertices();
+        let num_indices = mesh.num_indices();
+        let draw_mode = match mesh.topology {
+            Topology::TriangleList => WebGl2RenderingContext::TRIANGLES,
+            Topology::TriangleStrip => WebGl2RenderingContext::TRIANGLE_STRIP,
+            Topology::LineList => WebGl2RenderingContext::LINES,
+            Topology::LineStrip => WebGl2RenderingContext::LINE_STRIP,
+            Topology::PointList => WebGl2RenderingContext::POINTS,
+        };
         let mesh = Mesh {
-            buffer,
+            buffers,
+            index_buffer,
             vao,
             texture,
-            num_vertices,
+            num_indices,
+            index_type: gl_index_type(index_width),
+            draw_mode,
+            uniform_buffers: HashMap::new(),
         };
         let index = self.mesh_arena.insert(mesh);
-        Ok(MeshIndex { index })
+        Ok(MeshIndex { index, bounds })
+    }
+    /// Points the attributes of `stream` at the bound array buffer
+    fn vertex_attrib_pointers(&mut self, stream: &VertexStream) {
+        let mut offset: usize = 0;
+        let stride = stream.vertex_layout.vertex_size();
+        for (location, vertex) in stream
+            .locations
+            .iter()
+            .zip(stream.vertex_layout.components.iter())
+        {
+            let location = *location;
+            self.context.enable_vertex_attrib_array(location);
+            let ty = match vertex {
+                VertexComponent::Vec1F32
+                | VertexComponent::Vec2F32
+                | VertexComponent::Vec3F32
+                | VertexComponent::Vec4F32 => WebGl2RenderingContext::FLOAT,
+                VertexComponent::Vec4U8Norm => WebGl2RenderingContext::UNSIGNED_BYTE,
+                VertexComponent::Vec2U16 | VertexComponent::Vec4U16 => {
+                    WebGl2RenderingContext::UNSIGNED_SHORT
+                }
+                VertexComponent::Vec1U32 => WebGl2RenderingContext::UNSIGNED_INT,
+                VertexComponent::Vec1I32 => WebGl2RenderingContext::INT,
+                VertexComponent::Vec1F16 | VertexComponent::Vec2F16 | VertexComponent::Vec4F16 => {
+                    WebGl2RenderingContext::HALF_FLOAT
+                }
+            };
+            if vertex.is_integer() {
+                self.context.vertex_attrib_i_pointer_with_i32(
+                    location,
+                    vertex.num_components() as i32,
+                    ty,
+                    stride as i32,
+                    offset as i32,
+                );
+            } else {
+                self.context.vertex_attrib_pointer_with_i32(
+                    location,
+                    vertex.num_components() as i32,
+                    ty,
+                    vertex.is_normalized(),
+                    stride as i32,
+                    offset as i32,
+                );
+            }
+            offset += vertex.size();
+        }
     }
     pub fn bind_texture(
         &mut self,
@@ -216,6 +323,138 @@ impl Backend {
         });
         Ok(TextureIndex { index })
     }
+    pub fn build_buffer(&mut self, kind: BufferKind, data: &[u8]) -> Result<BufferIndex> {
+        let buffer = match self.context.create_buffer() {
+            Some(buffer) => buffer,
+            None => bail!("failed to create buffer"),
+        };
+        self.context
+            .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&buffer));
+        self.context.buffer_data_with_u8_array(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            data,
+            WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        self.context
+            .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, None);
+        let index = self.buffer_arena.insert(Buffer {
+            buffer,
+            kind,
+            size: data.len(),
+        });
+        Ok(BufferIndex { index })
+    }
+    pub fn update_buffer(&mut self, buffer: &BufferIndex, data: &[u8]) -> Result<()> {
+        let buffer = &self.buffer_arena[buffer.index];
+        if data.len() > buffer.size {
+            bail!(
+                "update of {} bytes does not fit in buffer of {} bytes",
+                data.len(),
+                buffer.size
+            )
+        }
+        self.context
+            .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&buffer.buffer));
+        self.context.buffer_sub_data_with_i32_and_u8_array(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            0,
+            data,
+        );
+        self.context
+            .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, None);
+        Ok(())
+    }
+    pub fn read_buffer(&mut self, buffer: &BufferIndex) -> Result<Vec<u8>> {
+        let buffer = &self.buffer_arena[buffer.index];
+        let mut data = vec![0; buffer.size];
+        self.context
+            .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, Some(&buffer.buffer));
+        self.context.get_buffer_sub_data_with_i32_and_u8_array(
+            WebGl2RenderingContext::UNIFORM_BUFFER,
+            0,
+            &mut data,
+        );
+        self.context
+            .bind_buffer(WebGl2RenderingContext::UNIFORM_BUFFER, None);
+        Ok(data)
+    }
+    pub fn bind_shader_buffer(
+        &mut self,
+        shader: &str,
+        name: &str,
+        buffer: &BufferIndex,
+    ) -> Result<()> {
+        let shader_module = match self.shaders.get(shader) {
+            Some(shader_module) => shader_module,
+            None => bail!("shader {} not found", shader),
+        };
+        if !shader_module
+            .shader
+            .buffers
+            .iter()
+            .any(|block| block.buffer.name == name)
+        {
+            bail!("shader {} has no buffer named {}", shader, name)
+        }
+        self.shader_buffers
+            .entry(shader.to_string())
+            .or_default()
+            .insert(name.to_string(), *buffer);
+        Ok(())
+    }
+    pub fn bind_mesh_buffer(
+        &mut self,
+        mesh: &mut MeshIndex,
+        name: &str,
+        buffer: &BufferIndex,
+    ) -> Result<()> {
+        self.mesh_arena[mesh.index]
+            .uniform_buffers
+            .insert(name.to_string(), *buffer);
+        Ok(())
+    }
+    /// Binds the uniform blocks of the bound shader, block `i` uses binding point `i + 1` as the
+    /// push uniform may be a block at binding point 0
+    fn bind_uniform_blocks(&self, mesh: &Mesh) -> Result<()> {
+        let bound_shader = &self.shaders[&self.bound_shader];
+        for (i, block) in bound_shader.shader.buffers.iter().enumerate() {
+            let name = &block.buffer.name;
+            let index = match mesh.uniform_buffers.get(name).or_else(|| {
+                self.shader_buffers
+                    .get(&self.bound_shader)
+                    .and_then(|buffers| buffers.get(name))
+            }) {
+                Some(index) => index,
+                None => bail!(
+                    "buffer {} of shader {} is not bound",
+                    name,
+                    self.bound_shader
+                ),
+            };
+            let buffer = &self.buffer_arena[index.index];
+            if buffer.kind != block.buffer.kind || buffer.size < block.buffer.min_size as usize {
+                bail!(
+                    "buffer {} of shader {} needs a {:?} buffer of at least {} bytes",
+                    name,
+                    self.bound_shader,
+                    block.buffer.kind,
+                    block.buffer.min_size
+                )
+            }
+            let binding_point = i as u32 + 1;
+            let block_index = self
+                .context
+                .get_uniform_block_index(&bound_shader.program, &block.block_name);
+            self.context
+                .uniform_block_binding(&bound_shader.program, block_index, binding_point);
+            self.context.bind_buffer_base(
+                WebGl2RenderingContext::UNIFORM_BUFFER,
+                binding_point,
+                Some(&buffer.buffer),
+            );
+        }
+        Ok(())
+    }
     /// Very slow, todo: make finding uniform part of shader initilization
     pub fn draw_mesh(&mut self, push_data: Vec<u8>, mesh_index: &MeshIndex) -> Result<()> {
         let bound_shader = &self.shaders[&self.bound_shader];
@@ -306,6 +545,7 @@ impl Backend {
         self.context
             .uniform_matrix4fv_with_f32_array(loc.as_ref(), false, &float_arr);
         let mesh = &self.mesh_arena[mesh_index.index];
+        self.bind_uniform_blocks(mesh)?;
         let texture = match mesh.texture {
             DrawableTexture::Texture(index) => self.texture_arena[index.index].texture.clone(),
             DrawableTexture::Framebuffer(_) => todo!("draw framebuffer surfaces"),
@@ -320,24 +560,73 @@ impl Backend {
             .get_uniform_location(&bound_shader.program, &bound_shader.shader.texture_name);
         self.context.uniform1i(texture_loc.as_ref(), 0);
         self.context.bind_vertex_array(Some(&mesh.vao));
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&mesh.buffer));
         let offset = 0;
-        self.context.draw_arrays(
-            WebGl2RenderingContext::TRIANGLES,
+        self.context.draw_elements_with_i32(
+            mesh.draw_mode,
+            mesh.get_num_indices() as i32,
+            mesh.index_type,
             offset,
-            mesh.get_num_vertices() as i32,
         );
         self.context.bind_vertex_array(None);
-        self.context
-            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
 
         self.context
             .bind_texture(WebGl2RenderingContext::TEXTURE_2D, None);
         Ok(())
     }
 
-    pub fn build_framebuffer(&mut self, _: Vector2<u32>) -> Result<Framebuffer> {
+    /// Draws a line mesh with the debug shader. The mesh is uploaded into the same
+    /// buffers every frame as its layout never changes.
+    pub fn draw_debug(&mut self, mesh: MeshAsset, push_data: Vec<u8>) -> Result<()> {
+        let previous_shader = std::mem::replace(&mut self.bound_shader, DEBUG_SHADER.to_string());
+        self.shaders[DEBUG_SHADER].bind_shader(&mut self.context)?;
+        let result = self.upload_debug_mesh(mesh).and_then(|mesh_index| {
+            let draw = self.draw_mesh(push_data, &mesh_index);
+            self.debug_mesh = Some(mesh_index);
+            draw
+        });
+        self.bound_shader = previous_shader;
+        self.shaders[&self.bound_shader].bind_shader(&mut self.context)?;
+        result
+    }
+    fn upload_debug_mesh(&mut self, mesh: MeshAsset) -> Result<MeshIndex> {
+        if let Some(mesh_index) = self.debug_mesh.take() {
+            mesh.validate_lengths()?;
+            let gl_mesh = &mut self.mesh_arena[mesh_index.index];
+            // debug vertices are a single stream
+            self.context
+                .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&gl_mesh.buffers[0]));
+            self.context.buffer_data_with_u8_array(
+                WebGl2RenderingContext::ARRAY_BUFFER,
+                &mesh.vertices,
+                WebGl2RenderingContext::DYNAMIC_DRAW,
+            );
+            self.context
+                .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
+            // element array binding belongs to the vertex array object
+            self.context.bind_vertex_array(Some(&gl_mesh.vao));
+            let index_width = mesh.index_width();
+            self.context.buffer_data_with_u8_array(
+                WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+                &index_width.index_bytes(&mesh.indices),
+                WebGl2RenderingContext::DYNAMIC_DRAW,
+            );
+            self.context.bind_vertex_array(None);
+            gl_mesh.num_indices = mesh.num_indices();
+            gl_mesh.index_type = gl_index_type(index_width);
+            return Ok(mesh_index);
+        }
+        let texture = match self.debug_texture {
+            Some(texture) => texture,
+            None => {
+                let white = RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+                let texture = self.build_texture(&white)?;
+                self.debug_texture = Some(texture);
+                texture
+            }
+        };
+        self.build_mesh(mesh, GenericDrawableTexture::Texture(&texture))
+    }
+    pub fn build_framebuffer(&mut self, _: FramebufferDesc) -> Result<Framebuffer> {
         todo!("build framebuffer")
     }
     pub fn bind_shader(&mut self, _: GenericBindable<Framebuffer>, _: &str) -> Result<()> {
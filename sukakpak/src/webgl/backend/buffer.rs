@@ -0,0 +1,10 @@
+use super::super::super::BufferKind;
+use web_sys::WebGlBuffer;
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Uniform buffer, storage buffers are built but can not be read by webgl2 shaders
+pub struct Buffer {
+    pub buffer: WebGlBuffer,
+    pub kind: BufferKind,
+    /// size in bytes
+    pub size: usize,
+}
@@ -1,17 +1,26 @@
-use super::DrawableTexture;
+use super::{BufferIndex, DrawableTexture};
+use std::collections::HashMap;
 use web_sys::{WebGlBuffer, WebGlVertexArrayObject as VAO};
 #[derive(Debug, Clone, PartialEq, Eq)]
 /// Describes mesh data for drawing
 pub struct Mesh {
     pub vao: VAO,
-    pub buffer: WebGlBuffer,
+    /// one buffer per vertex stream
+    pub buffers: Vec<WebGlBuffer>,
+    pub index_buffer: WebGlBuffer,
     pub texture: DrawableTexture,
-    /// number of verticies to draw
-    pub num_vertices: usize,
+    /// number of indices to draw
+    pub num_indices: usize,
+    /// `UNSIGNED_SHORT` or `UNSIGNED_INT`
+    pub index_type: u32,
+    /// primitive mode passed to `draw_elements`
+    pub draw_mode: u32,
+    /// uniform buffers bound with `bind_mesh_buffer`
+    pub uniform_buffers: HashMap<String, BufferIndex>,
 }
 impl Mesh {
-    /// Gets the number of vertices of the mesh
-    pub fn get_num_vertices(&self) -> usize {
-        self.num_vertices
+    /// Gets the number of indices of the mesh
+    pub fn get_num_indices(&self) -> usize {
+        self.num_indices
     }
 }
@@ -84,4 +84,11 @@ impl ShaderModule {
             context,
         )
     }
+    /// Shader used by `ContextTrait::draw_debug`
+    pub fn debug_shader(context: &mut WebGl2RenderingContext) -> Result<Self> {
+        Self::from_json_str(
+            include_str!("../../../shaders/v2/debug_lines.ass_glsl"),
+            context,
+        )
+    }
 }
@@ -0,0 +1,60 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+/// Derives `sukakpak::VertexTrait`. Every field must implement
+/// `sukakpak::VertexAttribute`, field order is the `location` order of the layout.
+#[proc_macro_derive(Vertex)]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return syn::Error::new_spanned(&input.ident, "Vertex can only be derived for structs")
+                .to_compile_error()
+                .into()
+        }
+    };
+    let (types, accessors): (Vec<_>, Vec<_>) = match fields {
+        Fields::Named(named) => named
+            .named
+            .iter()
+            .map(|f| {
+                let name = f.ident.as_ref().unwrap();
+                (&f.ty, quote! {#name})
+            })
+            .unzip(),
+        Fields::Unnamed(unnamed) => unnamed
+            .unnamed
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let index = Index::from(i);
+                (&f.ty, quote! {#index})
+            })
+            .unzip(),
+        Fields::Unit => (vec![], vec![]),
+    };
+    if types.is_empty() {
+        return syn::Error::new_spanned(&input.ident, "Vertex must have at least one field")
+            .to_compile_error()
+            .into();
+    }
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::sukakpak::VertexTrait for #name #ty_generics #where_clause {
+            fn vertex_layout() -> ::sukakpak::VertexLayout {
+                ::sukakpak::VertexLayout {
+                    components: vec![
+                        #(<#types as ::sukakpak::VertexAttribute>::component()),*
+                    ],
+                }
+            }
+            fn write_bytes(&self, out: &mut Vec<u8>) {
+                #(::sukakpak::VertexAttribute::write_bytes(&self.#accessors, out);)*
+            }
+        }
+    }
+    .into()
+}
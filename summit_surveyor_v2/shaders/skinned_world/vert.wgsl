@@ -0,0 +1,53 @@
+struct VertexOutput{
+    [[location(0)]] tex_coord: vec2<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[builtin(position)]] position: vec4<f32>;
+};
+[[block]]
+struct Locals{
+    transform: mat4x4<f32>;
+};
+[[group(0),binding(3)]]
+var<uniform> locals: Locals;
+[[block]]
+struct Joints{
+    matrices: array<mat4x4<f32>>;
+};
+[[group(1),binding(0)]]
+var<storage, read> joints: Joints;
+[[stage(vertex)]]
+fn vs_main(
+	[[location(0)]] position: vec3<f32>,
+	[[location(1)]] tex_coord: vec2<f32>,
+	[[location(2)]] normal: vec3<f32>,
+	[[location(3)]] joint_indices: vec4<u32>,
+	[[location(4)]] weights: vec4<f32>,
+)->VertexOutput{
+    let rest_position = vec4<f32>(position,1.0);
+    let rest_normal = vec4<f32>(normal,0.0);
+    // naga can not add matrices so the skinned position and normal are blended
+    let skinned_position = joints.matrices[joint_indices.x]*rest_position*weights.x
+        +joints.matrices[joint_indices.y]*rest_position*weights.y
+        +joints.matrices[joint_indices.z]*rest_position*weights.z
+        +joints.matrices[joint_indices.w]*rest_position*weights.w;
+    let skinned_normal = joints.matrices[joint_indices.x]*rest_normal*weights.x
+        +joints.matrices[joint_indices.y]*rest_normal*weights.y
+        +joints.matrices[joint_indices.z]*rest_normal*weights.z
+        +joints.matrices[joint_indices.w]*rest_normal*weights.w;
+    var out: VertexOutput;
+    out.tex_coord=tex_coord;
+    out.normal=normalize(skinned_normal.xyz);
+    out.position = locals.transform*skinned_position;
+    return out;
+}
+[[group(0),binding(0)]]
+var mesh_texture: texture_2d<f32>;
+[[group(0),binding(1)]]
+var sampler: sampler;
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput)->[[location(0)]]vec4<f32>{
+    let sun_dir = normalize(vec3<f32>(1.0,1.0,0.0));
+    let texture= textureSample(mesh_texture,sampler,in.tex_coord);
+    let color = min(dot(in.normal,sun_dir)*texture.xyz+vec3<f32>(0.1,0.1,0.1),vec3<f32>(1.0,1.0,1.0));
+    return vec4<f32>(color,1.0);
+}
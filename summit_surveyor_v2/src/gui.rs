@@ -113,6 +113,7 @@ impl GuiSquare {
                         sukakpak::VertexComponent::Vec3F32,
                     ],
                 },
+                topology: sukakpak::Topology::TriangleList,
             },
             DrawableTexture::Texture(
                 texture_manager
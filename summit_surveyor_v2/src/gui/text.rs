@@ -139,6 +139,7 @@ impl TextBuilder {
                         sukakpak::VertexComponent::Vec3F32,
                     ],
                 },
+                topology: sukakpak::Topology::TriangleList,
             },
         )
     }
@@ -1,3 +1,4 @@
+use super::model::rigid;
 use super::prelude::{
     Camera, ContainerAlignment, EventCollector, EventListener, GraphLayer, GraphNode, GraphType,
     GraphWeight, GuiComponent, GuiSquare, GuiState, ModelRenderData, MouseButtonEvent, RenderLayer,
@@ -63,7 +64,7 @@ fn lift_tuple(
     let model = model_manager.insert(
         graphics
             .build_mesh(
-                sukakpak::MeshAsset::new_cube(),
+                rigid(&sukakpak::MeshAsset::new_cube()).expect("failed to skin lift mesh"),
                 DrawableTexture::Texture(&texture),
             )
             .expect("failed to build lift mesh"),
@@ -213,7 +214,7 @@ pub fn lift_builder_gui(
     let lift_model = model_manager.insert(
         context
             .build_mesh(
-                sukakpak::MeshAsset::new_cube(),
+                rigid(&sukakpak::MeshAsset::new_cube()).expect("failed to skin lift mesh"),
                 DrawableTexture::Texture(&texture),
             )
             .expect("failed to build mesh"),
@@ -235,7 +236,7 @@ pub fn lift_builder_gui(
     let top_lift_model = model_manager.insert(
         context
             .build_mesh(
-                sukakpak::MeshAsset::new_cube(),
+                rigid(&sukakpak::MeshAsset::new_cube()).expect("failed to skin lift mesh"),
                 DrawableTexture::Texture(&texture),
             )
             .expect("failed to build mesh"),
@@ -27,6 +27,8 @@ struct Game {
     world: World,
     resources: Resources,
     game_render_surface: ScreenPlane,
+    /// Kept alive for world meshes that are not animated, see `model::rigid`
+    _rigid_joints: sukakpak::Buffer,
 }
 pub mod prelude {
     pub use super::camera::{Camera, FPSCamera, Ray};
@@ -48,8 +50,10 @@ impl sukakpak::Renderable for Game {
             .load_shader("./shaders/gui_shader", "gui_shader")
             .expect("failed to load gui shader");
         context
-            .load_shader("./shaders/world", "world")
+            .load_shader(include_str!("../shaders/skinned_world.ass_spv"), "world")
             .expect("failed to load");
+        let rigid_joints =
+            model::bind_rigid_joints(&mut context, "world").expect("failed to bind joints");
 
         let mut model_manager: AssetManager<sukakpak::Mesh> = Default::default();
         let mut texture_manager: AssetManager<Texture> = Default::default();
@@ -144,6 +148,7 @@ impl sukakpak::Renderable for Game {
             world,
             resources,
             game_render_surface,
+            _rigid_joints: rigid_joints,
         }
     }
     fn render_frame(&mut self, events: &[Event], mut context: Context, delta_time: Duration) {
@@ -166,6 +171,7 @@ impl sukakpak::Renderable for Game {
             .add_system(gui::react_events_system())
             .add_system(model::render_model_system())
             .add_system(model::render_model_vec_system())
+            .add_system(model::render_lod_model_system())
             .add_system(terrain_camera_system())
             .build();
         game_renderng_schedule.execute(&mut self.world, &mut self.resources);
@@ -201,6 +207,13 @@ fn main() {
         default_size: Vector2::new(1000, 1000),
         name: "Summit Surveyor".to_string(),
         window_id: "canvas".to_string(),
+        vulkan_sdk_path: None,
+        pipeline_cache_dir: Some(std::path::PathBuf::from("cache")),
+        present_mode: sukakpak::PresentMode::NoVsync,
+        swapchain_images: None,
+        max_frames_in_flight: 2,
+        sample_count: sukakpak::SampleCount::X1,
+        device_preference: sukakpak::DevicePreference::Discrete,
     });
 }
 #[system]
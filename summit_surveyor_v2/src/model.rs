@@ -5,8 +5,10 @@ use std::sync::Mutex;
 use sukakpak::{
     anyhow::Result,
     image::{Rgba, RgbaImage},
-    nalgebra::Vector2,
-    Bindable, Context, ContextTrait, DrawableTexture, VertexComponent, VertexLayout,
+    joint_matrix_bytes,
+    nalgebra::{Matrix4, Vector2, Vector4},
+    with_skin, Bindable, BufferKind, Context, ContextTrait, DrawQueue, DrawableTexture,
+    FramebufferDesc, Frustum, LodMesh, MeshAsset, SampleCount, VertexComponent, VertexLayout,
 };
 pub struct ScreenPlane {
     pub framebuffer: sukakpak::Framebuffer,
@@ -18,7 +20,11 @@ pub fn build_screen_plane(
     screen_resolution: Vector2<u32>,
     z: f32,
 ) -> Result<ScreenPlane> {
-    let framebuffer = context.build_framebuffer(screen_resolution)?;
+    // the world is drawn to this framebuffer, multisampled to smooth terrain edges
+    let framebuffer = context.build_framebuffer(FramebufferDesc {
+        resolution: screen_resolution,
+        sample_count: SampleCount::X4,
+    })?;
     context.bind_shader(Bindable::UserFramebuffer(&framebuffer), shader_name);
 
     let vertices = [
@@ -44,12 +50,39 @@ pub fn build_screen_plane(
                     sukakpak::VertexComponent::Vec2F32,
                 ],
             },
+            topology: sukakpak::Topology::TriangleList,
         },
         sukakpak::DrawableTexture::Framebuffer(&framebuffer),
     )?;
     Ok(ScreenPlane { mesh, framebuffer })
 }
 
+/// The world shader skins every mesh, this binds all vertices of `mesh` to joint 0
+/// so it is drawn unchanged with the joints from `bind_rigid_joints`
+pub fn rigid(mesh: &MeshAsset) -> Result<MeshAsset> {
+    with_skin(mesh, |_| ([0; 4], [1.0, 0.0, 0.0, 0.0]))
+}
+/// Binds a single identity joint to `shader_name`, read by meshes that do not have
+/// their own joint buffer. The buffer must be kept alive while the shader is used.
+pub fn bind_rigid_joints(context: &mut Context, shader_name: &str) -> Result<sukakpak::Buffer> {
+    let joints = context.build_buffer(
+        BufferKind::Storage,
+        &joint_matrix_bytes(&[Matrix4::identity()]),
+    )?;
+    context.bind_shader_buffer(shader_name, "joints", &joints)?;
+    Ok(joints)
+}
+/// Checks if mesh is inside of the camera's view, meshes without bounds are always visible
+fn is_visible(camera: &dyn Camera, transform: &Transform, mesh: &sukakpak::Mesh) -> bool {
+    mesh.bounds()
+        .map(|bounds| Frustum::from_matrix(&camera.get_mat(transform)).intersects_bounds(&bounds))
+        .unwrap_or(true)
+}
+/// Distance of the model's origin from the camera, `matrix` is the model view projection
+/// matrix. Used to draw the world front to back.
+fn view_depth(matrix: &Matrix4<f32>) -> f32 {
+    (matrix * Vector4::new(0.0, 0.0, 0.0, 1.0)).w
+}
 #[system(for_each)]
 pub fn render_model_vec(
     mesh_vec: &Vec<(AssetHandle<sukakpak::Mesh>, Transform)>,
@@ -60,12 +93,17 @@ pub fn render_model_vec(
 ) {
     if render_data.get_render_layer() == RenderLayer::Main {
         for (model, transform) in mesh_vec.iter() {
-            graphics
-                .draw_mesh(
-                    camera.to_vec(transform),
-                    &manager.get(model).expect("model does not exist"),
-                )
-                .expect("failed to draw mesh");
+            let mesh = manager.get(model).expect("model does not exist");
+            if is_visible(camera.as_ref(), transform, mesh) {
+                graphics
+                    .draw_mesh_sorted(
+                        camera.to_vec(transform),
+                        mesh,
+                        DrawQueue::Opaque,
+                        view_depth(&camera.get_mat(transform)),
+                    )
+                    .expect("failed to draw mesh");
+            }
         }
     }
 }
@@ -108,11 +146,43 @@ pub fn render_model(
     #[resource] graphics: &mut Context,
 ) {
     if render_data.get_render_layer() == RenderLayer::Main {
-        graphics
-            .draw_mesh(
-                camera.to_vec(transform),
-                &manager.get(model).expect("model does not exist"),
-            )
-            .expect("failed to draw mesh");
+        let mesh = manager.get(model).expect("model does not exist");
+        if is_visible(camera.as_ref(), transform, mesh) {
+            graphics
+                .draw_mesh_sorted(
+                    camera.to_vec(transform),
+                    mesh,
+                    DrawQueue::Opaque,
+                    view_depth(&camera.get_mat(transform)),
+                )
+                .expect("failed to draw mesh");
+        }
+    }
+}
+/// Draws the level of detail matching the model's size on screen
+#[system(for_each)]
+pub fn render_lod_model(
+    model: &LodMesh<AssetHandle<sukakpak::Mesh>>,
+    render_data: &ModelRenderData,
+    transform: &Transform,
+    #[resource] camera: &mut Box<dyn Camera>,
+    #[resource] manager: &AssetManager<sukakpak::Mesh>,
+    #[resource] graphics: &mut Context,
+) {
+    if render_data.get_render_layer() == RenderLayer::Main {
+        let matrix = camera.get_mat(transform);
+        if Frustum::from_matrix(&matrix).intersects_sphere(&model.bounding_sphere()) {
+            let mesh = manager
+                .get(model.select(&matrix))
+                .expect("model does not exist");
+            graphics
+                .draw_mesh_sorted(
+                    camera.to_vec(transform),
+                    mesh,
+                    DrawQueue::Opaque,
+                    view_depth(&matrix),
+                )
+                .expect("failed to draw mesh");
+        }
     }
 }
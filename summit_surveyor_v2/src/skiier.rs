@@ -10,8 +10,10 @@ use std::{sync::Mutex, time::Duration};
 use sukakpak::{
     anyhow::Result,
     image::{Rgba, RgbaImage},
-    nalgebra::{Vector2, Vector3},
-    Context, ContextTrait, DrawableTexture, Texture,
+    joint_matrix_bytes, mesh_ops,
+    nalgebra::{Matrix4, UnitQuaternion, Vector2, Vector3, Vector4},
+    with_skin, AnimationClip, BufferKind, Channel, ChannelProperty, Context, ContextTrait,
+    DrawableTexture, EasyMesh, Interpolation, Joint, JointTransform, MeshAsset, Skeleton, Texture,
 };
 pub struct FollowPath {
     start: GraphNode,
@@ -35,6 +37,151 @@ impl FollowPath {
     pub fn at_end(&self) -> bool {
         self.t >= self.points.len() as f32
     }
+    /// Direction of the current segment, None at the end of the path
+    pub fn direction(&self) -> Option<Vector3<f32>> {
+        let start = self.points.get(self.t.floor() as usize)?;
+        let end = self.points.get(self.t.floor() as usize + 1)?;
+        (end - start).try_normalize(f32::EPSILON)
+    }
+}
+/// Animates a skiier carving turns while following a path. The legs move side
+/// to side and lean into the turn while the torso bends forward and leans back
+/// against them to stay upright. Joint matrices are written to `joints`, which
+/// the world shader reads to skin the skiier's mesh.
+pub struct Carve {
+    skeleton: Skeleton,
+    clip: AnimationClip,
+    time: f32,
+    joints: sukakpak::Buffer,
+}
+impl Carve {
+    /// Time in seconds for a left and right turn
+    const PERIOD: f32 = 2.0;
+    /// Distance moved to the side in each turn
+    const WIDTH: f32 = 0.4;
+    /// Angle the legs lean into the turn in radians
+    const LEAN: f32 = 0.35;
+    /// Fraction of the lean the torso undoes
+    const ANGULATION: f32 = 0.6;
+    /// Angle the torso bends forward in radians
+    const CROUCH: f32 = 0.3;
+    /// Height of the hips above the ground, the torso joint rotates about the hips
+    const HIP_HEIGHT: f32 = 0.35;
+    /// Vertices closer to the hips than this are blended between legs and torso
+    const HIP_BLEND: f32 = 0.1;
+    pub fn new(graphics: &mut Context) -> Result<Self> {
+        let hips = Vector3::new(0.0, Self::HIP_HEIGHT, 0.0);
+        let skeleton = Skeleton::new(
+            vec![
+                Joint {
+                    name: Some("legs".to_string()),
+                    parent: None,
+                    rest: JointTransform::identity(),
+                    inverse_bind_matrix: Matrix4::identity(),
+                },
+                Joint {
+                    name: Some("torso".to_string()),
+                    parent: Some(0),
+                    rest: JointTransform {
+                        translation: hips,
+                        ..JointTransform::identity()
+                    },
+                    inverse_bind_matrix: Matrix4::new_translation(&-hips),
+                },
+            ],
+            Matrix4::identity(),
+        )?;
+        let times: Vec<f32> = (0..=4).map(|i| i as f32 * Self::PERIOD / 4.0).collect();
+        // cubic spline keyframes with zero tangents ease in and out of each turn
+        let keys = |values: Vec<Vector4<f32>>| -> Vec<Vector4<f32>> {
+            values
+                .iter()
+                .flat_map(|v| [Vector4::zeros(), *v, Vector4::zeros()])
+                .collect()
+        };
+        let side = [0.0, 1.0, 0.0, -1.0, 0.0];
+        let translations = side
+            .iter()
+            .map(|s| Vector4::new(s * Self::WIDTH, 0.0, 0.0, 0.0))
+            .collect();
+        // leaning towards the center of the turn, which is the opposite side
+        let leans = side
+            .iter()
+            .map(|s| UnitQuaternion::from_axis_angle(&Vector3::z_axis(), s * Self::LEAN).coords)
+            .collect();
+        let crouch = UnitQuaternion::from_axis_angle(&Vector3::x_axis(), Self::CROUCH);
+        let torso = side
+            .iter()
+            .map(|s| {
+                let angulation = UnitQuaternion::from_axis_angle(
+                    &Vector3::z_axis(),
+                    -s * Self::LEAN * Self::ANGULATION,
+                );
+                (angulation * crouch).coords
+            })
+            .collect();
+        let clip = AnimationClip::new(
+            Some("carve".to_string()),
+            vec![
+                Channel {
+                    target: 0,
+                    property: ChannelProperty::Translation,
+                    interpolation: Interpolation::CubicSpline,
+                    times: times.clone(),
+                    values: keys(translations),
+                },
+                Channel {
+                    target: 0,
+                    property: ChannelProperty::Rotation,
+                    interpolation: Interpolation::CubicSpline,
+                    times: times.clone(),
+                    values: keys(leans),
+                },
+                Channel {
+                    target: 1,
+                    property: ChannelProperty::Rotation,
+                    interpolation: Interpolation::CubicSpline,
+                    times,
+                    values: keys(torso),
+                },
+            ],
+        )?;
+        let pose = clip.sample(&skeleton, 0.0, true);
+        let joints = graphics.build_buffer(
+            BufferKind::Storage,
+            &joint_matrix_bytes(&skeleton.joint_matrices(&pose)),
+        )?;
+        Ok(Self {
+            skeleton,
+            clip,
+            time: 0.0,
+            joints,
+        })
+    }
+    /// Capsule standing on the ground, skinned to the legs below the hips and the
+    /// torso above them
+    pub fn build_mesh(&self) -> Result<MeshAsset> {
+        // the capsule is 0.7 tall and centered on the hips
+        let mut capsule = EasyMesh::new_capsule(0.15, 0.4, 12, 4);
+        mesh_ops::transform(
+            &mut capsule,
+            &Matrix4::new_translation(&Vector3::new(0.0, Self::HIP_HEIGHT, 0.0)),
+        );
+        with_skin(&capsule.into(), |position| {
+            let torso =
+                ((position.y - Self::HIP_HEIGHT) / (2.0 * Self::HIP_BLEND) + 0.5).clamp(0.0, 1.0);
+            ([0, 1, 0, 0], [1.0 - torso, torso, 0.0, 0.0])
+        })
+    }
+    /// Advances animation and uploads the new joint matrices
+    pub fn advance(&mut self, delta_time: f32, graphics: &mut Context) -> Result<()> {
+        self.time += delta_time;
+        let pose = self.clip.sample(&self.skeleton, self.time, true);
+        graphics.update_buffer(
+            &self.joints,
+            &joint_matrix_bytes(&self.skeleton.joint_matrices(&pose)),
+        )
+    }
 }
 pub struct Skiier {}
 impl Skiier {
@@ -72,20 +219,19 @@ impl Skiier {
             100,
             Rgba::from([20, 200, 200, 200]),
         ))?;
-        let model = model_manager.insert(
-            r_ctx
-                .build_mesh(
-                    sukakpak::MeshAsset::new_cube(),
-                    DrawableTexture::Texture(&texture),
-                )
-                .expect("failed to build mesh"),
-        );
+        let carve = Carve::new(r_ctx)?;
+        let mut mesh = r_ctx
+            .build_mesh(carve.build_mesh()?, DrawableTexture::Texture(&texture))
+            .expect("failed to build mesh");
+        r_ctx.bind_mesh_buffer(&mut mesh, "joints", &carve.joints)?;
+        let model = model_manager.insert(mesh);
         texture_manager.insert(texture);
         println!("path: {}", path);
         world.push((
             ModelRenderData::default(),
             Skiier {},
             follow,
+            carve,
             transform,
             model,
             decison_tree,
@@ -128,10 +274,23 @@ pub fn skiier_path(
 #[system(for_each)]
 pub fn skiier(
     path: &mut FollowPath,
+    carve: &mut Carve,
     transform: &mut Transform,
     #[resource] duration: &mut Duration,
+    #[resource] graphics: &mut Context,
 ) {
+    let position = path.incr(1000.0 * duration.as_secs_f32());
+    carve
+        .advance(duration.as_secs_f32(), graphics)
+        .expect("failed to animate skiier");
+    // turns are relative to the direction of travel along the ground
+    let heading = path
+        .direction()
+        .and_then(|d| Vector3::new(d.x, 0.0, d.z).try_normalize(f32::EPSILON))
+        .map(|d| UnitQuaternion::face_towards(&d, &Vector3::y()))
+        .unwrap_or_else(UnitQuaternion::identity);
     *transform = transform
         .clone()
-        .set_translation(path.incr(1000.0 * duration.as_secs_f32()));
+        .set_translation(position)
+        .set_rotation(heading);
 }
@@ -1,3 +1,4 @@
+use super::model::rigid;
 use super::prelude::{
     Camera, EventCollector, GraphLayer, GraphNode, GraphType, GraphWeight, ModelRenderData, Ray,
     RenderLayer, Transform,
@@ -9,13 +10,32 @@ use parry3d::{
     query::{Ray as PRay, RayCast},
     shape::HeightField as PHeightField,
 };
-use std::sync::Mutex;
+use std::{ops::RangeInclusive, sync::Mutex};
 use sukakpak::{
     anyhow::Result,
     image::{Rgba, RgbaImage},
+    mesh_ops,
     nalgebra::{DMatrix, Isometry3, Point3, Vector2, Vector3},
-    Context, ContextTrait, DrawableTexture,
+    Context, ContextTrait, DrawableTexture, EasyMesh, LodMesh, LodThreshold, MeshAsset,
 };
+/// Side length in quads of the chunks terrain is drawn in, each chunk picks its own
+/// level of detail
+const CHUNK_SIZE: usize = 25;
+/// Levels of detail used for terrain chunks, distant chunks are drawn with fewer triangles
+const TERRAIN_LODS: [LodThreshold; 3] = [
+    LodThreshold {
+        ratio: 1.0,
+        min_screen_size: 0.4,
+    },
+    LodThreshold {
+        ratio: 0.25,
+        min_screen_size: 0.15,
+    },
+    LodThreshold {
+        ratio: 0.05,
+        min_screen_size: 0.0,
+    },
+];
 pub struct Grid<T> {
     data: Vec<T>,
     dimensions: Vector2<usize>,
@@ -113,7 +133,7 @@ impl Terrain {
         context: &mut Context,
     ) -> Result<()> {
         let graph_layer: Box<dyn GraphLayer> = (&self).into();
-        let (mesh, texture) = {
+        let (chunks, texture) = {
             let mut layers = resources.get_mut_or_insert::<Vec<Mutex<Box<dyn GraphLayer>>>>(vec![]);
             layers.push(Mutex::new(graph_layer));
             let texture = context.build_texture(&RgbaImage::from_pixel(
@@ -122,135 +142,62 @@ impl Terrain {
                 Rgba::from([200, 200, 200, 200]),
             ))?;
 
-            let mut vertices = vec![];
-            for x in 0..self.dimensions.x - 1 {
-                for y in 0..self.dimensions.y - 1 {
-                    let x0_y0 = Vector3::new(x as f32, self.heights[(x, y)], y as f32);
-                    let x0_y1 = Vector3::new(x as f32, self.heights[(x, y + 1)], y as f32 + 1.0);
-                    let x1_y0 = Vector3::new(x as f32 + 1.0, self.heights[(x + 1, y)], y as f32);
-                    let x1_y1 =
-                        Vector3::new(x as f32 + 1.0, self.heights[(x + 1, y + 1)], y as f32 + 1.0);
-                    let triangle0_normal = (x0_y1 - x0_y0).cross(&(x1_y0 - x0_y0)).normalize();
-                    let triangle1_normal = (x1_y0 - x1_y1).cross(&(x0_y1 - x1_y1)).normalize();
-
-                    //triangle 0
-                    vertices.push([
-                        //position:
-                        x0_y0.x,
-                        x0_y0.y,
-                        x0_y0.z,
-                        //uv
-                        0.0,
-                        0.0,
-                        //normal
-                        triangle0_normal.x,
-                        triangle0_normal.y,
-                        triangle0_normal.z,
-                    ]);
-                    vertices.push([
-                        //position:
-                        x0_y1.x,
-                        x0_y1.y,
-                        x0_y1.z,
-                        //uv
-                        0.0,
-                        1.0,
-                        //normal
-                        triangle0_normal.x,
-                        triangle0_normal.y,
-                        triangle0_normal.z,
-                    ]);
-                    vertices.push([
-                        //position:
-                        x1_y0.x,
-                        x1_y0.y,
-                        x1_y0.z,
-                        //uv
-                        1.0,
-                        0.0,
-                        //normal
-                        triangle0_normal.x,
-                        triangle0_normal.y,
-                        triangle0_normal.z,
-                    ]);
-                    //triangle 1
-                    vertices.push([
-                        //position:
-                        x0_y1.x,
-                        x0_y1.y,
-                        x0_y1.z,
-                        //uv
-                        0.0,
-                        1.0,
-                        //normal
-                        triangle1_normal.x,
-                        triangle1_normal.y,
-                        triangle1_normal.z,
-                    ]);
-                    vertices.push([
-                        //position:
-                        x1_y1.x,
-                        x1_y1.y,
-                        x1_y1.z,
-                        //uv
-                        1.0,
-                        1.0,
-                        //normal
-                        triangle1_normal.x,
-                        triangle1_normal.y,
-                        triangle1_normal.z,
-                    ]);
-                    vertices.push([
-                        //position:
-                        x1_y0.x,
-                        x1_y0.y,
-                        x1_y0.z,
-                        //uv
-                        1.0,
-                        0.0,
-                        //normal
-                        triangle1_normal.x,
-                        triangle1_normal.y,
-                        triangle1_normal.z,
-                    ]);
+            let heights = &self.heights;
+            let dimensions = self.dimensions;
+            let positions: Vec<Vector3<f32>> = (0..dimensions.x)
+                .flat_map(|x| {
+                    (0..dimensions.y)
+                        .map(move |y| Vector3::new(x as f32, heights[(x, y)], y as f32))
+                })
+                .collect();
+            let indices = grid_indices(0..=dimensions.x - 1, 0..=dimensions.y - 1);
+            let mut terrain_mesh = EasyMesh::from_positions(&positions, indices);
+            for vertex in terrain_mesh.vertices.iter_mut() {
+                vertex.uv = Vector2::new(vertex.position.x, vertex.position.z);
+            }
+            let mut chunks = vec![];
+            for chunk_x in (0..dimensions.x - 1).step_by(CHUNK_SIZE) {
+                for chunk_y in (0..dimensions.y - 1).step_by(CHUNK_SIZE) {
+                    let xs = chunk_x..=(chunk_x + CHUNK_SIZE).min(dimensions.x - 1);
+                    let ys = chunk_y..=(chunk_y + CHUNK_SIZE).min(dimensions.y - 1);
+                    // vertices are copied from the whole terrain so normals match across
+                    // chunk borders
+                    let vertices = xs
+                        .clone()
+                        .flat_map(|x| ys.clone().map(move |y| (x, y)))
+                        .map(|(x, y)| terrain_mesh.vertices[x * dimensions.y + y])
+                        .collect();
+                    let mut mesh: MeshAsset = EasyMesh {
+                        vertices,
+                        indices: grid_indices(0..=xs.end() - xs.start(), 0..=ys.end() - ys.start()),
+                    }
+                    .into();
+                    mesh_ops::optimize_vertex_cache(&mut mesh);
+                    chunks.push(LodMesh::build(&mesh, &TERRAIN_LODS, |mut level| {
+                        mesh_ops::optimize_vertex_cache(&mut level);
+                        let level = context
+                            .build_mesh(rigid(&level)?, DrawableTexture::Texture(&texture))?;
+                        Ok(model_manager.insert(level))
+                    })?);
                 }
             }
-            let indices = (0..self.dimensions.x * self.dimensions.y * 6)
-                .map(|i| i as u32)
-                .collect();
-
-            let mesh = sukakpak::MeshAsset {
-                indices,
-                vertices: vertices
-                    .iter()
-                    .flatten()
-                    .map(|f| f.to_ne_bytes())
-                    .flatten()
-                    .collect(),
-                vertex_layout: sukakpak::VertexLayout {
-                    components: vec![
-                        sukakpak::VertexComponent::Vec3F32,
-                        sukakpak::VertexComponent::Vec2F32,
-                        sukakpak::VertexComponent::Vec3F32,
-                    ],
-                },
-            };
-
-            let model = context
-                .build_mesh(mesh, DrawableTexture::Texture(&texture))
-                .expect("failed to build mesh");
-            (model, texture)
+            (chunks, texture)
         };
 
-        let model = model_manager.insert(mesh);
-
-        world.push((
-            InsertableTerrain {},
-            Transform::default(),
-            ModelRenderData::default(),
-            model,
-            texture,
-        ));
+        world.push((InsertableTerrain {}, texture));
+        world.extend(
+            chunks
+                .into_iter()
+                .map(|model| {
+                    (
+                        InsertableTerrain {},
+                        Transform::default(),
+                        ModelRenderData::default(),
+                        model,
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
         resources.insert(self);
         Ok(())
     }
@@ -286,6 +233,20 @@ impl Terrain {
         }
     }
 }
+/// Indices of two triangles per quad of a grid of vertices, vertex `(x, y)` is at
+/// `x * ys.len() + y`
+fn grid_indices(xs: RangeInclusive<usize>, ys: RangeInclusive<usize>) -> Vec<u32> {
+    let height = ys.end() - ys.start() + 1;
+    let index = |x: usize, y: usize| ((x - xs.start()) * height + y - ys.start()) as u32;
+    let mut indices = vec![];
+    for x in *xs.start()..*xs.end() {
+        for y in *ys.start()..*ys.end() {
+            indices.extend_from_slice(&[index(x, y), index(x, y + 1), index(x + 1, y)]);
+            indices.extend_from_slice(&[index(x, y + 1), index(x + 1, y + 1), index(x + 1, y)]);
+        }
+    }
+    indices
+}
 struct TerrainWeight(f32);
 pub struct TerrainGraphLayer {
     grid: Grid<TerrainWeight>,
@@ -308,7 +269,7 @@ pub fn insert_highlited(
     let model = model_manager.insert(
         graphics
             .build_mesh(
-                sukakpak::MeshAsset::new_cube(),
+                rigid(&sukakpak::MeshAsset::new_cube()).expect("failed to skin highlight mesh"),
                 DrawableTexture::Texture(&texture),
             )
             .expect("failed to build lift mesh"),
@@ -1,4 +1,4 @@
-use sukakpak::nalgebra::{Matrix4, Vector3};
+use sukakpak::nalgebra::{Matrix4, UnitQuaternion, Vector3};
 #[derive(Debug, Clone, PartialEq)]
 pub struct Transform {
     position: Vector3<f32>,
@@ -54,6 +54,17 @@ impl Transform {
             roll: self.roll,
         }
     }
+    /// Sets roll, pitch and yaw from a rotation
+    pub fn set_rotation(self, rotation: UnitQuaternion<f32>) -> Self {
+        let (roll, pitch, yaw) = rotation.euler_angles();
+        Self {
+            scale: self.scale,
+            pitch,
+            position: self.position,
+            yaw,
+            roll,
+        }
+    }
     /// Translates the transform by given delta
     pub fn translate(self, delta: Vector3<f32>) -> Self {
         Self {